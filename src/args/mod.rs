@@ -0,0 +1,431 @@
+use super::bounds::Export;
+use super::cluster::Address;
+use super::disk::{Codec, DEFAULT_ZLIB_LEVEL};
+use super::frontier::Priority;
+use super::ui::UI;
+
+use std::slice::Iter;
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Interactive,
+    Search(Options),
+    Verify(String, Option<usize>),
+    Split(SplitOptions),
+    Merge(String, Vec<Export>),
+    Coordinate(CoordinateOptions),
+    Help,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SplitOptions {
+    pub n: usize,
+    pub depth: usize,
+    pub jobs_dir: String,
+    pub known_bounds: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CoordinateOptions {
+    pub n: usize,
+    pub depth: usize,
+    pub address: Address,
+    pub known_bounds: Option<String>,
+    pub exports: Vec<Export>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub n: usize,
+    pub memory: f64,
+    pub codec: Codec,
+    pub verbose: bool,
+    pub scratch_dir: String,
+    pub witnesses: Option<String>,
+    pub checkpoint: Option<f64>,
+    pub status: Option<f64>,
+    pub resume: bool,
+    pub symmetry: bool,
+    pub drop_duplicates: bool,
+    pub dominance: Option<usize>,
+    pub priority: Priority,
+    pub batch: usize,
+    pub exports: Vec<Export>,
+    pub known_bounds: Option<String>,
+    pub log: Option<String>,
+    pub job: Option<String>,
+    pub connect: Option<Address>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            n: 5,
+            memory: 12.,
+            codec: Codec::Raw,
+            verbose: false,
+            scratch_dir: "scratch-files".to_string(),
+            witnesses: None,
+            checkpoint: None,
+            status: Some(60.),
+            resume: false,
+            symmetry: false,
+            drop_duplicates: false,
+            dominance: None,
+            priority: Priority::Fifo,
+            batch: 1,
+            exports: vec![],
+            known_bounds: None,
+            log: None,
+            job: None,
+            connect: None,
+        }
+    }
+}
+
+pub struct Args { }
+
+impl Args {
+    pub fn parse(args: &[String]) -> Result<Command, String> {
+        let (subcommand, flags) = match args.split_first() {
+            None => return Ok(Command::Interactive),
+            Some((first, rest)) if !first.starts_with('-') => (first.as_str(), rest),
+            Some(_) => ("search", args),
+        };
+
+        match subcommand {
+            "search" => Self::parse_search(flags),
+            "verify" => Self::parse_verify(flags),
+            "split" => Self::parse_split(flags),
+            "merge" => Self::parse_merge(flags),
+            "coordinate" => Self::parse_coordinate(flags),
+            "help" => Ok(Command::Help),
+            other => Err(format!("Unknown subcommand '{}'.", other)),
+        }
+    }
+
+    pub fn usage() -> &'static str {
+        "\
+Usage: leaps-and-bounds [search] [OPTIONS]
+       leaps-and-bounds verify <string> [--n <symbols>]
+       leaps-and-bounds split --depth <depth> --jobs <dir> [--n <symbols>] [--known-bounds <path>]
+       leaps-and-bounds merge --jobs <dir> [--export <path>]...
+       leaps-and-bounds coordinate --listen <address> [--depth <depth>] [--n <symbols>]
+                        [--known-bounds <path>] [--export <path>]...
+       leaps-and-bounds help
+
+Runs interactively if no arguments are given. The verify subcommand counts the
+permutations and wasted symbols in a string, e.g. 123412314231243121342132413214321
+
+The split subcommand writes a job for each string of the seed expanded <depth>
+times, to be searched separately with --job. Each job should --export its bounds
+next to its job file, e.g. job-00001.json, so that merge can combine them.
+
+The coordinate subcommand splits the search in the same way (default depth: 1)
+and hands the jobs out to workers that connect to it with --connect. Workers
+share the bounds they find with each other while they run, and give some of
+their candidates to idle workers when there are no jobs left. The address is
+<host>:<port> or the path of a Unix socket.
+
+Options:
+  --n <symbols>          How many symbols the string should contain (default: 5)
+  --memory <size>        How much memory the tool may use, e.g. 12G or 512M (default: 12G)
+  --codec <codec>        Compress scratch files with none, zlib, zlib:<level> from
+                         0 to 9, or bitset, which is faster than zlib (default: none)
+  --gzip[=yes|no]        The same as --codec zlib or --codec none
+  --verbose[=yes|no]     Print each event in the search as it happens (default: no)
+  --scratch-dir <path>   Where to write scratch files (default: scratch-files)
+  --witnesses <path>     Append a string that achieves each bound to this file
+                         (uses 9 bytes of scratch space per candidate)
+  --checkpoint <minutes> Save a checkpoint to the scratch directory this often
+  --status <seconds|no>  Print the progress of the search this often (default: 60)
+  --resume               Continue from the checkpoint in the scratch directory
+  --symmetry[=yes|no]    Collapse candidates that are the same up to renaming
+                         their symbols (default: no)
+  --drop-duplicates[=yes|no]
+                         Skip candidates that have already been reached by a
+                         different path (default: no, implied by --symmetry)
+  --dominance <count>    Drop candidates that can't do better than one of the
+                         last <count> candidates added with the same tail
+  --priority <priority>  Break ties between candidates with the same waste and
+                         permutations by fifo, tail, reachable or random:<seed>
+                         (default: fifo, the fastest)
+  --batch <count>        Expand up to <count> candidates from the same bucket at
+                         once on all cores (default: 1, one at a time)
+  --export <path>        Write the bounds to this file each time one is found, as
+                         .json, .csv or .txt for an OEIS b-file (can be repeated)
+  --known-bounds <path>  Start from bounds in a file written by --export, trusting
+                         them to prune the search straight away
+  --log <path>           Write each bound, prune, unprune, offload and onload to
+                         this file as JSON Lines (appended to when resuming)
+  --job <path>           Only search the strings that start with the prefix in a
+                         job written by split, starting from the job's bounds
+  --connect <address>    Search jobs from a coordinator until there are none left,
+                         writing scratch files to --scratch-dir for each in turn
+  -h, --help             Print this message"
+    }
+
+    fn parse_search(flags: &[String]) -> Result<Command, String> {
+        let mut options = Options::default();
+        let mut flags = flags.iter();
+
+        while let Some(flag) = flags.next() {
+            let (name, inline_value) = Self::split(flag);
+
+            match name {
+                "--n" => {
+                    let value = Self::value(name, inline_value, &mut flags)?;
+                    options.n = UI::parse_n(&value).map_err(|e| Self::invalid(name, e))?;
+                },
+                "--memory" => {
+                    let value = Self::value(name, inline_value, &mut flags)?;
+                    options.memory = UI::parse_memory(&value).map_err(|e| Self::invalid(name, e))?;
+                },
+                "--codec" => {
+                    let value = Self::value(name, inline_value, &mut flags)?;
+                    options.codec = UI::parse_codec(&value).map_err(|e| Self::invalid(name, e))?;
+                },
+                "--gzip" => {
+                    options.codec = match Self::switch(name, inline_value)? {
+                        true => Codec::Zlib(DEFAULT_ZLIB_LEVEL),
+                        false => Codec::Raw,
+                    };
+                },
+                "--verbose" => {
+                    options.verbose = Self::switch(name, inline_value)?;
+                },
+                "--scratch-dir" => {
+                    let value = Self::value(name, inline_value, &mut flags)?;
+                    options.scratch_dir = UI::parse_path(&value).map_err(|e| Self::invalid(name, e))?;
+                },
+                "--witnesses" => {
+                    let value = Self::value(name, inline_value, &mut flags)?;
+                    options.witnesses = Some(UI::parse_path(&value).map_err(|e| Self::invalid(name, e))?);
+                },
+                "--checkpoint" => {
+                    let value = Self::value(name, inline_value, &mut flags)?;
+                    options.checkpoint = Some(UI::parse_minutes(&value).map_err(|e| Self::invalid(name, e))?);
+                },
+                "--status" => {
+                    let value = Self::value(name, inline_value, &mut flags)?;
+                    options.status = UI::parse_status(&value).map_err(|e| Self::invalid(name, e))?;
+                },
+                "--resume" => {
+                    options.resume = Self::switch(name, inline_value)?;
+                },
+                "--symmetry" => {
+                    options.symmetry = Self::switch(name, inline_value)?;
+                },
+                "--drop-duplicates" => {
+                    options.drop_duplicates = Self::switch(name, inline_value)?;
+                },
+                "--dominance" => {
+                    let value = Self::value(name, inline_value, &mut flags)?;
+                    options.dominance = Some(UI::parse_count(&value).map_err(|e| Self::invalid(name, e))?);
+                },
+                "--priority" => {
+                    let value = Self::value(name, inline_value, &mut flags)?;
+                    options.priority = UI::parse_priority(&value).map_err(|e| Self::invalid(name, e))?;
+                },
+                "--batch" => {
+                    let value = Self::value(name, inline_value, &mut flags)?;
+                    options.batch = UI::parse_count(&value).map_err(|e| Self::invalid(name, e))?;
+                },
+                "--export" => {
+                    let value = Self::value(name, inline_value, &mut flags)?;
+                    options.exports.push(UI::parse_export(&value).map_err(|e| Self::invalid(name, e))?);
+                },
+                "--job" => {
+                    let value = Self::value(name, inline_value, &mut flags)?;
+                    options.job = Some(UI::parse_path(&value).map_err(|e| Self::invalid(name, e))?);
+                },
+                "--known-bounds" => {
+                    let value = Self::value(name, inline_value, &mut flags)?;
+                    options.known_bounds = Some(UI::parse_known_bounds(&value).map_err(|e| Self::invalid(name, e))?);
+                },
+                "--connect" => {
+                    let value = Self::value(name, inline_value, &mut flags)?;
+                    options.connect = Some(UI::parse_address(&value).map_err(|e| Self::invalid(name, e))?);
+                },
+                "--log" => {
+                    let value = Self::value(name, inline_value, &mut flags)?;
+                    options.log = Some(UI::parse_path(&value).map_err(|e| Self::invalid(name, e))?);
+                },
+                "-h" | "--help" => return Ok(Command::Help),
+                _ => return Err(format!("Unknown option '{}'.", name)),
+            }
+        }
+
+        if options.resume && options.known_bounds.is_some() {
+            return Err("Option '--known-bounds' can't be used with '--resume' because the checkpoint has the bounds.".to_string());
+        }
+
+        if options.job.is_some() && options.known_bounds.is_some() {
+            return Err("Option '--known-bounds' can't be used with '--job' because the job has the bounds. Pass it to split instead.".to_string());
+        }
+
+        if options.job.is_some() && options.witnesses.is_some() {
+            return Err("Option '--witnesses' can't be used with '--job' because the strings wouldn't include the prefix.".to_string());
+        }
+
+        if options.connect.is_some() {
+            let conflicts = [
+                ("--job", options.job.is_some()),
+                ("--known-bounds", options.known_bounds.is_some()),
+                ("--witnesses", options.witnesses.is_some()),
+                ("--resume", options.resume),
+                ("--checkpoint", options.checkpoint.is_some()),
+                ("--export", !options.exports.is_empty()),
+            ];
+
+            if let Some((flag, _)) = conflicts.iter().find(|(_, given)| *given) {
+                return Err(format!("Option '{}' can't be used with '--connect' because the coordinator hands out the jobs and merges their bounds.", flag));
+            }
+        }
+
+        Ok(Command::Search(options))
+    }
+
+    fn parse_split(flags: &[String]) -> Result<Command, String> {
+        let mut n = Options::default().n;
+        let mut depth = None;
+        let mut jobs_dir = None;
+        let mut known_bounds = None;
+        let mut flags = flags.iter();
+
+        while let Some(flag) = flags.next() {
+            let (name, inline_value) = Self::split(flag);
+            let value = match name {
+                "-h" | "--help" => return Ok(Command::Help),
+                _ => Self::value(name, inline_value, &mut flags)?,
+            };
+
+            match name {
+                "--n" => n = UI::parse_n(&value).map_err(|e| Self::invalid(name, e))?,
+                "--depth" => depth = Some(UI::parse_count(&value).map_err(|e| Self::invalid(name, e))?),
+                "--jobs" => jobs_dir = Some(UI::parse_path(&value).map_err(|e| Self::invalid(name, e))?),
+                "--known-bounds" => known_bounds = Some(UI::parse_known_bounds(&value).map_err(|e| Self::invalid(name, e))?),
+                _ => return Err(format!("Unknown option '{}'.", name)),
+            }
+        }
+
+        match (depth, jobs_dir) {
+            (Some(depth), Some(jobs_dir)) => Ok(Command::Split(SplitOptions { n, depth, jobs_dir, known_bounds })),
+            _ => Err("The split subcommand requires --depth and --jobs.".to_string()),
+        }
+    }
+
+    fn parse_merge(flags: &[String]) -> Result<Command, String> {
+        let mut jobs_dir = None;
+        let mut exports = vec![];
+        let mut flags = flags.iter();
+
+        while let Some(flag) = flags.next() {
+            let (name, inline_value) = Self::split(flag);
+            let value = match name {
+                "-h" | "--help" => return Ok(Command::Help),
+                _ => Self::value(name, inline_value, &mut flags)?,
+            };
+
+            match name {
+                "--jobs" => jobs_dir = Some(UI::parse_path(&value).map_err(|e| Self::invalid(name, e))?),
+                "--export" => exports.push(UI::parse_export(&value).map_err(|e| Self::invalid(name, e))?),
+                _ => return Err(format!("Unknown option '{}'.", name)),
+            }
+        }
+
+        match jobs_dir {
+            Some(jobs_dir) => Ok(Command::Merge(jobs_dir, exports)),
+            None => Err("The merge subcommand requires --jobs.".to_string()),
+        }
+    }
+
+    fn parse_coordinate(flags: &[String]) -> Result<Command, String> {
+        let mut n = Options::default().n;
+        let mut depth = 1;
+        let mut address = None;
+        let mut known_bounds = None;
+        let mut exports = vec![];
+        let mut flags = flags.iter();
+
+        while let Some(flag) = flags.next() {
+            let (name, inline_value) = Self::split(flag);
+            let value = match name {
+                "-h" | "--help" => return Ok(Command::Help),
+                _ => Self::value(name, inline_value, &mut flags)?,
+            };
+
+            match name {
+                "--n" => n = UI::parse_n(&value).map_err(|e| Self::invalid(name, e))?,
+                "--depth" => depth = UI::parse_count(&value).map_err(|e| Self::invalid(name, e))?,
+                "--listen" => address = Some(UI::parse_address(&value).map_err(|e| Self::invalid(name, e))?),
+                "--known-bounds" => known_bounds = Some(UI::parse_known_bounds(&value).map_err(|e| Self::invalid(name, e))?),
+                "--export" => exports.push(UI::parse_export(&value).map_err(|e| Self::invalid(name, e))?),
+                _ => return Err(format!("Unknown option '{}'.", name)),
+            }
+        }
+
+        match address {
+            Some(address) => Ok(Command::Coordinate(CoordinateOptions { n, depth, address, known_bounds, exports })),
+            None => Err("The coordinate subcommand requires --listen.".to_string()),
+        }
+    }
+
+    fn parse_verify(flags: &[String]) -> Result<Command, String> {
+        let mut string = None;
+        let mut n = None;
+        let mut flags = flags.iter();
+
+        while let Some(flag) = flags.next() {
+            let (name, inline_value) = Self::split(flag);
+
+            match name {
+                "--n" => {
+                    let value = Self::value(name, inline_value, &mut flags)?;
+                    n = Some(UI::parse_n(&value).map_err(|e| Self::invalid(name, e))?);
+                },
+                "-h" | "--help" => return Ok(Command::Help),
+                _ if name.starts_with('-') => return Err(format!("Unknown option '{}'.", name)),
+                _ if string.is_none() => string = Some(flag.clone()),
+                _ => return Err(format!("Unexpected argument '{}'.", flag)),
+            }
+        }
+
+        match string {
+            Some(string) => Ok(Command::Verify(string, n)),
+            None => Err("The verify subcommand requires a string.".to_string()),
+        }
+    }
+
+    fn split(flag: &str) -> (&str, Option<&str>) {
+        match flag.find('=') {
+            Some(index) => (&flag[..index], Some(&flag[index + 1..])),
+            None => (flag, None),
+        }
+    }
+
+    fn value(name: &str, inline_value: Option<&str>, flags: &mut Iter<String>) -> Result<String, String> {
+        if let Some(value) = inline_value {
+            return Ok(value.to_string());
+        }
+
+        match flags.next() {
+            Some(value) if !value.starts_with("--") => Ok(value.clone()),
+            _ => Err(format!("Option '{}' requires a value.", name)),
+        }
+    }
+
+    fn switch(name: &str, inline_value: Option<&str>) -> Result<bool, String> {
+        match inline_value {
+            None => Ok(true),
+            Some(value) => UI::parse_boolean(value).map_err(|e| Self::invalid(name, e)),
+        }
+    }
+
+    fn invalid(name: &str, error: String) -> String {
+        format!("Invalid value for '{}': {}", name, error)
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,186 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use super::*;
+use super::super::bounds::Format;
+use super::super::cluster::Address;
+
+type Subject = Args;
+
+fn parse(args: &[&str]) -> Result<Command, String> {
+    let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+    Subject::parse(&args)
+}
+
+fn options(args: &[&str]) -> Options {
+    match parse(args) {
+        Ok(Command::Search(options)) => options,
+        other => panic!("expected search options, got {:?}", other),
+    }
+}
+
+mod parse {
+    use super::*;
+
+    #[test]
+    fn it_runs_interactively_if_no_arguments_are_given() {
+        assert_eq!(parse(&[]), Ok(Command::Interactive));
+    }
+
+    #[test]
+    fn it_uses_the_defaults_for_options_that_are_not_given() {
+        assert_eq!(options(&["search"]), Options::default());
+        assert_eq!(options(&["--gzip"]), Options { codec: Codec::Zlib(6), ..Options::default() });
+    }
+
+    #[test]
+    fn it_parses_all_of_the_options() {
+        let actual = options(&[
+            "--n", "4", "--memory", "512M", "--codec", "bitset", "--verbose",
+            "--scratch-dir", "/tmp/x", "--witnesses", "/tmp/w.txt",
+            "--checkpoint", "30", "--status", "5", "--resume", "--symmetry", "--drop-duplicates",
+            "--dominance", "16", "--export", "b.txt", "--export=b.json", "--log", "events.jsonl",
+            "--priority", "random:3", "--batch", "256",
+        ]);
+
+        assert_eq!(actual.n, 4);
+        assert_eq!(actual.memory, 0.5);
+        assert_eq!(actual.codec, Codec::Bitset);
+        assert_eq!(actual.verbose, true);
+        assert_eq!(actual.scratch_dir, "/tmp/x");
+        assert_eq!(actual.witnesses, Some("/tmp/w.txt".to_string()));
+        assert_eq!(actual.checkpoint, Some(30.));
+        assert_eq!(actual.status, Some(5.));
+        assert_eq!(actual.resume, true);
+        assert_eq!(actual.symmetry, true);
+        assert_eq!(actual.drop_duplicates, true);
+        assert_eq!(actual.dominance, Some(16));
+        assert_eq!(actual.priority, Priority::Random(3));
+        assert_eq!(actual.batch, 256);
+        assert_eq!(actual.log, Some("events.jsonl".to_string()));
+        assert_eq!(actual.exports.iter().map(|e| e.format).collect::<Vec<_>>(), &[Format::BFile, Format::Json]);
+    }
+
+    #[test]
+    fn it_accepts_values_after_an_equals_sign() {
+        let actual = options(&["search", "--n=6", "--memory=2G", "--codec=zlib:9", "--verbose=yes"]);
+
+        assert_eq!(actual.n, 6);
+        assert_eq!(actual.memory, 2.);
+        assert_eq!(actual.codec, Codec::Zlib(9));
+        assert_eq!(actual.verbose, true);
+    }
+
+    #[test]
+    fn it_turns_off_the_status_line() {
+        assert_eq!(options(&["--status=no"]).status, None);
+    }
+
+    #[test]
+    fn it_parses_the_path_to_known_bounds() {
+        let actual = options(&["--known-bounds", "b000001.txt"]);
+        assert_eq!(actual.known_bounds, Some("b000001.txt".to_string()));
+    }
+
+    #[test]
+    fn it_returns_help_for_the_help_subcommand_or_flag() {
+        assert_eq!(parse(&["help"]), Ok(Command::Help));
+        assert_eq!(parse(&["--help"]), Ok(Command::Help));
+        assert_eq!(parse(&["search", "-h"]), Ok(Command::Help));
+    }
+
+    #[test]
+    fn it_parses_the_verify_subcommand() {
+        assert_eq!(parse(&["verify", "1234123"]), Ok(Command::Verify("1234123".to_string(), None)));
+        assert_eq!(parse(&["verify", "--n", "4", "1234"]), Ok(Command::Verify("1234".to_string(), Some(4))));
+        assert_eq!(parse(&["verify"]), Err("The verify subcommand requires a string.".to_string()));
+    }
+
+    #[test]
+    fn it_parses_the_split_and_merge_subcommands() {
+        let expected = SplitOptions { n: 5, depth: 2, jobs_dir: "/tmp/jobs".to_string(), known_bounds: None };
+        assert_eq!(parse(&["split", "--depth", "2", "--jobs", "/tmp/jobs"]), Ok(Command::Split(expected)));
+        assert_eq!(parse(&["split", "--depth", "2"]), Err("The split subcommand requires --depth and --jobs.".to_string()));
+
+        let export = Export { path: "b.txt".to_string(), format: Format::BFile };
+        assert_eq!(parse(&["merge", "--jobs", "/tmp/jobs", "--export", "b.txt"]), Ok(Command::Merge("/tmp/jobs".to_string(), vec![export])));
+        assert_eq!(parse(&["merge"]), Err("The merge subcommand requires --jobs.".to_string()));
+    }
+
+    #[test]
+    fn it_parses_the_coordinate_subcommand() {
+        let address = Address::Unix("/tmp/leaps.sock".to_string());
+        let expected = CoordinateOptions { n: 5, depth: 1, address, known_bounds: None, exports: vec![] };
+        assert_eq!(parse(&["coordinate", "--listen", "/tmp/leaps.sock"]), Ok(Command::Coordinate(expected)));
+        assert_eq!(parse(&["coordinate", "--depth", "2"]), Err("The coordinate subcommand requires --listen.".to_string()));
+
+        let actual = options(&["--connect", "localhost:7878"]);
+        assert_eq!(actual.connect, Some(Address::Tcp("localhost:7878".to_string())));
+    }
+
+    #[test]
+    fn it_parses_the_path_to_a_job() {
+        let actual = options(&["--job", "/tmp/jobs/job-00001.job"]);
+        assert_eq!(actual.job, Some("/tmp/jobs/job-00001.job".to_string()));
+    }
+
+    mod when_the_arguments_are_invalid {
+        use super::*;
+
+        #[test]
+        fn it_returns_an_error_for_unknown_subcommands_and_options() {
+            assert_eq!(parse(&["explode"]), Err("Unknown subcommand 'explode'.".to_string()));
+            assert_eq!(parse(&["--fast"]), Err("Unknown option '--fast'.".to_string()));
+        }
+
+        #[test]
+        fn it_returns_an_error_if_a_value_is_missing() {
+            assert_eq!(parse(&["--n"]), Err("Option '--n' requires a value.".to_string()));
+            assert_eq!(parse(&["--n", "--gzip"]), Err("Option '--n' requires a value.".to_string()));
+        }
+
+        #[test]
+        fn it_returns_an_error_that_explains_the_bad_value() {
+            let expected = "Invalid value for '--n': The number of symbols must be between 2 and 10.";
+            assert_eq!(parse(&["--n", "11"]), Err(expected.to_string()));
+
+            let expected = "Invalid value for '--memory': 'lots' is not an amount of memory, e.g. 12G or 512M.";
+            assert_eq!(parse(&["--memory", "lots"]), Err(expected.to_string()));
+
+            let expected = "Invalid value for '--gzip': 'maybe' is not yes or no.";
+            assert_eq!(parse(&["--gzip=maybe"]), Err(expected.to_string()));
+        }
+
+        #[test]
+        fn it_returns_the_same_error_for_known_bounds_in_every_subcommand() {
+            let expected = Err("Invalid value for '--known-bounds': 'b.xml' must end in .json, .csv or .txt for an OEIS b-file.".to_string());
+
+            assert_eq!(parse(&["--known-bounds", "b.xml"]), expected);
+            assert_eq!(parse(&["split", "--depth", "2", "--jobs", "/tmp/jobs", "--known-bounds", "b.xml"]), expected);
+            assert_eq!(parse(&["coordinate", "--listen", "/tmp/leaps.sock", "--known-bounds", "b.xml"]), expected);
+        }
+
+        #[test]
+        fn it_returns_an_error_if_known_bounds_are_given_when_resuming() {
+            let expected = "Option '--known-bounds' can't be used with '--resume' because the checkpoint has the bounds.";
+            assert_eq!(parse(&["--resume", "--known-bounds", "b.csv"]), Err(expected.to_string()));
+        }
+
+        #[test]
+        fn it_returns_an_error_if_known_bounds_or_witnesses_are_given_with_a_job() {
+            let expected = "Option '--known-bounds' can't be used with '--job' because the job has the bounds. Pass it to split instead.";
+            assert_eq!(parse(&["--job", "j.job", "--known-bounds", "b.csv"]), Err(expected.to_string()));
+
+            let expected = "Option '--witnesses' can't be used with '--job' because the strings wouldn't include the prefix.";
+            assert_eq!(parse(&["--job", "j.job", "--witnesses", "w.txt"]), Err(expected.to_string()));
+        }
+
+        #[test]
+        fn it_returns_an_error_if_a_worker_is_given_options_for_its_own_search() {
+            let expected = "Option '--resume' can't be used with '--connect' because the coordinator hands out the jobs and merges their bounds.";
+            assert_eq!(parse(&["--connect", "localhost:7878", "--resume"]), Err(expected.to_string()));
+
+            let expected = "Option '--export' can't be used with '--connect' because the coordinator hands out the jobs and merges their bounds.";
+            assert_eq!(parse(&["--connect", "localhost:7878", "--export", "b.txt"]), Err(expected.to_string()));
+        }
+    }
+}
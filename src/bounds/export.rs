@@ -0,0 +1,104 @@
+use super::Bounds;
+
+use std::fs::{rename, write};
+use std::io;
+use std::path::Path;
+
+// The bounds are exported each time a new one is found so that other tools
+// can follow the search while it runs. Rows where the lower and upper bounds
+// meet are exact, and only those go in the b-file.
+
+/// A file the bounds are written to while the search runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Export {
+    pub path: String,
+    pub format: Format,
+}
+
+#[derive(Serialize)]
+struct Row {
+    wasted_symbols: usize,
+    lower_bound: usize,
+    upper_bound: usize,
+    threshold: usize,
+    exact: bool,
+}
+
+#[derive(Serialize)]
+struct JsonFile {
+    n: usize,
+    bounds: Vec<Row>,
+}
+
+/// The format of an exported file, which is chosen by its extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Csv,
+    BFile,
+}
+
+impl Format {
+    pub fn for_path(path: &str) -> Option<Self> {
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("json") => Some(Format::Json),
+            Some("csv") => Some(Format::Csv),
+            Some("txt") => Some(Format::BFile),
+            _ => None,
+        }
+    }
+}
+
+impl Export {
+    /// The file is written to a temporary file and renamed over the previous
+    /// one so that readers never see a partially written table.
+    pub fn write(&self, bounds: &Bounds, n: usize) -> io::Result<()> {
+        let contents = match self.format {
+            Format::Json => bounds.to_json(n),
+            Format::Csv => bounds.to_csv(),
+            Format::BFile => bounds.to_b_file(n),
+        };
+
+        let temporary_path = format!("{}.tmp", self.path);
+
+        write(&temporary_path, contents)?;
+        rename(&temporary_path, &self.path)
+    }
+}
+
+impl Bounds {
+    pub fn to_json(&self, n: usize) -> String {
+        let file = JsonFile { n, bounds: self.rows().collect() };
+
+        serde_json::to_string_pretty(&file).unwrap() + "\n"
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = "wasted_symbols,lower_bound,upper_bound,threshold,exact\n".to_string();
+
+        for row in self.rows() {
+            csv.push_str(&format!("{},{},{},{},{}\n", row.wasted_symbols, row.lower_bound, row.upper_bound, row.threshold, row.exact));
+        }
+
+        csv
+    }
+
+    pub fn to_b_file(&self, n: usize) -> String {
+        let mut b_file = format!("# The maximum number of permutations of {} symbols in a string with n wasted symbols.\n", n);
+
+        for row in self.rows().filter(|row| row.exact) {
+            b_file.push_str(&format!("{} {}\n", row.wasted_symbols, row.lower_bound));
+        }
+
+        b_file
+    }
+
+    fn rows(&self) -> impl Iterator<Item=Row> + '_ {
+        (0..self.lower_bounds.len()).map(move |w| {
+            let (lower_bound, upper_bound) = (self.lower_bounds[w], self.upper_bounds[w]);
+            let (threshold, exact) = (self.thresholds[w], lower_bound == upper_bound);
+
+            Row { wasted_symbols: w, lower_bound, upper_bound, threshold, exact }
+        })
+    }
+}
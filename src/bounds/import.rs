@@ -0,0 +1,138 @@
+use super::{Bounds, Format};
+
+use std::fs::read_to_string;
+
+// Bounds can be loaded from a file this tool exported or one written by hand in
+// the same format. They're trusted, so the search prunes with them straight
+// away, but if it finds more permutations than a row that the file says is
+// exact then it's reported as a contradiction.
+
+#[derive(Deserialize)]
+pub(super) struct Row {
+    pub wasted_symbols: usize,
+    pub lower_bound: usize,
+    #[serde(default = "Row::exact")]
+    pub exact: bool,
+}
+
+// Other fields, such as the upper bounds, are ignored.
+#[derive(Deserialize)]
+struct JsonFile {
+    n: Option<usize>,
+    bounds: Vec<Row>,
+}
+
+impl Row {
+    // A row that doesn't say whether it's exact is trusted to be.
+    fn exact() -> bool {
+        true
+    }
+}
+
+impl Bounds {
+    /// Reads bounds from a file in any of the export formats.
+    pub fn load(path: &str, n: usize) -> Result<Self, String> {
+        let fail = |reason: String| format!("Failed to load bounds from {}: {}", path, reason);
+
+        let format = Format::for_path(path).ok_or_else(|| fail("it must end in .json, .csv or .txt.".to_string()))?;
+        let contents = read_to_string(path).map_err(|e| fail(e.to_string()))?;
+
+        let rows = match format {
+            Format::Json => Self::json_rows(&contents, n),
+            Format::Csv => Self::csv_rows(&contents),
+            Format::BFile => Self::b_file_rows(&contents),
+        }.map_err(fail)?;
+
+        let mut bounds = Self::new(n);
+        bounds.seed(&rows).map_err(fail)?;
+
+        Ok(bounds)
+    }
+
+    pub(super) fn seed(&mut self, rows: &[Row]) -> Result<(), String> {
+        if rows.is_empty() {
+            return Err("it doesn't have any bounds.".to_string());
+        }
+
+        for (i, row) in rows.iter().enumerate() {
+            if row.wasted_symbols != i {
+                return Err(format!("it should have a row for each number of wasted symbols from 0, but row {} is for {}.", i, row.wasted_symbols));
+            }
+
+            if !row.exact && i != rows.len() - 1 {
+                return Err(format!("only the last row can be inexact, but the row for {} wasted symbols is.", i));
+            }
+
+            if row.lower_bound > self.max {
+                return Err(format!("it has {} permutations for {} wasted symbols, but there are only {}.", row.lower_bound, i, self.max));
+            }
+
+            if i > 0 && row.lower_bound < rows[i - 1].lower_bound {
+                return Err(format!("the bound for {} wasted symbols is less than the one before it.", i));
+            }
+
+            match i {
+                0 => self.increase_lower_bound(0, row.lower_bound),
+                _ => self.add_new_index(i, row.lower_bound),
+            }
+
+            if row.exact {
+                self.trusted = i + 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn json_rows(contents: &str, n: usize) -> Result<Vec<Row>, String> {
+        let file: JsonFile = serde_json::from_str(contents).map_err(|e| format!("it isn't valid JSON: {}.", e))?;
+
+        match file.n {
+            Some(file_n) if file_n != n => Err(format!("it is for {} symbols, but the search is for {}.", file_n, n)),
+            _ => Ok(file.bounds),
+        }
+    }
+
+    fn csv_rows(contents: &str) -> Result<Vec<Row>, String> {
+        let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+        let header = lines.next().ok_or("it is empty.")?.split(',').map(str::trim).collect::<Vec<_>>();
+
+        let column = |key| header.iter().position(|&name| name == key);
+        let wasted_symbols = column("wasted_symbols").ok_or("it doesn't have a wasted_symbols column.")?;
+        let lower_bound = column("lower_bound").ok_or("it doesn't have a lower_bound column.")?;
+        let exact = column("exact");
+
+        lines.map(|line| {
+            let values = line.split(',').map(str::trim).collect::<Vec<_>>();
+
+            Ok(Row {
+                wasted_symbols: Self::parse_number(values.get(wasted_symbols).copied(), "wasted_symbols")?,
+                lower_bound: Self::parse_number(values.get(lower_bound).copied(), "lower_bound")?,
+                exact: exact.and_then(|i| values.get(i)).is_none_or(|&exact| exact == "true"),
+            })
+        }).collect()
+    }
+
+    fn b_file_rows(contents: &str) -> Result<Vec<Row>, String> {
+        contents.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let mut values = line.split_whitespace();
+
+                Ok(Row {
+                    wasted_symbols: Self::parse_number(values.next(), "n")?,
+                    lower_bound: Self::parse_number(values.next(), "a(n)")?,
+                    exact: true,
+                })
+            }).collect()
+    }
+
+    fn parse_number(value: Option<&str>, name: &str) -> Result<usize, String> {
+        match value.map(|v| v.parse()) {
+            Some(Ok(number)) => Ok(number),
+            Some(Err(_)) => Err(format!("'{}' is not a number for {}.", value.unwrap(), name)),
+            None => Err(format!("a row doesn't have a value for {}.", name)),
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use super::Bounds;
+use super::import::Row;
+
+// A search that has been split by prefix finds the bounds of each part on its
+// own. Every string starts with one of the prefixes, or is one of the shorter
+// strings they were built from, which each part counts as well. So the most
+// permutations for a number of wasted symbols is the most that any part found,
+// but it's only exact once every part has finished with that number. A part
+// that hasn't got as far still has strings that could do better.
+
+impl Bounds {
+    /// Combines the bounds of each part of a split search. A row is exact if
+    /// it's exact in every part, and there's one more row after those with the
+    /// most permutations found so far.
+    pub fn merge(parts: &[Bounds], n: usize) -> Result<Self, String> {
+        let exact_rows = parts.iter().map(Self::exact_rows).min().ok_or("There are no bounds to merge.")?;
+        let rows = parts.iter().map(|b| b.lower_bounds.len()).max().unwrap_or(0);
+
+        let mut merged_rows = vec![];
+        let mut bounds = Self::new(n);
+
+        for w in 0..rows.min(exact_rows + 1) {
+            let lower_bound = parts.iter().map(|b| b.lower_bounds[w.min(b.lower_bounds.len() - 1)]).max().unwrap_or(0);
+            let exact = w < exact_rows;
+
+            merged_rows.push(Row { wasted_symbols: w, lower_bound, exact });
+
+            // Parts that didn't find every permutation can go on past the row
+            // where another part did.
+            if exact && lower_bound == bounds.max {
+                break;
+            }
+        }
+
+        bounds.seed(&merged_rows)?;
+
+        Ok(bounds)
+    }
+
+    // Rows are exact once the search has moved past them, or if they were
+    // loaded from a file that said they were. A part's upper bounds are only for
+    // its own strings, so a lower bound from another part can be above them.
+    fn exact_rows(&self) -> usize {
+        (0..self.lower_bounds.len())
+            .take_while(|&w| w < self.trusted || self.lower_bounds[w] >= self.upper_bounds[w])
+            .count()
+    }
+}
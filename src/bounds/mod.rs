@@ -1,11 +1,46 @@
+mod export;
+mod import;
+mod merge;
+
+use super::events::{Event, EventLog};
+
 use std::cmp::{min, max};
+use std::mem;
 
-#[derive(Debug)]
+pub use self::export::{Export, Format};
+
+/// The table of bounds, indexed by the number of wasted symbols. A row is exact
+/// once its lower and upper bounds meet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Bounds {
+    /// The most permutations found so far for each number of wasted symbols.
     pub lower_bounds: Vec<usize>,
+    /// The most permutations that could possibly fit.
     pub upper_bounds: Vec<usize>,
+    /// Candidates with fewer permutations than this can't improve the bounds.
     pub thresholds: Vec<usize>,
+    /// The number of permutations, n!.
     pub max: usize,
+    /// How many rows came from known bounds that are trusted to be exact.
+    pub trusted: usize,
+    /// Upper bounds that hold for every string, kept separately when the
+    /// search is from a prefix. See `from_prefix`.
+    suffix_bounds: Option<Vec<usize>>,
+    #[serde(skip)]
+    log: Option<EventLog>,
+    #[serde(skip)]
+    contradictions: Vec<Contradiction>,
+}
+
+/// A trusted row that the search found more permutations than, which means
+/// the known bounds it was given are wrong.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contradiction {
+    pub wasted_symbols: usize,
+    /// The most permutations the known bounds said would fit.
+    pub known: usize,
+    /// The permutations the search found.
+    pub found: usize,
 }
 
 impl Bounds {
@@ -17,28 +52,84 @@ impl Bounds {
             upper_bounds: vec![factorial],
             thresholds: vec![0],
             max: factorial,
+            trusted: 0,
+            suffix_bounds: None,
+            log: None,
+            contradictions: vec![],
         }
     }
 
+    /// Makes these the bounds for a search of only the strings that start
+    /// with a prefix. The rows it finds are exact for those strings but not
+    /// for others, so they can't bound what follows a wasted symbol, which
+    /// could be anything. Those bounds are worked out from the trusted rows
+    /// instead, which must include the row for no wasted symbols.
+    pub fn from_prefix(&mut self) {
+        assert!(self.trusted > 0, "Expected the bounds for no wasted symbols to be trusted");
+
+        let mut suffix_bounds = vec![];
+
+        for i in 0..self.upper_bounds.len() {
+            suffix_bounds.push(self.suffix_bound(&suffix_bounds, i));
+        }
+
+        self.suffix_bounds = Some(suffix_bounds);
+    }
+
+    /// The upper bounds for any string, which can be less tight than
+    /// `upper_bounds` when the search is from a prefix.
+    pub fn suffix_bounds(&self) -> &[usize] {
+        self.suffix_bounds.as_deref().unwrap_or(&self.upper_bounds)
+    }
+
+    /// Emits an event to the log each time a bound improves.
+    pub fn log_to(&mut self, log: EventLog) {
+        self.log = Some(log);
+    }
+
+    /// Records that a candidate with `bound` permutations was taken from the
+    /// frontier with `index` total waste. Returns true if a bound improved. A
+    /// trusted row that improves is kept as a contradiction.
     pub fn update(&mut self, index: usize, bound: usize) -> bool {
         if self.lower_bounds.len() <= index {
             self.add_new_index(index, bound);
-            println!("{} wasted symbols: at most {} permutations", index - 1, bound);
+            self.emit(index, bound, true);
+
             return true;
         }
 
         if self.lower_bounds[index] < bound {
+            if index < self.trusted {
+                let known = self.lower_bounds[index];
+                self.contradictions.push(Contradiction { wasted_symbols: index, known, found: bound });
+            }
+
             self.increase_lower_bound(index, bound);
+            self.emit(index, bound, false);
+
             return true
         }
 
         false
     }
 
+    /// Takes the contradictions found since this was last called.
+    pub fn take_contradictions(&mut self) -> Vec<Contradiction> {
+        mem::take(&mut self.contradictions)
+    }
+
+    fn emit(&self, wasted_symbols: usize, permutations: usize, new_index: bool) {
+        if let Some(log) = &self.log {
+            log.emit(Event::Bound { wasted_symbols, permutations, new_index });
+        }
+    }
+
+    /// The upper bound for a number of wasted symbols, or n! past the table.
     pub fn upper(&self, wasted_symbols: usize) -> usize {
         *self.upper_bounds.get(wasted_symbols).unwrap_or(&self.max)
     }
 
+    /// Whether every permutation has been seen, so the search is finished.
     pub fn found_for_superpermutation(&self) -> bool {
         *self.lower_bounds.last().unwrap() == self.max
     }
@@ -51,6 +142,15 @@ impl Bounds {
         self.upper_bounds.resize(index + 1, self.max);
         self.thresholds.resize(index + 1, 0);
 
+        if let Some(mut suffix_bounds) = self.suffix_bounds.take() {
+            for i in previous_len..=index {
+                let bound = self.suffix_bound(&suffix_bounds, i);
+                suffix_bounds.push(bound);
+            }
+
+            self.suffix_bounds = Some(suffix_bounds);
+        }
+
         for i in previous_len..=index {
             self.increase_lower_bound(i, max(bound, last_bound));
         }
@@ -64,7 +164,7 @@ impl Bounds {
 
     fn increase_lower_bound(&mut self, index: usize, bound: usize) {
         self.lower_bounds[index] = bound;
-        self.thresholds[index] = bound - self.lower_bounds[0];
+        self.thresholds[index] = bound.saturating_sub(self.lower_bounds[0]);
     }
 
     fn fix_upper_bound(&mut self, index: usize) {
@@ -77,7 +177,7 @@ impl Bounds {
         for w in 0..index {
             let starting_point = self.upper_bounds[w];
             let allowed_waste = index - w - 1;
-            let maximum_permutations = self.upper_bounds[allowed_waste];
+            let maximum_permutations = self.suffix_bounds()[allowed_waste];
 
             upper_bound = min(upper_bound, starting_point + maximum_permutations);
         }
@@ -85,6 +185,18 @@ impl Bounds {
         self.upper_bounds[index] = upper_bound;
     }
 
+    // Trusted rows hold for every string. Past those, a string that wastes
+    // `index` symbols is split at one of them in the same way as above.
+    fn suffix_bound(&self, suffix_bounds: &[usize], index: usize) -> usize {
+        if index < self.trusted {
+            return self.lower_bounds[index];
+        }
+
+        (0..index)
+            .map(|w| suffix_bounds[w] + suffix_bounds[index - w - 1])
+            .fold(self.max, min)
+    }
+
     pub fn factorial(n: usize) -> usize {
         match n {
             0 => 1,
@@ -1,3 +1,5 @@
+#![allow(clippy::bool_assert_comparison)]
+
 use super::*;
 
 type Subject = Bounds;
@@ -47,6 +49,22 @@ mod update {
         assert_eq!(subject.update(0, 3), true);
     }
 
+    #[test]
+    fn it_keeps_a_contradiction_when_a_trusted_row_improves() {
+        let mut subject = Subject::new(N);
+
+        subject.update(0, 5);
+        subject.update(1, 10);
+        subject.trusted = 1;
+
+        subject.update(0, 6);
+        subject.update(1, 11);
+
+        let contradiction = Contradiction { wasted_symbols: 0, known: 5, found: 6 };
+        assert_eq!(subject.take_contradictions(), &[contradiction]);
+        assert_eq!(subject.take_contradictions(), &[]);
+    }
+
     mod when_the_index_is_larger_than_the_array {
         use super::*;
 
@@ -210,3 +228,227 @@ mod found_for_superpermutation {
         assert_eq!(subject.found_for_superpermutation(), true);
     }
 }
+
+mod export {
+    use super::*;
+    use std::fs::{create_dir_all, read_to_string};
+
+    fn subject() -> Subject {
+        let mut subject = Subject::new(N);
+
+        subject.update(0, 5);
+        subject.update(1, 8);
+        subject.update(2, 9);
+
+        subject
+    }
+
+    #[test]
+    fn it_exports_every_row_as_json() {
+        let expected = "\
+{
+  \"n\": 5,
+  \"bounds\": [
+    {
+      \"wasted_symbols\": 0,
+      \"lower_bound\": 5,
+      \"upper_bound\": 5,
+      \"threshold\": 0,
+      \"exact\": true
+    },
+    {
+      \"wasted_symbols\": 1,
+      \"lower_bound\": 8,
+      \"upper_bound\": 8,
+      \"threshold\": 3,
+      \"exact\": true
+    },
+    {
+      \"wasted_symbols\": 2,
+      \"lower_bound\": 9,
+      \"upper_bound\": 13,
+      \"threshold\": 4,
+      \"exact\": false
+    }
+  ]
+}
+";
+
+        assert_eq!(subject().to_json(N), expected);
+    }
+
+    #[test]
+    fn it_exports_every_row_as_csv() {
+        let expected = "\
+wasted_symbols,lower_bound,upper_bound,threshold,exact
+0,5,5,0,true
+1,8,8,3,true
+2,9,13,4,false
+";
+
+        assert_eq!(subject().to_csv(), expected);
+    }
+
+    #[test]
+    fn it_only_exports_the_exact_rows_to_the_b_file() {
+        let expected = "\
+# The maximum number of permutations of 5 symbols in a string with n wasted symbols.
+0 5
+1 8
+";
+
+        assert_eq!(subject().to_b_file(N), expected);
+    }
+
+    #[test]
+    fn it_writes_the_file_in_the_given_format() {
+        let dir = "/tmp/superpermutation-test/bounds-1";
+        create_dir_all(dir).unwrap();
+
+        let export = Export { path: format!("{}/bounds.csv", dir), format: Format::Csv };
+        export.write(&subject(), N).unwrap();
+
+        assert_eq!(read_to_string(&export.path).unwrap(), subject().to_csv());
+    }
+}
+
+mod load {
+    use super::*;
+    use super::super::super::candidate::Candidate;
+    use super::super::super::disk::Codec;
+    use super::super::super::frontier::Frontier;
+    use std::fs::{create_dir_all, write};
+
+    const PATH: &str = "/tmp/superpermutation-test/bounds-2";
+
+    fn subject() -> Subject {
+        let mut subject = Subject::new(N);
+
+        subject.update(0, 5);
+        subject.update(1, 8);
+        subject.update(2, 9);
+
+        subject
+    }
+
+    fn file(name: &str, contents: &str) -> String {
+        create_dir_all(PATH).unwrap();
+
+        let path = format!("{}/{}", PATH, name);
+        write(&path, contents).unwrap();
+
+        path
+    }
+
+    fn exported(name: &str, format: Format) -> String {
+        create_dir_all(PATH).unwrap();
+
+        let export = Export { path: format!("{}/{}", PATH, name), format };
+        export.write(&subject(), N).unwrap();
+
+        export.path
+    }
+
+    #[test]
+    fn it_loads_the_bounds_that_were_exported_as_json_or_csv() {
+        for path in &[exported("bounds.json", Format::Json), exported("bounds.csv", Format::Csv)] {
+            let loaded = Subject::load(path, N).unwrap();
+
+            assert_eq!(loaded, Subject { trusted: 2, ..subject() });
+        }
+    }
+
+    #[test]
+    fn it_loads_the_exact_bounds_from_a_b_file() {
+        let loaded = Subject::load(&exported("bounds.txt", Format::BFile), N).unwrap();
+
+        assert_eq!(loaded.lower_bounds, &[5, 8]);
+        assert_eq!(loaded.upper_bounds, &[5, 10]);
+        assert_eq!(loaded.thresholds, &[0, 3]);
+        assert_eq!(loaded.trusted, 2);
+    }
+
+    #[test]
+    fn it_loads_bounds_that_were_written_by_hand() {
+        let path = file("hand.csv", "lower_bound, wasted_symbols\n5, 0\n8, 1\n\n");
+        let loaded = Subject::load(&path, N).unwrap();
+
+        assert_eq!(loaded.lower_bounds, &[5, 8]);
+        assert_eq!(loaded.trusted, 2);
+
+        let json = "{\"bounds\": [{\"lower_bound\": 5, \"wasted_symbols\": 0}, {\"wasted_symbols\": 1, \"lower_bound\": 8, \"exact\": false}]}";
+        let loaded = Subject::load(&file("hand.json", json), N).unwrap();
+
+        assert_eq!(loaded.lower_bounds, &[5, 8]);
+        assert_eq!(loaded.trusted, 1);
+    }
+
+    #[test]
+    fn it_returns_an_error_if_the_bounds_are_inconsistent() {
+        let error = |name, contents| Subject::load(&file(name, contents), N).unwrap_err();
+
+        assert_eq!(error("gap.txt", "0 5\n2 9\n").ends_with("row 1 is for 2."), true);
+        assert_eq!(error("decrease.txt", "0 5\n1 4\n").ends_with("is less than the one before it."), true);
+        assert_eq!(error("max.txt", "0 5\n1 121\n").ends_with("but there are only 120."), true);
+        assert_eq!(error("n.json", "{\"n\": 4, \"bounds\": []}").ends_with("it is for 4 symbols, but the search is for 5."), true);
+        assert_eq!(error("word.txt", "0 five\n").ends_with("'five' is not a number for a(n)."), true);
+        assert_eq!(error("word.json", "{\"bounds\": [{\"wasted_symbols\": 0, \"lower_bound\": \"five\"}]}").contains("it isn't valid JSON"), true);
+        assert_eq!(error("missing.json", "{\"n\": 5}").contains("missing field `bounds`"), true);
+        assert_eq!(error("empty.txt", "# nothing\n").ends_with("it doesn't have any bounds."), true);
+
+        let inexact = "wasted_symbols,lower_bound,exact\n0,5,false\n1,8,true\n";
+        assert_eq!(error("inexact.csv", inexact).ends_with("the row for 0 wasted symbols is."), true);
+    }
+
+    #[test]
+    fn it_finds_the_same_bounds_when_the_search_starts_from_known_ones() {
+        let expected = search(Subject::new(N), "bounds-3", 24);
+
+        let mut known = Subject::new(N);
+        for (w, &bound) in expected[..12].iter().enumerate() {
+            known.update(w, bound);
+        }
+
+        let path = format!("{}/known.txt", PATH);
+        create_dir_all(PATH).unwrap();
+        Export { path: path.clone(), format: Format::BFile }.write(&known, N).unwrap();
+
+        let loaded = Subject::load(&path, N).unwrap();
+        assert_eq!(loaded.trusted, 11);
+
+        assert_eq!(search(loaded, "bounds-4", 24), expected);
+    }
+
+    fn search(mut bounds: Subject, test_id: &str, max_bounds: usize) -> Vec<usize> {
+        let scratch_dir = format!("/tmp/superpermutation-test/{}", test_id);
+        let mut frontier = Frontier::new(1.0, &scratch_dir, Codec::Raw, N).unwrap();
+
+        frontier.add(Candidate::seed(N), N);
+
+        for (wasted_symbols, &threshold) in bounds.thresholds.iter().enumerate() {
+            frontier.prune(wasted_symbols, threshold, false);
+        }
+
+        while let Some(mut wasted_symbols) = frontier.min_waste() {
+            wasted_symbols = frontier.unprune(wasted_symbols, &bounds.lower_bounds, &bounds.upper_bounds);
+
+            let candidate = frontier.next().unwrap();
+
+            if bounds.update(wasted_symbols, candidate.number_of_permutations()) {
+                let threshold = bounds.thresholds[wasted_symbols];
+                frontier.prune(wasted_symbols, threshold, true);
+            }
+
+            if bounds.lower_bounds.len() > max_bounds {
+                break;
+            }
+
+            for child in candidate.expand(bounds.upper(wasted_symbols), N) {
+                frontier.add(child, N);
+            }
+        }
+
+        bounds.lower_bounds.truncate(max_bounds);
+        bounds.lower_bounds
+    }
+}
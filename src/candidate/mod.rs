@@ -4,15 +4,52 @@ use bit_set::BitSet;
 use lehmer::Lehmer;
 use std::iter::once;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// The smallest number of symbols a search can be for.
+pub const MIN_SYMBOLS: usize = 2;
+/// The largest number of symbols a search can be for.
+pub const MAX_SYMBOLS: usize = 10;
+
+/// A string that is being built by the search. Only what's needed to extend it
+/// is kept: the permutations it has seen and the last few symbols.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Candidate {
+    /// The permutations in the string, by their Lehmer code.
     #[serde(serialize_with="serialize::serialize", deserialize_with="serialize::deserialize")]
     pub permutations_seen: BitSet,
+    /// Up to n - 1 symbols at the end of the string, from 0 to n - 1.
     pub tail_of_string: Vec<u8>,
+    /// How many symbols didn't complete a new permutation.
     pub wasted_symbols: u16,
+    /// Identifies the string in a witness file, or 0 if there isn't one.
+    pub ancestry_id: AncestryId,
+}
+
+/// An ancestry id in 48 bits. With `wasted_symbols` it fills the padding at the
+/// end of a candidate, so that candidates take no more memory when witnesses
+/// aren't recorded than they would without it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct AncestryId([u16; 3]);
+
+impl AncestryId {
+    /// The largest id that fits.
+    pub const MAX: u64 = (1 << 48) - 1;
+}
+
+impl From<u64> for AncestryId {
+    fn from(id: u64) -> Self {
+        assert!(id <= Self::MAX, "The ancestry id {} doesn't fit in 48 bits", id);
+        Self([id as u16, (id >> 16) as u16, (id >> 32) as u16])
+    }
+}
+
+impl From<AncestryId> for u64 {
+    fn from(id: AncestryId) -> Self {
+        id.0[0] as u64 | (id.0[1] as u64) << 16 | (id.0[2] as u64) << 32
+    }
 }
 
 impl Candidate {
+    /// The string 0, 1, ..., n - 1, which has seen the first permutation.
     pub fn seed(n: usize) -> Self {
         let max_value = Lehmer::max_value(n) as usize;
         let mut seen = BitSet::with_capacity(max_value);
@@ -23,9 +60,12 @@ impl Candidate {
             permutations_seen: seen,
             tail_of_string: (1..n as u8).collect(),
             wasted_symbols: 0,
+            ancestry_id: AncestryId::default(),
         }
     }
 
+    /// Appends each symbol other than the last one. Once the candidate has seen
+    /// `upper_bound` permutations, every symbol appended is counted as wasted.
     pub fn expand(self, upper_bound: usize, n: usize) -> impl Iterator<Item=Self> {
         let last_symbol = *self.tail_of_string.last().unwrap();
         let at_upper_bound = self.number_of_permutations() == upper_bound;
@@ -39,15 +79,18 @@ impl Candidate {
         self.permutations_seen.len()
     }
 
+    /// The symbols that must be wasted before the tail is a full permutation.
     pub fn future_waste(&self, n: usize) -> usize {
         n - self.tail_of_string.len() - 1
     }
 
+    /// The waste used to order candidates in the frontier.
     pub fn total_waste(&self, n: usize) -> usize {
         self.wasted_symbols as usize + self.future_waste(n)
     }
 
-    fn expand_one(&self, symbol: u8, at_upper_bound: bool, n: usize) -> Self {
+    /// Appends a single symbol.
+    pub fn expand_one(&self, symbol: u8, at_upper_bound: bool, n: usize) -> Self {
         let tail_of_string = self.build_tail(symbol, n);
 
         if Self::less_than_full(&self.tail_of_string, n) {
@@ -83,8 +126,9 @@ impl Candidate {
     fn candidate_with_wasted_symbol(&self, tail_of_string: Vec<u8>, penalty: usize) -> Self {
         Candidate {
             permutations_seen: self.permutations_seen.clone(),
-            tail_of_string: tail_of_string,
+            tail_of_string,
             wasted_symbols: self.wasted_symbols + penalty as u16,
+            ancestry_id: self.ancestry_id,
         }
     }
 
@@ -93,7 +137,9 @@ impl Candidate {
         permutations_seen.insert(id);
 
         let wasted_symbols = self.wasted_symbols;
-        Candidate { permutations_seen, tail_of_string, wasted_symbols }
+        let ancestry_id = self.ancestry_id;
+
+        Candidate { permutations_seen, tail_of_string, wasted_symbols, ancestry_id }
     }
 
     fn less_than_full(tail_of_string: &[u8], n: usize) -> bool {
@@ -104,11 +150,12 @@ impl Candidate {
         symbol == *self.tail_of_string.first().unwrap()
     }
 
+    /// The Lehmer code of the permutation made by the tail and the symbol.
     // TODO: update Lehmer crate to accept a slice or iterator of usize
-    fn permutation_id(tail_of_string: &[u8], symbol: u8) -> usize {
+    pub fn permutation_id(tail_of_string: &[u8], symbol: u8) -> usize {
         let permutation = tail_of_string
             .iter()
-            .map(|&e| e)
+            .copied()
             .chain(once(symbol))
             .collect();
 
@@ -147,7 +194,7 @@ impl Candidate {
     }
 
     fn append(slice: &[u8], symbol: u8) -> Vec<u8> {
-        slice.iter().map(|&e| e).chain(once(symbol)).collect()
+        slice.iter().copied().chain(once(symbol)).collect()
     }
 }
 
@@ -1,7 +1,7 @@
 use super::*;
 use std::fmt;
 
-use serde::{Serializer, Deserializer, de::Visitor};
+use serde::{Serialize, Serializer, Deserialize, Deserializer, de::{self, Visitor}};
 use serde_bytes::ByteBuf;
 
 pub fn serialize<S: Serializer>(bit_set: &BitSet, serializer: S) -> Result<S::Ok, S::Error> {
@@ -29,3 +29,19 @@ pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BitSet,
 
     deserializer.deserialize_byte_buf(MyVisitor { })
 }
+
+// Ancestry ids are written as a u64, as they were before they were packed.
+impl Serialize for AncestryId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(u64::from(*self))
+    }
+}
+
+impl<'de> Deserialize<'de> for AncestryId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match u64::deserialize(deserializer)? {
+            id if id <= AncestryId::MAX => Ok(AncestryId::from(id)),
+            id => Err(de::Error::custom(format!("the ancestry id {} doesn't fit in 48 bits", id))),
+        }
+    }
+}
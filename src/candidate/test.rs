@@ -1,5 +1,6 @@
+#![allow(clippy::bool_assert_comparison)]
+
 use super::*;
-use std::usize::MAX;
 
 type Subject = Candidate;
 
@@ -38,7 +39,7 @@ mod expand {
     #[test]
     fn it_expands_all_candidates_except_for_the_last_symbol_of_the_tail() {
         let subject = Subject::seed(N);
-        let candidates: Vec<Subject> = subject.expand(MAX, N).collect();
+        let candidates: Vec<Subject> = subject.expand(usize::MAX, N).collect();
 
         assert_eq!(candidates.len(), 4);
 
@@ -291,3 +292,29 @@ mod serialization {
         assert_eq!(subject, candidate);
     }
 }
+
+mod ancestry_id {
+    use super::*;
+    use bincode::{serialize, deserialize};
+    use std::mem::size_of;
+
+    #[test]
+    fn it_takes_no_space_that_the_rest_of_the_candidate_would_not() {
+        assert_eq!(size_of::<Subject>(), size_of::<(BitSet, Vec<u8>, u16)>());
+    }
+
+    #[test]
+    fn it_keeps_every_id_up_to_the_max() {
+        for &id in &[0, 1, 0xFFFF, 0x1_0000, 0x1234_5678_9ABC, AncestryId::MAX] {
+            assert_eq!(u64::from(AncestryId::from(id)), id);
+        }
+    }
+
+    #[test]
+    fn it_is_serialized_as_a_u64() {
+        let id = AncestryId::from(0x1234_5678_9ABC);
+
+        assert_eq!(serialize(&id).unwrap(), serialize(&0x1234_5678_9ABC_u64).unwrap());
+        assert_eq!(deserialize::<AncestryId>(&serialize(&(AncestryId::MAX + 1)).unwrap()).is_err(), true);
+    }
+}
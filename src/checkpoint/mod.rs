@@ -0,0 +1,153 @@
+use super::args::Options;
+use super::bounds::Bounds;
+use super::frontier::Frontier;
+use super::witness::Witnesses;
+
+use bincode::{serialize_into, deserialize_from};
+
+use std::fs::{File, rename};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::time::{Duration, Instant};
+
+const VERSION: u32 = 9;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    n: usize,
+    witnesses: bool,
+    symmetry: bool,
+}
+
+pub struct Checkpoint {
+    path: String,
+    interval: Duration,
+    last_saved: Instant,
+}
+
+impl Checkpoint {
+    pub fn new(scratch_dir: &str, interval_minutes: f64) -> Self {
+        Self {
+            path: Self::filename(scratch_dir),
+            interval: Duration::from_secs_f64(interval_minutes * 60.),
+            last_saved: Instant::now(),
+        }
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.last_saved.elapsed() >= self.interval
+    }
+
+    // The checkpoint is written to a temporary file and renamed over the
+    // previous one so that there is always a complete checkpoint on disk. If
+    // there's an error, the previous one is left as it was.
+    pub fn save(&mut self, options: &Options, frontier: &Frontier, bounds: &Bounds, witnesses: Option<&mut Witnesses>) -> io::Result<()> {
+        let temporary_path = format!("{}.tmp", self.path);
+
+        Self::write(&temporary_path, options, frontier, bounds, witnesses).map_err(in_file(&temporary_path))?;
+        rename(&temporary_path, &self.path).map_err(in_file(&self.path))?;
+
+        frontier.remove_consumed_disk_files();
+
+        self.last_saved = Instant::now();
+
+        Ok(())
+    }
+
+    fn write(path: &str, options: &Options, frontier: &Frontier, bounds: &Bounds, witnesses: Option<&mut Witnesses>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        let header = Header {
+            version: VERSION,
+            n: options.n,
+            witnesses: witnesses.is_some(),
+            symmetry: options.symmetry,
+        };
+
+        serialize_into(&mut writer, &header).map_err(Self::to_io)?;
+        serialize_into(&mut writer, bounds).map_err(Self::to_io)?;
+
+        if let Some(w) = witnesses {
+            w.save(&mut writer).map_err(Self::to_io)?;
+        }
+
+        frontier.save(&mut writer).map_err(Self::to_io)?;
+
+        writer.flush()?;
+        writer.get_ref().sync_all()
+    }
+
+    pub fn load(options: &Options) -> Result<(Frontier, Bounds, Option<Witnesses>), String> {
+        let path = Self::filename(&options.scratch_dir);
+        let file = File::open(&path).map_err(|e| format!("Failed to open checkpoint {}: {}", path, e))?;
+
+        let mut reader = BufReader::new(file);
+        let corrupt = |e| format!("Failed to read checkpoint {}: {}", path, e);
+
+        let header: Header = deserialize_from(&mut reader).map_err(corrupt)?;
+        Self::check(&header, options)?;
+
+        let bounds: Bounds = deserialize_from(&mut reader).map_err(corrupt)?;
+
+        let witnesses = match &options.witnesses {
+            Some(output_path) => Some(Witnesses::resume(&options.scratch_dir, output_path, options.symmetry, options.n, &mut reader).map_err(corrupt)?),
+            None if header.witnesses => {
+                let _: (u64, Vec<(usize, u64)>) = deserialize_from(&mut reader).map_err(corrupt)?;
+                None
+            },
+            None => None,
+        };
+
+        let frontier = Frontier::resume(
+            options.memory,
+            &options.scratch_dir,
+            options.codec,
+            options.n,
+            &mut reader,
+        ).map_err(corrupt)?;
+
+        Ok((frontier, bounds, witnesses))
+    }
+
+    fn check(header: &Header, options: &Options) -> Result<(), String> {
+        if header.version != VERSION {
+            return Err(format!("The checkpoint has version {} but this build reads version {}.", header.version, VERSION));
+        }
+
+        if header.n != options.n {
+            return Err(format!("The checkpoint is for {} symbols, but --n is {}.", header.n, options.n));
+        }
+
+        if header.symmetry != options.symmetry {
+            return Err(format!("The checkpoint was saved with --symmetry={}, so it must be resumed with it.", Self::yes_no(header.symmetry)));
+        }
+
+        if options.witnesses.is_some() && !header.witnesses {
+            return Err("The checkpoint was saved without --witnesses, so it cannot be resumed with it.".to_string());
+        }
+
+        Ok(())
+    }
+
+    fn filename(scratch_dir: &str) -> String {
+        format!("{}/checkpoint.dat", scratch_dir)
+    }
+
+    fn to_io(error: bincode::Error) -> io::Error {
+        io::Error::other(error.to_string())
+    }
+
+    fn yes_no(value: bool) -> &'static str {
+        match value {
+            true => "yes",
+            false => "no",
+        }
+    }
+}
+
+fn in_file(path: &str) -> impl Fn(io::Error) -> io::Error + '_ {
+    move |e| io::Error::new(e.kind(), format!("{}: {}", path, e))
+}
+
+#[cfg(test)]
+mod test;
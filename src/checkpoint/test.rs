@@ -0,0 +1,129 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use super::*;
+use super::super::candidate::Candidate;
+
+use std::fs::{create_dir_all, remove_dir_all};
+use std::os::unix::fs::symlink;
+use std::path::Path;
+
+type Subject = Checkpoint;
+
+const N: usize = 4;
+const PATH: &str = "/tmp/superpermutation-test";
+
+fn options(test_id: &'static str) -> Options {
+    let scratch_dir = format!("{}/{}", PATH, test_id);
+
+    let _ = remove_dir_all(&scratch_dir);
+    create_dir_all(&scratch_dir).unwrap();
+
+    Options { n: N, memory: 1., scratch_dir, ..Options::default() }
+}
+
+fn frontier(options: &Options) -> Frontier {
+    let mut frontier = Frontier::new(options.memory, &options.scratch_dir, options.codec, N).unwrap();
+
+    for candidate in Candidate::seed(N).expand(usize::MAX, N) {
+        frontier.add(candidate, N);
+    }
+
+    frontier
+}
+
+mod save_and_load {
+    use super::*;
+
+    #[test]
+    fn it_restores_the_frontier_and_bounds() {
+        let options = options("checkpoint-1");
+        let mut subject = Subject::new(&options.scratch_dir, 60.);
+
+        let mut frontier = frontier(&options);
+        let mut bounds = Bounds::new(N);
+        bounds.update(0, 2);
+        bounds.update(1, 3);
+
+        frontier.prune(2, 2, true);
+        subject.save(&options, &frontier, &bounds, None).unwrap();
+
+        let (mut loaded_frontier, loaded_bounds, witnesses) = Subject::load(&options).unwrap();
+
+        assert_eq!(loaded_bounds, bounds);
+        assert_eq!(witnesses.is_none(), true);
+        assert_eq!(loaded_frontier.len(), frontier.len());
+
+        for expected in frontier.by_ref() {
+            assert_eq!(loaded_frontier.next(), Some(expected));
+        }
+
+        assert_eq!(loaded_frontier.next(), None);
+    }
+
+    #[test]
+    fn it_replaces_the_previous_checkpoint_atomically() {
+        let options = options("checkpoint-2");
+        let mut subject = Subject::new(&options.scratch_dir, 60.);
+
+        let frontier = frontier(&options);
+        let bounds = Bounds::new(N);
+
+        subject.save(&options, &frontier, &bounds, None).unwrap();
+        subject.save(&options, &frontier, &bounds, None).unwrap();
+
+        assert_eq!(Path::new(&subject.path).exists(), true);
+        assert_eq!(Path::new(&format!("{}.tmp", subject.path)).exists(), false);
+    }
+
+    #[test]
+    fn it_returns_an_error_and_keeps_the_previous_checkpoint_if_it_cannot_be_written() {
+        let options = options("checkpoint-5");
+        let mut subject = Subject::new(&options.scratch_dir, 60.);
+
+        let frontier = frontier(&options);
+        let bounds = Bounds::new(N);
+
+        subject.save(&options, &frontier, &bounds, None).unwrap();
+        symlink("/dev/full", format!("{}.tmp", subject.path)).unwrap();
+
+        let error = subject.save(&options, &frontier, &bounds, None).unwrap_err();
+        assert_eq!(error.to_string().starts_with(&format!("{}.tmp: ", subject.path)), true);
+
+        assert_eq!(Subject::load(&options).is_ok(), true);
+    }
+}
+
+mod load {
+    use super::*;
+
+    #[test]
+    fn it_returns_an_error_if_there_is_no_checkpoint() {
+        let options = options("checkpoint-3");
+        let error = Subject::load(&options).err().unwrap();
+
+        assert_eq!(error.starts_with("Failed to open checkpoint"), true);
+    }
+
+    #[test]
+    fn it_returns_an_error_if_the_options_do_not_match_the_checkpoint() {
+        let options = options("checkpoint-4");
+        let mut subject = Subject::new(&options.scratch_dir, 60.);
+
+        subject.save(&options, &frontier(&options), &Bounds::new(N), None).unwrap();
+
+        let different_n = Options { n: 5, ..options };
+        let error = Subject::load(&different_n).err().unwrap();
+
+        assert_eq!(error, "The checkpoint is for 4 symbols, but --n is 5.");
+    }
+}
+
+mod is_due {
+    use super::*;
+
+    #[test]
+    fn it_returns_true_once_the_interval_has_elapsed() {
+        assert_eq!(Subject::new(PATH, 60.).is_due(), false);
+        assert_eq!(Subject::new(PATH, 0.).is_due(), true);
+    }
+}
@@ -0,0 +1,271 @@
+use super::{Address, Connection, Listener, Request, Response, Stream, VERSION};
+use super::super::bounds::Bounds;
+use super::super::split::Job;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+/// Hands out jobs to workers and merges the bounds they find.
+pub struct Coordinator {
+    n: usize,
+    address: Address,
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+struct State {
+    pending: VecDeque<(usize, Job)>,
+    /// Each job that a worker has, so that it can be handed out again if the
+    /// worker goes away, and the latest bounds the worker sent for it.
+    running: HashMap<usize, (Job, Bounds)>,
+    finished: Vec<Bounds>,
+    /// The connections of workers that asked for a job when there wasn't one.
+    waiting: HashSet<usize>,
+    /// Why workers couldn't be accepted, since `run` last reported it.
+    accept_errors: Vec<io::Error>,
+    /// The most permutations any job has found for each number of wasted
+    /// symbols, which the workers prune with.
+    lower_bounds: Vec<usize>,
+    next_id: usize,
+    connected: usize,
+    changed: bool,
+    /// The merged bounds are exact up to a superpermutation, so the jobs that
+    /// are left can't change them.
+    complete: bool,
+}
+
+impl Coordinator {
+    /// Listens on `address` for workers and hands out `jobs`, e.g. from
+    /// `Job::split`. Workers can connect as soon as this returns.
+    pub fn bind(address: &Address, jobs: Vec<Job>, n: usize) -> io::Result<Self> {
+        let listener = Listener::bind(address)?;
+        let address = listener.address(address)?;
+
+        let mut state = State {
+            pending: VecDeque::new(),
+            running: HashMap::new(),
+            finished: vec![],
+            waiting: HashSet::new(),
+            accept_errors: vec![],
+            lower_bounds: vec![],
+            next_id: 0,
+            connected: 0,
+            changed: true,
+            complete: false,
+        };
+
+        state.add_jobs(jobs);
+
+        let shared = Arc::new(Shared { state: Mutex::new(state), changed: Condvar::new() });
+        let accepting = shared.clone();
+
+        thread::spawn(move || {
+            for connection_id in 0.. {
+                match listener.accept() {
+                    Ok(stream) => {
+                        let shared = accepting.clone();
+                        thread::spawn(move || shared.serve(connection_id, stream));
+                    },
+                    Err(e) => {
+                        accepting.state.lock().unwrap().accept_errors.push(e);
+                        accepting.changed.notify_all();
+                    },
+                }
+            }
+        });
+
+        Ok(Self { n, address, shared })
+    }
+
+    /// The address workers should connect to, with the port filled in if it
+    /// was 0.
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    /// How many jobs there have been, including those that workers gave away.
+    pub fn jobs(&self) -> usize {
+        self.shared.state.lock().unwrap().next_id
+    }
+
+    /// Waits until every job has finished, or until the merged bounds are exact
+    /// up to a superpermutation, and returns them. `progress` is called with
+    /// them each time a worker changes them. Workers that are still connected
+    /// are told there's nothing left to do first. If a worker can't be
+    /// accepted, `failed` is called with the error and the rest carry on.
+    pub fn run<F: FnMut(&Bounds), E: FnMut(&io::Error)>(&self, mut progress: F, mut failed: E) -> Result<Bounds, String> {
+        let mut state = self.shared.state.lock().unwrap();
+        let mut merged = None;
+
+        // Once the bounds are complete, the jobs that are dropped halfway would
+        // only make them look less exact, so they aren't merged again.
+        loop {
+            if !state.accept_errors.is_empty() {
+                let errors = std::mem::take(&mut state.accept_errors);
+
+                drop(state);
+                errors.iter().for_each(&mut failed);
+
+                state = self.shared.state.lock().unwrap();
+                continue;
+            }
+
+            if state.changed && !state.complete {
+                state.changed = false;
+
+                let bounds = Bounds::merge(&state.parts(), self.n)?;
+
+                if bounds.found_for_superpermutation() && bounds.trusted == bounds.lower_bounds.len() {
+                    state.complete = true;
+                    state.pending.clear();
+                }
+
+                drop(state);
+                progress(&bounds);
+                merged = Some(bounds);
+
+                state = self.shared.state.lock().unwrap();
+                continue;
+            }
+
+            let finished = state.complete || state.pending.is_empty() && state.running.is_empty();
+
+            if finished && state.connected == 0 {
+                return Ok(merged.unwrap());
+            }
+
+            state = self.shared.changed.wait(state).unwrap();
+        }
+    }
+}
+
+impl Shared {
+    // A worker that disconnects, even halfway through a job, has its jobs
+    // handed out again from the start. Any candidates it gave away are then
+    // searched twice, which wastes time but doesn't change the bounds.
+    fn serve(&self, connection_id: usize, stream: Stream) {
+        let mut jobs = vec![];
+        self.state.lock().unwrap().connected += 1;
+
+        if let Ok(mut connection) = Connection::new(stream) {
+            while let Ok(request) = connection.receive() {
+                let response = self.respond(connection_id, request, &mut jobs);
+
+                if connection.send(&response).is_err() {
+                    break;
+                }
+            }
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.connected -= 1;
+        state.waiting.remove(&connection_id);
+
+        for id in jobs {
+            if let Some((job, _)) = state.running.remove(&id) {
+                state.pending.push_front((id, job));
+            }
+        }
+
+        state.changed = true;
+        self.changed.notify_all();
+    }
+
+    fn respond(&self, connection_id: usize, request: Request, jobs: &mut Vec<usize>) -> Response {
+        let mut state = self.state.lock().unwrap();
+
+        let response = match request {
+            Request::Hello(version) if version == VERSION => Response::Welcome,
+            Request::Hello(version) => {
+                Response::Refused(format!("The worker speaks version {} but the coordinator speaks version {}.", version, VERSION))
+            },
+            Request::Work if state.complete => Response::Done,
+            Request::Sync { .. } if state.complete => Response::Done,
+            Request::Work => match state.pending.pop_front() {
+                Some((id, job)) => {
+                    state.waiting.remove(&connection_id);
+                    state.running.insert(id, (job.clone(), job.bounds.clone()));
+                    jobs.push(id);
+
+                    Response::Job(id, job)
+                },
+                None if state.running.is_empty() => Response::Done,
+                None => {
+                    state.waiting.insert(connection_id);
+                    Response::Wait
+                },
+            },
+            Request::Sync { id, bounds } => {
+                state.improve(&bounds);
+
+                if let Some((_, latest)) = state.running.get_mut(&id) {
+                    if *latest != bounds {
+                        *latest = bounds;
+                        state.changed = true;
+                    }
+                }
+
+                let share = state.waiting.len().saturating_sub(state.pending.len());
+                Response::Bounds { lower_bounds: state.lower_bounds.clone(), share }
+            },
+            Request::Share { jobs } => {
+                state.add_jobs(jobs);
+                state.changed = true;
+
+                Response::Ok
+            },
+            Request::Finished { id, bounds } => {
+                state.improve(&bounds);
+
+                if state.running.remove(&id).is_some() {
+                    state.finished.push(bounds);
+                    state.changed = true;
+                }
+
+                jobs.retain(|&j| j != id);
+                Response::Ok
+            },
+        };
+
+        if state.changed {
+            self.changed.notify_all();
+        }
+
+        response
+    }
+}
+
+impl State {
+    fn add_jobs(&mut self, jobs: Vec<Job>) {
+        for job in jobs {
+            self.improve(&job.bounds);
+            self.pending.push_back((self.next_id, job));
+            self.next_id += 1;
+        }
+    }
+
+    fn improve(&mut self, bounds: &Bounds) {
+        if self.lower_bounds.len() < bounds.lower_bounds.len() {
+            self.lower_bounds.resize(bounds.lower_bounds.len(), 0);
+        }
+
+        for (best, &bound) in self.lower_bounds.iter_mut().zip(&bounds.lower_bounds) {
+            *best = (*best).max(bound);
+        }
+    }
+
+    // Jobs that haven't started yet count with the bounds they start with.
+    fn parts(&self) -> Vec<Bounds> {
+        self.finished.iter()
+            .chain(self.running.values().map(|(_, bounds)| bounds))
+            .chain(self.pending.iter().map(|(_, job)| &job.bounds))
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,206 @@
+mod coordinator;
+mod worker;
+
+use super::bounds::Bounds;
+use super::split::Job;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use std::fmt;
+use std::fs::{metadata, remove_file};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+
+pub use self::coordinator::Coordinator;
+pub use self::worker::Worker;
+
+// A search can also be shared between processes while it runs. A coordinator
+// splits it into jobs and hands them out to workers, which ask for one when
+// they're idle. Each worker sends the bounds of its job to the coordinator
+// every so often and gets back the best lower bounds that any job has found, so
+// that it can prune with those as well. If a worker is idle and there are no
+// jobs left, the next one to send its bounds is asked to give away candidates
+// from its best bucket as new jobs. The coordinator merges the bounds of every
+// job in the same way as `merge`, so they're exact once every job has finished.
+//
+// Every message is a request from a worker followed by a response from the
+// coordinator, written with bincode over TCP or a Unix socket.
+
+const VERSION: u32 = 1;
+
+// Messages are far smaller than this, so a longer one is refused rather than
+// allocated, since its length could be anything if the other end is broken.
+const MESSAGE_LIMIT: u64 = 64 * 1024 * 1024;
+
+/// Where the coordinator listens, written as `tcp:<host>:<port>` or
+/// `unix:<path>`. Without a prefix, a path with a slash in it is a Unix socket
+/// and anything else is a TCP address.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Address {
+    Tcp(String),
+    Unix(String),
+}
+
+impl Address {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+
+        let address = match (input.strip_prefix("tcp:"), input.strip_prefix("unix:")) {
+            (Some(tcp), _) => Address::Tcp(tcp.to_string()),
+            (_, Some(path)) => Address::Unix(path.to_string()),
+            _ if input.contains('/') => Address::Unix(input.to_string()),
+            _ => Address::Tcp(input.to_string()),
+        };
+
+        match &address {
+            Address::Tcp(a) if !a.contains(':') => Err(format!("'{}' is not an address, e.g. localhost:7878 or unix:/tmp/leaps.sock.", input)),
+            Address::Unix(p) if p.is_empty() => Err("The path of the socket must not be empty.".to_string()),
+            _ => Ok(address),
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Address::Tcp(address) => write!(f, "tcp:{}", address),
+            Address::Unix(path) => write!(f, "unix:{}", path),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum Request {
+    Hello(u32),
+    Work,
+    Sync { id: usize, bounds: Bounds },
+    Share { jobs: Vec<Job> },
+    Finished { id: usize, bounds: Bounds },
+}
+
+#[derive(Serialize, Deserialize)]
+enum Response {
+    Welcome,
+    Refused(String),
+    Job(usize, Job),
+    Wait,
+    Done,
+    Bounds { lower_bounds: Vec<usize>, share: usize },
+    Ok,
+}
+
+enum Stream {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl Stream {
+    fn connect(address: &Address) -> io::Result<Self> {
+        match address {
+            Address::Tcp(a) => TcpStream::connect(a).map(Stream::Tcp),
+            Address::Unix(p) => UnixStream::connect(p).map(Stream::Unix),
+        }
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Stream::Tcp(s) => s.try_clone().map(Stream::Tcp),
+            Stream::Unix(s) => s.try_clone().map(Stream::Unix),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(s) => s.read(buf),
+            Stream::Unix(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(s) => s.write(buf),
+            Stream::Unix(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(s) => s.flush(),
+            Stream::Unix(s) => s.flush(),
+        }
+    }
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+impl Listener {
+    // A socket file left behind by an earlier coordinator is replaced, but
+    // anything else at the path is left alone and fails to bind.
+    fn bind(address: &Address) -> io::Result<Self> {
+        match address {
+            Address::Tcp(a) => TcpListener::bind(a).map(Listener::Tcp),
+            Address::Unix(p) => {
+                if metadata(p).is_ok_and(|m| m.file_type().is_socket()) {
+                    remove_file(p)?;
+                }
+
+                UnixListener::bind(p).map(Listener::Unix)
+            },
+        }
+    }
+
+    // Binding to port 0 picks a free port, so the address is read back.
+    fn address(&self, requested: &Address) -> io::Result<Address> {
+        match self {
+            Listener::Tcp(l) => Ok(Address::Tcp(l.local_addr()?.to_string())),
+            Listener::Unix(_) => Ok(requested.clone()),
+        }
+    }
+
+    fn accept(&self) -> io::Result<Stream> {
+        match self {
+            Listener::Tcp(l) => l.accept().map(|(s, _)| Stream::Tcp(s)),
+            Listener::Unix(l) => l.accept().map(|(s, _)| Stream::Unix(s)),
+        }
+    }
+}
+
+struct Connection {
+    reader: BufReader<Stream>,
+    writer: BufWriter<Stream>,
+}
+
+impl Connection {
+    fn new(stream: Stream) -> io::Result<Self> {
+        let writer = BufWriter::new(stream.try_clone()?);
+        let reader = BufReader::new(stream);
+
+        Ok(Self { reader, writer })
+    }
+
+    fn send<T: Serialize>(&mut self, message: &T) -> io::Result<()> {
+        bincode::config().limit(MESSAGE_LIMIT).serialize_into(&mut self.writer, message).map_err(Self::to_io)?;
+        self.writer.flush()
+    }
+
+    fn receive<T: DeserializeOwned>(&mut self) -> io::Result<T> {
+        bincode::config().limit(MESSAGE_LIMIT).deserialize_from(&mut self.reader).map_err(Self::to_io)
+    }
+
+    fn to_io(error: bincode::Error) -> io::Error {
+        io::Error::other(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test;
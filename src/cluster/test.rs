@@ -0,0 +1,198 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use super::*;
+use super::super::candidate::Candidate;
+use super::super::disk::Codec;
+use super::super::frontier::Frontier;
+use super::super::search::Search;
+
+use std::fs::create_dir_all;
+use std::thread::{self, sleep, JoinHandle};
+use std::time::Duration;
+
+type Subject = Coordinator;
+
+const N: usize = 4;
+const EXACT: [usize; 7] = [4, 8, 12, 14, 18, 20, 24];
+
+fn socket(test_id: &'static str) -> Address {
+    create_dir_all("/tmp/superpermutation-test").unwrap();
+    Address::Unix(format!("/tmp/superpermutation-test/{}.sock", test_id))
+}
+
+fn start(scratch_dir: String) -> impl FnMut(usize, Job) -> Search {
+    move |_id, job| {
+        let frontier = Frontier::new(1.0, &scratch_dir, Codec::Raw, job.n).unwrap();
+        Search::from_prefix(frontier, job.bounds, job.prefix, job.n)
+    }
+}
+
+fn spawn_worker(address: &Address, test_id: &'static str, index: usize) -> JoinHandle<usize> {
+    let mut worker = Worker::connect(address).unwrap();
+    worker.sync_every(Duration::from_millis(1));
+
+    let scratch_dir = format!("/tmp/superpermutation-test/{}/worker-{}", test_id, index);
+    thread::spawn(move || worker.run(start(scratch_dir)).unwrap())
+}
+
+mod address {
+    use super::*;
+
+    #[test]
+    fn it_parses_tcp_addresses_and_unix_sockets() {
+        assert_eq!(Address::parse("localhost:7878"), Ok(Address::Tcp("localhost:7878".to_string())));
+        assert_eq!(Address::parse("tcp:127.0.0.1:0"), Ok(Address::Tcp("127.0.0.1:0".to_string())));
+        assert_eq!(Address::parse("/tmp/leaps.sock"), Ok(Address::Unix("/tmp/leaps.sock".to_string())));
+        assert_eq!(Address::parse("unix:leaps.sock"), Ok(Address::Unix("leaps.sock".to_string())));
+    }
+
+    #[test]
+    fn it_returns_an_error_for_an_address_without_a_port() {
+        let expected = "'localhost' is not an address, e.g. localhost:7878 or unix:/tmp/leaps.sock.";
+        assert_eq!(Address::parse("localhost"), Err(expected.to_string()));
+    }
+}
+
+mod connection {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn it_refuses_a_message_that_is_longer_than_the_limit() {
+        let (ours, mut theirs) = UnixStream::pair().unwrap();
+        let mut subject = Connection::new(Stream::Unix(ours)).unwrap();
+
+        // A refusal with a reason that claims to be a terabyte long.
+        theirs.write_all(&1u32.to_le_bytes()).unwrap();
+        theirs.write_all(&(1u64 << 40).to_le_bytes()).unwrap();
+
+        assert_eq!(subject.receive::<Response>().is_err(), true);
+    }
+}
+
+mod run {
+    use super::*;
+
+    #[test]
+    fn it_merges_the_bounds_of_every_job() {
+        let address = socket("cluster-1");
+        let subject = Subject::bind(&address, Job::split(Bounds::new(N), 2, N), N).unwrap();
+
+        let workers = (0..3).map(|i| spawn_worker(&address, "cluster-1", i)).collect::<Vec<_>>();
+        let bounds = subject.run(|_| (), |_| ()).unwrap();
+
+        assert_eq!(bounds.lower_bounds, EXACT);
+        assert_eq!(bounds.trusted, EXACT.len());
+
+        // Jobs that are still pending when the bounds are complete are never
+        // handed out.
+        let searched: usize = workers.into_iter().map(|w| w.join().unwrap()).sum();
+        assert!(searched <= subject.jobs());
+    }
+
+    #[test]
+    fn it_works_over_tcp_and_reports_progress() {
+        let address = Address::Tcp("127.0.0.1:0".to_string());
+        let subject = Subject::bind(&address, Job::split(Bounds::new(N), 1, N), N).unwrap();
+
+        let worker = spawn_worker(subject.address(), "cluster-2", 0);
+        let mut exact_rows = vec![];
+        let bounds = subject.run(|b| exact_rows.push(b.trusted), |_| ()).unwrap();
+
+        assert_eq!(bounds.lower_bounds, EXACT);
+        assert!(worker.join().unwrap() <= 3);
+
+        assert!(exact_rows.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(exact_rows.last(), Some(&EXACT.len()));
+    }
+
+    #[test]
+    fn it_asks_a_busy_worker_to_share_its_candidates_with_an_idle_one() {
+        let address = socket("cluster-3");
+        let subject = Subject::bind(&address, Job::split(Bounds::new(N), 0, N), N).unwrap();
+
+        // The first worker is driven by hand so that it only carries on with
+        // its job once the coordinator has asked it to share.
+        let mut connection = Connection::new(Stream::connect(&address).unwrap()).unwrap();
+        connection.send(&Request::Hello(VERSION)).unwrap();
+        assert!(matches!(connection.receive().unwrap(), Response::Welcome));
+
+        connection.send(&Request::Work).unwrap();
+        let job = match connection.receive().unwrap() {
+            Response::Job(0, job) => job,
+            _ => panic!("Expected the first job"),
+        };
+
+        let frontier = Frontier::new(1.0, "/tmp/superpermutation-test/cluster-3/worker-0", Codec::Raw, N).unwrap();
+        let mut search = Search::from_prefix(frontier, job.bounds, job.prefix, N);
+        search.run_until(|s| s.frontier().len() > 1);
+
+        let second = spawn_worker(&address, "cluster-3", 1);
+
+        let share = loop {
+            connection.send(&Request::Sync { id: 0, bounds: search.bounds().clone() }).unwrap();
+
+            match connection.receive().unwrap() {
+                Response::Bounds { share: 0, .. } => sleep(Duration::from_millis(1)),
+                Response::Bounds { share, .. } => break share,
+                _ => panic!("Expected the merged bounds"),
+            }
+        };
+
+        let candidates = search.frontier_mut().next_batch(share);
+        let jobs = candidates.into_iter().map(|c| Job::new(c, search.bounds().clone(), N)).collect();
+
+        connection.send(&Request::Share { jobs }).unwrap();
+        assert!(matches!(connection.receive().unwrap(), Response::Ok));
+
+        search.run();
+        connection.send(&Request::Finished { id: 0, bounds: search.bounds().clone() }).unwrap();
+        assert!(matches!(connection.receive().unwrap(), Response::Ok));
+        drop(connection);
+
+        let bounds = subject.run(|_| (), |_| ()).unwrap();
+        assert_eq!(bounds.lower_bounds, EXACT);
+
+        assert!(subject.jobs() > 1);
+        assert!(second.join().unwrap() < subject.jobs());
+    }
+
+    #[test]
+    fn it_hands_out_a_job_again_if_its_worker_goes_away() {
+        let address = socket("cluster-4");
+        let subject = Subject::bind(&address, Job::split(Bounds::new(N), 1, N), N).unwrap();
+
+        let mut connection = Connection::new(Stream::connect(&address).unwrap()).unwrap();
+        connection.send(&Request::Hello(VERSION)).unwrap();
+        assert!(matches!(connection.receive().unwrap(), Response::Welcome));
+
+        connection.send(&Request::Work).unwrap();
+        assert!(matches!(connection.receive().unwrap(), Response::Job(0, _)));
+        drop(connection);
+
+        let worker = spawn_worker(&address, "cluster-4", 0);
+        let bounds = subject.run(|_| (), |_| ()).unwrap();
+
+        assert_eq!(bounds.lower_bounds, EXACT);
+        assert!(worker.join().unwrap() <= 3);
+    }
+}
+
+mod connect {
+    use super::*;
+
+    #[test]
+    fn it_refuses_a_worker_with_a_different_version() {
+        let address = socket("cluster-5");
+        let job = Job::new(Candidate::seed(N), Bounds::new(N), N);
+        let _subject = Subject::bind(&address, vec![job], N).unwrap();
+
+        let mut connection = Connection::new(Stream::connect(&address).unwrap()).unwrap();
+        connection.send(&Request::Hello(VERSION + 1)).unwrap();
+
+        match connection.receive().unwrap() {
+            Response::Refused(reason) => assert_eq!(reason, "The worker speaks version 2 but the coordinator speaks version 1."),
+            _ => panic!("Expected the worker to be refused"),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use super::{Address, Connection, Request, Response, Stream, VERSION};
+use super::super::search::{Search, Step};
+use super::super::split::Job;
+
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Searches jobs from a coordinator until there are none left.
+pub struct Worker {
+    connection: Connection,
+    interval: Duration,
+}
+
+impl Worker {
+    pub fn connect(address: &Address) -> Result<Self, String> {
+        let fail = |e: std::io::Error| format!("Failed to connect to the coordinator at {}: {}", address, e);
+
+        let stream = Stream::connect(address).map_err(fail)?;
+        let connection = Connection::new(stream).map_err(fail)?;
+        let mut worker = Self { connection, interval: Duration::from_secs(1) };
+
+        match worker.request(Request::Hello(VERSION))? {
+            Response::Welcome => Ok(worker),
+            Response::Refused(reason) => Err(format!("The coordinator at {} refused to connect: {}", address, reason)),
+            _ => Err(Self::unexpected()),
+        }
+    }
+
+    /// How often to send the bounds to the coordinator, and to ask again for a
+    /// job when there isn't one yet (default: every second).
+    pub fn sync_every(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Searches each job it's given with the search `start` returns for it,
+    /// e.g. from `Search::from_prefix`. Returns how many jobs it searched.
+    pub fn run<F: FnMut(usize, Job) -> Search>(&mut self, mut start: F) -> Result<usize, String> {
+        let mut searched = 0;
+
+        loop {
+            match self.request(Request::Work)? {
+                Response::Job(id, job) => {
+                    self.search(id, start(id, job))?;
+                    searched += 1;
+                },
+                Response::Wait => sleep(self.interval),
+                Response::Done => return Ok(searched),
+                _ => return Err(Self::unexpected()),
+            }
+        }
+    }
+
+    // A job that runs out of candidates is finished as well, since there are
+    // no more strings that start with its prefix. A job is dropped halfway if
+    // the coordinator already has every bound it needs.
+    fn search(&mut self, id: usize, mut search: Search) -> Result<(), String> {
+        loop {
+            let started = Instant::now();
+
+            match search.run_until(|_| started.elapsed() >= self.interval) {
+                Step::Running if self.sync(id, &mut search)? => continue,
+                Step::Running => return Ok(()),
+                Step::Finished(_) | Step::Exhausted => {
+                    let bounds = search.bounds().clone();
+                    return self.request(Request::Finished { id, bounds }).map(|_| ());
+                },
+                Step::Stopped => return Err(search.frontier().storage_error().unwrap().to_string()),
+            }
+        }
+    }
+
+    // Returns false if the coordinator doesn't need the job any more.
+    fn sync(&mut self, id: usize, search: &mut Search) -> Result<bool, String> {
+        let bounds = search.bounds().clone();
+
+        let (lower_bounds, share) = match self.request(Request::Sync { id, bounds })? {
+            Response::Bounds { lower_bounds, share } => (lower_bounds, share),
+            Response::Done => return Ok(false),
+            _ => return Err(Self::unexpected()),
+        };
+
+        let wasted_symbols = search.bounds().lower_bounds.len() - 1;
+
+        if let Some(&permutations) = lower_bounds.get(wasted_symbols) {
+            search.raise_lower_bound(wasted_symbols, permutations);
+        }
+
+        // At least one candidate is kept so that the job isn't left with
+        // nothing to search.
+        if share > 0 && search.frontier().len() > share {
+            let n = search.n();
+            let candidates = search.frontier_mut().next_batch(share);
+            let jobs = candidates.into_iter().map(|c| Job::new(c, search.bounds().clone(), n)).collect();
+
+            self.request(Request::Share { jobs })?;
+        }
+
+        Ok(true)
+    }
+
+    fn request(&mut self, request: Request) -> Result<Response, String> {
+        let lost = |e| format!("Lost the connection to the coordinator: {}", e);
+
+        self.connection.send(&request).map_err(lost)?;
+        self.connection.receive().map_err(lost)
+    }
+
+    fn unexpected() -> String {
+        "The coordinator sent an unexpected response.".to_string()
+    }
+}
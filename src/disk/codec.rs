@@ -0,0 +1,167 @@
+use std::io::{self, Read, Write};
+
+// Each file records the codec that wrote it so that files written with
+// different codecs can be read back in the same run.
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Codec {
+    Raw,
+    Zlib(u32),
+    Bitset,
+}
+
+pub const DEFAULT_ZLIB_LEVEL: u32 = 6;
+
+// The bitset codec is a run-length encoding that is much faster than zlib. The
+// permutations_seen bitsets take up most of each file and are mostly made of
+// 0x00 bytes early in the search and 0xFF bytes later on. Each control byte is
+// followed by up to 128 literal bytes or stands for a run of up to 64 of them.
+
+const MAX_LITERALS: usize = 128;
+const MAX_RUN: usize = 64;
+const ZEROS: u8 = 0x80;
+const ONES: u8 = 0xC0;
+
+pub struct BitsetEncoder<W: Write> {
+    inner: W,
+    literals: Vec<u8>,
+    run: Option<(u8, usize)>,
+}
+
+impl<W: Write> BitsetEncoder<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner, literals: Vec::with_capacity(MAX_LITERALS), run: None }
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.end_run()?;
+        self.write_literals()?;
+
+        Ok(self.inner)
+    }
+
+    fn push(&mut self, byte: u8) -> io::Result<()> {
+        if byte == 0x00 || byte == 0xFF {
+            if let Some((run_byte, len)) = &mut self.run {
+                if *run_byte == byte && *len < MAX_RUN {
+                    *len += 1;
+                    return Ok(());
+                }
+            }
+
+            self.end_run()?;
+            self.run = Some((byte, 1));
+
+            return Ok(());
+        }
+
+        self.end_run()?;
+        self.push_literal(byte)
+    }
+
+    // A run of one byte is cheaper to write as a literal.
+    fn end_run(&mut self) -> io::Result<()> {
+        match self.run.take() {
+            None => Ok(()),
+            Some((byte, 1)) => self.push_literal(byte),
+            Some((byte, len)) => {
+                self.write_literals()?;
+
+                let control = if byte == 0x00 { ZEROS } else { ONES };
+                self.inner.write_all(&[control + (len - 1) as u8])
+            },
+        }
+    }
+
+    fn push_literal(&mut self, byte: u8) -> io::Result<()> {
+        self.literals.push(byte);
+
+        match self.literals.len() == MAX_LITERALS {
+            true => self.write_literals(),
+            false => Ok(()),
+        }
+    }
+
+    fn write_literals(&mut self) -> io::Result<()> {
+        if self.literals.is_empty() {
+            return Ok(());
+        }
+
+        self.inner.write_all(&[(self.literals.len() - 1) as u8])?;
+        self.inner.write_all(&self.literals)?;
+        self.literals.clear();
+
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for BitsetEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            self.push(byte)?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub struct BitsetDecoder<R: Read> {
+    inner: R,
+    literals: usize,
+    run: (u8, usize),
+}
+
+impl<R: Read> BitsetDecoder<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, literals: 0, run: (0, 0) }
+    }
+
+    // Returns false at the end of the stream.
+    fn read_control(&mut self) -> io::Result<bool> {
+        let mut control = [0];
+
+        if self.inner.read(&mut control)? == 0 {
+            return Ok(false);
+        }
+
+        match control[0] {
+            c if c < ZEROS => self.literals = c as usize + 1,
+            c if c < ONES => self.run = (0x00, (c - ZEROS) as usize + 1),
+            c => self.run = (0xFF, (c - ONES) as usize + 1),
+        }
+
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for BitsetDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.literals == 0 && self.run.1 == 0 && !self.read_control()? {
+            return Ok(0);
+        }
+
+        if self.literals > 0 {
+            let len = self.literals.min(buf.len());
+            self.inner.read_exact(&mut buf[..len])?;
+            self.literals -= len;
+
+            return Ok(len);
+        }
+
+        let (byte, remaining) = self.run;
+        let len = remaining.min(buf.len());
+
+        buf[..len].iter_mut().for_each(|b| *b = byte);
+        self.run.1 -= len;
+
+        Ok(len)
+    }
+}
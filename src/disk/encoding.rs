@@ -0,0 +1,206 @@
+use super::super::candidate::{AncestryId, Candidate};
+
+use bit_set::BitSet;
+use std::io::{self, Read, Write};
+
+// Candidates in a bucket usually have very similar permutations_seen bitsets,
+// so each bitset is written as whichever is smallest of: the full bitset, the
+// sorted list of permutations it has seen, or the list of bits that differ
+// from the previous candidate's. Lists are written as gaps between ids and
+// every number is written as a varint so that small numbers take one byte.
+
+const FULL: u8 = 0;
+const SPARSE: u8 = 1;
+const DELTA: u8 = 2;
+
+pub struct BucketEncoder {
+    previous: BitSet,
+    bytes: usize,
+    record: Vec<u8>,
+    sparse: Vec<u8>,
+    delta: Vec<u8>,
+}
+
+impl BucketEncoder {
+    pub fn new(n: usize) -> Self {
+        let bytes = bytes_per_bitset(n);
+        Self { previous: BitSet::new(), bytes, record: vec![], sparse: vec![], delta: vec![] }
+    }
+
+    pub fn write<W: Write>(&mut self, writer: &mut W, candidate: &Candidate) -> io::Result<()> {
+        let Candidate { permutations_seen, tail_of_string, wasted_symbols, ancestry_id } = candidate;
+
+        self.record.clear();
+        write_varint(&mut self.record, tail_of_string.len() as u64);
+        self.record.extend_from_slice(tail_of_string);
+        write_varint(&mut self.record, *wasted_symbols as u64);
+        write_varint(&mut self.record, u64::from(*ancestry_id));
+
+        write_ids(&mut self.sparse, permutations_seen.iter());
+        write_ids(&mut self.delta, permutations_seen.symmetric_difference(&self.previous));
+
+        if self.delta.len() <= self.sparse.len() && self.delta.len() < self.bytes {
+            self.record.push(DELTA);
+            self.record.extend_from_slice(&self.delta);
+        } else if self.sparse.len() < self.bytes {
+            self.record.push(SPARSE);
+            self.record.extend_from_slice(&self.sparse);
+        } else {
+            let mut full = permutations_seen.get_ref().to_bytes();
+            full.resize(self.bytes, 0);
+
+            self.record.push(FULL);
+            self.record.extend_from_slice(&full);
+        }
+
+        self.previous.clone_from(permutations_seen);
+        writer.write_all(&self.record)
+    }
+}
+
+/// Reads the candidates written by a `BucketEncoder`. Anything that couldn't
+/// have been written for `n` symbols is an `InvalidData` error, rather than a
+/// candidate that the search would go on to expand.
+pub struct BucketDecoder {
+    previous: BitSet,
+    bytes: usize,
+    n: usize,
+    permutations: usize,
+}
+
+impl BucketDecoder {
+    pub fn new(n: usize) -> Self {
+        let bytes = bytes_per_bitset(n);
+        let permutations = (1..=n).product();
+
+        Self { previous: BitSet::from_bytes(&vec![0; bytes]), bytes, n, permutations }
+    }
+
+    pub fn read<R: Read>(&mut self, reader: &mut R) -> io::Result<Candidate> {
+        let len = read_varint(reader)?;
+
+        if len >= self.n as u64 {
+            return Err(invalid(format!("it has a tail of {} symbols, but the tail is at most {}", len, self.n - 1)));
+        }
+
+        let mut tail_of_string = vec![0; len as usize];
+        reader.read_exact(&mut tail_of_string)?;
+
+        if let Some(&symbol) = tail_of_string.iter().find(|&&s| s as usize >= self.n) {
+            return Err(invalid(format!("it has the symbol {} in a tail, but there are only {}", symbol, self.n)));
+        }
+
+        let wasted_symbols = read_varint(reader)? as u16;
+        let ancestry_id = match read_varint(reader)? {
+            id if id <= AncestryId::MAX => AncestryId::from(id),
+            _ => return Err(invalid("it has an ancestry id that is too large".to_string())),
+        };
+
+        let mut tag = [0];
+        reader.read_exact(&mut tag)?;
+
+        let permutations_seen = match tag[0] {
+            FULL => {
+                let mut bytes = vec![0; self.bytes];
+                reader.read_exact(&mut bytes)?;
+
+                let bitset = BitSet::from_bytes(&bytes);
+
+                if (self.permutations..self.bytes * 8).any(|id| bitset.contains(id)) {
+                    return Err(invalid(format!("it has a permutation id that isn't less than {}", self.permutations)));
+                }
+
+                bitset
+            },
+            SPARSE => {
+                let mut bitset = BitSet::from_bytes(&vec![0; self.bytes]);
+                read_ids(reader, self.permutations, |id| { bitset.insert(id); })?;
+
+                bitset
+            },
+            DELTA => {
+                let mut bitset = self.previous.clone();
+                read_ids(reader, self.permutations, |id| if !bitset.remove(id) { bitset.insert(id); })?;
+
+                bitset
+            },
+            other => return Err(invalid(format!("it has an unknown bitset encoding {}", other))),
+        };
+
+        self.previous.clone_from(&permutations_seen);
+
+        Ok(Candidate { permutations_seen, tail_of_string, wasted_symbols, ancestry_id })
+    }
+}
+
+fn bytes_per_bitset(n: usize) -> usize {
+    (1..=n).product::<usize>().div_ceil(8)
+}
+
+// The number of ids isn't known until they've been written so it is inserted
+// at the front afterwards.
+fn write_ids<I: Iterator<Item=usize>>(buffer: &mut Vec<u8>, ids: I) {
+    let mut len = 0;
+    let mut previous = 0;
+
+    buffer.clear();
+
+    for id in ids {
+        write_varint(buffer, (id - previous) as u64);
+
+        len += 1;
+        previous = id + 1;
+    }
+
+    let mut prefix = Vec::with_capacity(10);
+    write_varint(&mut prefix, len);
+
+    buffer.splice(0..0, prefix);
+}
+
+// Each id must be less than `max`, the number of permutations.
+fn read_ids<R: Read, F: FnMut(usize)>(reader: &mut R, max: usize, mut f: F) -> io::Result<()> {
+    let len = read_varint(reader)?;
+    let mut previous = 0;
+
+    for _ in 0..len {
+        let id = match read_varint(reader)?.checked_add(previous as u64) {
+            Some(id) if id < max as u64 => id as usize,
+            _ => return Err(invalid(format!("it has a permutation id that isn't less than {}", max))),
+        };
+
+        f(id);
+        previous = id + 1;
+    }
+
+    Ok(())
+}
+
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+
+    buffer.push(value as u8);
+}
+
+fn read_varint<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut value = 0;
+    let mut byte = [0];
+
+    for shift in (0..64).step_by(7) {
+        reader.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7F) as u64) << shift;
+
+        if byte[0] < 0x80 {
+            return Ok(value);
+        }
+    }
+
+    Err(invalid("it has a number that is too long".to_string()))
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
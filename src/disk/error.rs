@@ -0,0 +1,56 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Why a scratch file couldn't be used. Each has the name of the file.
+#[derive(Debug)]
+pub enum Error {
+    Read(String, io::Error),
+    Write(String, io::Error),
+    Remove(String, io::Error),
+    Create(String, io::Error),
+    Incompatible(String, String),
+    Corrupt(String, String),
+}
+
+impl Error {
+    pub fn from_bincode(filename: &str, error: bincode::ErrorKind, writing: bool) -> Self {
+        match (error, writing) {
+            (bincode::ErrorKind::Io(e), true) => Error::Write(filename.to_string(), e),
+            (bincode::ErrorKind::Io(e), false) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                Error::Corrupt(filename.to_string(), "the file ends too soon.".to_string())
+            },
+            (bincode::ErrorKind::Io(e), false) if e.kind() == io::ErrorKind::InvalidData => {
+                Error::Corrupt(filename.to_string(), format!("{}.", e))
+            },
+            (bincode::ErrorKind::Io(e), false) => Error::Read(filename.to_string(), e),
+            (other, _) => Error::Corrupt(filename.to_string(), format!("{}.", other)),
+        }
+    }
+
+    /// Problems with reading or writing the file might go away if we try again,
+    /// but a file that is missing, corrupt or incompatible will stay that way.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Read(_, e) | Error::Write(_, e) | Error::Remove(_, e) | Error::Create(_, e) => {
+                !matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied)
+            },
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Read(filename, e) => write!(f, "Failed to read {}: {}", filename, e),
+            Error::Write(filename, e) => write!(f, "Failed to write {}: {}", filename, e),
+            Error::Remove(filename, e) => write!(f, "Failed to remove {}: {}", filename, e),
+            Error::Create(path, e) => write!(f, "Failed to create {}: {}", path, e),
+            Error::Incompatible(filename, reason) => write!(f, "Failed to read {}: {}", filename, reason),
+            Error::Corrupt(filename, reason) => write!(f, "Failed to read {}: {}", filename, reason),
+        }
+    }
+}
+
+impl error::Error for Error { }
@@ -1,73 +1,328 @@
+mod codec;
+mod encoding;
+mod error;
+mod reader;
+
 use super::candidate::Candidate;
 
-use std::collections::VecDeque;
-use std::fs::{File, create_dir_all, remove_dir_all, remove_file};
-use std::io::{BufWriter, BufReader};
+use std::collections::{HashSet, VecDeque};
+use std::fs::{File, create_dir_all, metadata, read_dir, remove_dir_all, remove_file};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
 use std::sync::{Arc, Mutex};
 
-use flate2::{Compression, read::ZlibDecoder, write::ZlibEncoder};
-use bincode::{serialize_into, deserialize_from};
+use flate2::{Compression, CrcWriter, write::ZlibEncoder};
+use self::codec::BitsetEncoder;
+use self::encoding::BucketEncoder;
+use bincode::serialize_into;
+
+pub use self::codec::{Codec, DEFAULT_ZLIB_LEVEL};
+pub use self::error::Error;
+pub use self::reader::ChunkReader;
 
 const SPLIT_SIZE: usize = 222_222;
 
+const MAGIC: [u8; 8] = *b"LEAPBNDS";
+const VERSION: u32 = 3;
+
+/// The range of file numbers that haven't been read yet for each bucket, by
+/// wasted symbols and then permutations.
+pub type Index = Vec<Vec<Option<(usize, usize)>>>;
+
+/// Scratch files for buckets of candidates that don't fit in memory. Each
+/// bucket can have several files, which are read back in the order they were
+/// written.
 pub struct Disk {
     path: String,
-    gzip: bool,
-    index: Arc<Mutex<Vec<Vec<Option<(usize, usize)>>>>>,
+    codec: Codec,
+    n: usize,
+    index: Arc<Mutex<Index>>,
+    usage: Mutex<Usage>,
+    consumed: Mutex<Option<Vec<String>>>,
+}
+
+/// How many candidates are on disk and how many bytes have been written and read
+/// since the search started. Bytes are counted a whole file at a time.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    pub candidates: u64,
+    pub bytes_written: u64,
+    pub bytes_read: u64,
+}
+
+// Each file starts with a header that describes its contents. The checksum is
+// of the bytes that follow the header, as they were written to disk. It isn't
+// known until they have been written so the header is written again at the end.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Header {
+    magic: [u8; 8],
+    version: u32,
+    n: u32,
+    codec: Codec,
+    count: u64,
+    checksum: u32,
 }
 
 impl Disk {
-    pub fn new(path: String, gzip: bool) -> Self {
+    /// Creates an empty scratch directory, removing anything that was in it.
+    pub fn new(path: String, codec: Codec, n: usize) -> Result<Self, Error> {
         let _ = remove_dir_all(&path);
-        create_dir_all(&path).expect(&format!("Failed to create {}", path));
+        create_dir_all(&path).map_err(|e| Error::Create(path.clone(), e))?;
 
         let index = Arc::new(Mutex::new(vec![]));
-        Self { path, gzip, index }
+        Ok(Self { path, codec, n, index, usage: Mutex::new(Usage::default()), consumed: Mutex::new(None) })
     }
 
-    pub fn read(&self, wasted_symbols: usize, permutations: usize) -> Option<VecDeque<Candidate>> {
-        let filename = self.filename_for_reading(wasted_symbols, permutations)?;
-        let file = File::open(&filename).expect(&format!("Failed to open {}", filename));
+    /// Reopens a scratch directory from a checkpoint. Any files that were written
+    /// after the checkpoint was saved are not in its index so they are removed.
+    pub fn open(path: String, codec: Codec, n: usize, index: Index, usage: Usage) -> Result<Self, Error> {
+        create_dir_all(&path).map_err(|e| Error::Create(path.clone(), e))?;
 
-        let mut reader = BufReader::new(file);
+        let index = Arc::new(Mutex::new(index));
+        let disk = Self { path, codec, n, index, usage: Mutex::new(usage), consumed: Mutex::new(None) };
 
-        let candidates = if self.gzip {
-            let mut decoder = ZlibDecoder::new(reader);
-            deserialize_from(&mut decoder).unwrap()
-        } else {
-            deserialize_from(&mut reader).unwrap()
+        disk.remove_unindexed_files()?;
+        Ok(disk)
+    }
+
+    /// Files that have been read are kept until the next checkpoint is saved so
+    /// that the previous checkpoint can still be resumed from if we crash.
+    pub fn defer_removals(&self) {
+        *self.consumed.lock().unwrap() = Some(vec![]);
+    }
+
+    /// Tries to remove every file that has been read, and returns an error for
+    /// each one that couldn't be.
+    pub fn remove_consumed(&self) -> Vec<Error> {
+        let mut consumed = self.consumed.lock().unwrap();
+
+        let consumed = match consumed.as_mut() {
+            Some(consumed) => consumed,
+            None => return vec![],
         };
 
-        remove_file(&filename).expect(&format!("Failed to remove {}", filename));
+        consumed.drain(..).filter_map(|filename| {
+            remove_file(&filename).err().map(|e| Error::Remove(filename, e))
+        }).collect()
+    }
+
+    pub fn index(&self) -> Index {
+        self.index.lock().unwrap().clone()
+    }
+
+    pub fn usage(&self) -> Usage {
+        *self.usage.lock().unwrap()
+    }
+
+    /// Candidates are counted off as they're streamed rather than when the file
+    /// is consumed so that they aren't counted on disk and in memory at once.
+    pub(crate) fn mark_read(&self, candidates: usize) {
+        self.usage.lock().unwrap().candidates -= candidates as u64;
+    }
+
+    /// Whether the bucket has files that haven't been read yet.
+    pub fn has_files(&self, wasted_symbols: usize, permutations: usize) -> bool {
+        self.peek_index_to_read_from(wasted_symbols, permutations).is_some()
+    }
+
+    /// The buckets that have files that haven't been read yet.
+    pub fn buckets(&self) -> Vec<(usize, usize)> {
+        let index = self.index.lock().unwrap();
 
-        Some(candidates)
+        index.iter().enumerate().flat_map(|(w, nested)| {
+            nested.iter().enumerate().filter_map(move |(p, files)| match files {
+                Some((min, max)) if min <= max => Some((w, p)),
+                _ => None,
+            })
+        }).collect()
     }
 
-    pub fn write(&self, bucket: VecDeque<Candidate>, wasted_symbols: usize, permutations: usize) {
+    pub fn min_waste(&self) -> Option<usize> {
+        let index = self.index.lock().unwrap();
+
+        index.iter().position(|nested| {
+            nested.iter().any(|files| matches!(files, Some((min, max)) if min <= max))
+        })
+    }
+
+    #[cfg(test)]
+    pub fn read(&self, wasted_symbols: usize, permutations: usize) -> Result<Option<VecDeque<Candidate>>, Error> {
+        let reader = match self.stream(wasted_symbols, permutations)? {
+            None => return Ok(None),
+            Some(reader) => reader,
+        };
+
+        let candidates: VecDeque<Candidate> = reader.collect::<Result<_, _>>()?;
+        self.mark_read(candidates.len());
+        self.consume(wasted_symbols, permutations)?;
+
+        Ok(Some(candidates))
+    }
+
+    /// Opens the next file for the bucket without moving the index past it. That
+    /// happens when consume is called, after every candidate has been read, so a
+    /// file can be opened again if there was an error or from a checkpoint.
+    pub fn stream(&self, wasted_symbols: usize, permutations: usize) -> Result<Option<ChunkReader>, Error> {
+        let index = match self.peek_index_to_read_from(wasted_symbols, permutations) {
+            None => return Ok(None),
+            Some(index) => index,
+        };
+
+        let filename = format!("{}.{}", self.basename(wasted_symbols, permutations), index);
+        let reader = ChunkReader::open(&filename, |header| self.check(header))?;
+
+        Ok(Some(reader))
+    }
+
+    pub fn consume(&self, wasted_symbols: usize, permutations: usize) -> Result<(), Error> {
+        let index = match self.index_to_read_from(wasted_symbols, permutations) {
+            None => return Ok(()),
+            Some(index) => index,
+        };
+
+        let filename = format!("{}.{}", self.basename(wasted_symbols, permutations), index);
+
+        if let Ok(m) = metadata(&filename) {
+            self.usage.lock().unwrap().bytes_read += m.len();
+        }
+
+        match self.consumed.lock().unwrap().as_mut() {
+            Some(consumed) => consumed.push(filename),
+            None => remove_file(&filename).map_err(|e| Error::Remove(filename, e))?,
+        }
+
+        Ok(())
+    }
+
+    /// If the file can't be written it is removed and taken out of the index so
+    /// that the candidates can be written again later.
+    pub fn write(&self, candidates: &[Candidate], wasted_symbols: usize, permutations: usize) -> Result<(), Error> {
         let filename = self.filename_for_writing(wasted_symbols, permutations);
-        let file = File::create(&filename).expect(&format!("Failed to create {}", filename));
 
+        let result = File::create(&filename)
+            .map_err(|e| Error::Write(filename.clone(), e))
+            .and_then(|file| self.write_file(file, candidates).map_err(|e| Error::from_bincode(&filename, *e, true)));
+
+        match result {
+            Ok(bytes) => {
+                let mut usage = self.usage.lock().unwrap();
+
+                usage.candidates += candidates.len() as u64;
+                usage.bytes_written += bytes;
+
+                Ok(())
+            },
+            Err(error) => {
+                let _ = remove_file(&filename);
+                self.unindex_last(wasted_symbols, permutations);
+
+                Err(error)
+            },
+        }
+    }
+
+    /// Candidates are removed from the bucket as each chunk is written so that
+    /// if there's an error, the ones that weren't written are left in it.
+    pub fn write_chunks(&self, bucket: &mut VecDeque<Candidate>, wasted_symbols: usize, permutations: usize) -> Result<(), Error> {
+        self.write_chunks_with(bucket, wasted_symbols, permutations, |_| ())
+    }
+
+    /// Like `write_chunks`, but calls `written` with the number of candidates
+    /// in each chunk once it has been written.
+    pub fn write_chunks_with<F: FnMut(usize)>(&self, bucket: &mut VecDeque<Candidate>, wasted_symbols: usize, permutations: usize, mut written: F) -> Result<(), Error> {
+        while !bucket.is_empty() {
+            let len = match bucket.len() > SPLIT_SIZE * 2 {
+                true => SPLIT_SIZE,
+                false => bucket.len(),
+            };
+
+            self.write(&bucket.make_contiguous()[..len], wasted_symbols, permutations)?;
+            bucket.drain(..len);
+            written(len);
+        }
+
+        Ok(())
+    }
+
+    // Returns the size of the file that was written.
+    fn write_file(&self, file: File, candidates: &[Candidate]) -> bincode::Result<u64> {
         let mut writer = BufWriter::new(file);
 
-        if self.gzip {
-            let mut encoder = ZlibEncoder::new(writer, Compression::default());
-            serialize_into(&mut encoder, &bucket).unwrap();
-        } else {
-            serialize_into(&mut writer, &bucket).unwrap();
+        let mut header = Header {
+            magic: MAGIC,
+            version: VERSION,
+            n: self.n as u32,
+            codec: self.codec,
+            count: candidates.len() as u64,
+            checksum: 0,
+        };
+
+        serialize_into(&mut writer, &header)?;
+        let mut body = CrcWriter::new(writer);
+
+        match header.codec {
+            Codec::Raw => self.write_body(&mut body, candidates)?,
+            Codec::Zlib(level) => {
+                let mut encoder = ZlibEncoder::new(&mut body, Compression::new(level));
+                self.write_body(&mut encoder, candidates)?;
+                encoder.finish()?;
+            },
+            Codec::Bitset => {
+                let mut encoder = BitsetEncoder::new(&mut body);
+                self.write_body(&mut encoder, candidates)?;
+                encoder.finish()?;
+            },
+        }
+
+        header.checksum = body.crc().sum();
+
+        let mut writer = body.into_inner();
+        writer.seek(SeekFrom::Start(0))?;
+        serialize_into(&mut writer, &header)?;
+        writer.flush()?;
+
+        Ok(writer.get_ref().metadata()?.len())
+    }
+
+    // Candidates are written in order of their permutations so that each one
+    // is likely to be similar to the previous one, which makes deltas smaller.
+    // This loses the order they were added in, which --priority fifo uses, but
+    // the bounds are the same in any order.
+    fn write_body<W: Write>(&self, writer: &mut W, candidates: &[Candidate]) -> bincode::Result<()> {
+        let mut encoder = BucketEncoder::new(self.n);
+        serialize_into(&mut *writer, &(candidates.len() as u64))?;
+
+        let mut sorted: Vec<&Candidate> = candidates.iter().collect();
+        sorted.sort_unstable_by(|a, b| {
+            let a = a.permutations_seen.get_ref().blocks().map(u32::reverse_bits);
+            let b = b.permutations_seen.get_ref().blocks().map(u32::reverse_bits);
+
+            a.cmp(b)
+        });
+
+        for candidate in sorted {
+            encoder.write(writer, candidate)?;
         }
+
+        Ok(())
     }
 
-    pub fn write_chunks(&self, mut bucket: VecDeque<Candidate>, wasted_symbols: usize, permutations: usize) {
-        while bucket.len() > SPLIT_SIZE * 2 {
-            let remainder = bucket.split_off(SPLIT_SIZE);
+    fn check(&self, header: &Header) -> Result<(), String> {
+        if header.magic != MAGIC {
+            return Err("it is not a scratch file.".to_string());
+        }
+
+        if header.version != VERSION {
+            return Err(format!("it has format version {} but this build reads version {}.", header.version, VERSION));
+        }
 
-            self.write(bucket, wasted_symbols, permutations);
-            bucket = remainder
+        if header.n as usize != self.n {
+            return Err(format!("it is for {} symbols, but the search is for {}.", header.n, self.n));
         }
 
-        self.write(bucket, wasted_symbols, permutations);
+        Ok(())
     }
 
+    #[cfg(test)]
     pub fn filename_for_reading(&self, wasted_symbols: usize, permutations: usize) -> Option<String> {
         let basename = self.basename(wasted_symbols, permutations);
         let index = self.index_to_read_from(wasted_symbols, permutations)?;
@@ -75,14 +330,24 @@ impl Disk {
         Some(format!("{}.{}", basename, index))
     }
 
-    pub fn filename_for_writing(&self, wasted_symbols: usize, permutations: usize) -> String {
+    pub(crate) fn filename_for_writing(&self, wasted_symbols: usize, permutations: usize) -> String {
         let basename = self.basename(wasted_symbols, permutations);
         let index = self.index_to_write_to(wasted_symbols, permutations);
 
         format!("{}.{}", basename, index)
     }
 
-    pub fn index_to_read_from(&self, wasted_symbols: usize, permutations: usize) -> Option<usize> {
+    fn peek_index_to_read_from(&self, wasted_symbols: usize, permutations: usize) -> Option<usize> {
+        let index = self.index.lock().unwrap();
+        let (min, max) = (*index.get(wasted_symbols)?.get(permutations)?)?;
+
+        match min <= max {
+            true => Some(min),
+            false => None,
+        }
+    }
+
+    pub(crate) fn index_to_read_from(&self, wasted_symbols: usize, permutations: usize) -> Option<usize> {
         let mut index_mut = self.index.lock().unwrap();
         let (min, max) = (*index_mut.get(wasted_symbols)?.get(permutations)?)?;
 
@@ -95,7 +360,7 @@ impl Disk {
         }
     }
 
-    pub fn index_to_write_to(&self, wasted_symbols: usize, permutations: usize) -> usize {
+    pub(crate) fn index_to_write_to(&self, wasted_symbols: usize, permutations: usize) -> usize {
         let mut index = self.index.lock().unwrap();
         if index.len() <= wasted_symbols {
             index.resize(wasted_symbols + 1, vec![]);
@@ -115,19 +380,50 @@ impl Disk {
         tuple.1
     }
 
-    pub fn basename(&self, wasted_symbols: usize, permutations: usize) -> String {
-        let gzip_component = match self.gzip {
-            true => ".gz",
-            false => "",
+    fn unindex_last(&self, wasted_symbols: usize, permutations: usize) {
+        let mut index = self.index.lock().unwrap();
+        let range = &mut index[wasted_symbols][permutations];
+
+        *range = match *range {
+            Some((0, 0)) | None => None,
+            Some((min, max)) => Some((min, max - 1)),
         };
+    }
+
+    fn remove_unindexed_files(&self) -> Result<(), Error> {
+        let mut indexed = HashSet::new();
+
+        for (w, nested) in self.index.lock().unwrap().iter().enumerate() {
+            for (p, range) in nested.iter().enumerate() {
+                if let Some((min, max)) = *range {
+                    for i in min..=max {
+                        indexed.insert(format!("{}.{}", self.file_stem(w, p), i));
+                    }
+                }
+            }
+        }
+
+        let read_error = |e| Error::Read(self.path.clone(), e);
+
+        for entry in read_dir(&self.path).map_err(read_error)? {
+            let entry = entry.map_err(read_error)?;
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            if name.starts_with("candidates-with-") && !indexed.contains(&name) {
+                let path = entry.path();
+                remove_file(&path).map_err(|e| Error::Remove(path.display().to_string(), e))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn basename(&self, wasted_symbols: usize, permutations: usize) -> String {
+        format!("{}/{}", self.path, self.file_stem(wasted_symbols, permutations))
+    }
 
-        format!(
-            "{}/candidates-with-{}-wasted-symbols-and-{}-permutations.dat{}",
-            self.path,
-            wasted_symbols,
-            permutations,
-            gzip_component,
-        )
+    fn file_stem(&self, wasted_symbols: usize, permutations: usize) -> String {
+        format!("candidates-with-{}-wasted-symbols-and-{}-permutations.dat", wasted_symbols, permutations)
     }
 }
 
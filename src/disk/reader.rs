@@ -0,0 +1,136 @@
+use super::{Codec, Error, Header};
+use super::codec::BitsetDecoder;
+use super::encoding::BucketDecoder;
+use super::super::candidate::Candidate;
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+
+use flate2::{CrcReader, read::ZlibDecoder};
+use bincode::deserialize_from;
+
+// Reads the candidates in a file one at a time so that a whole file never has
+// to be held in memory. The body is read through once when the file is opened
+// to check its checksum, so that nothing from a corrupt file is ever expanded,
+// and then read again from the start of the body.
+
+pub struct ChunkReader {
+    filename: String,
+    body: Body,
+    decoder: BucketDecoder,
+    remaining: u64,
+    position: u64,
+    finished: bool,
+}
+
+enum Body {
+    Raw(BufReader<File>),
+    Zlib(ZlibDecoder<BufReader<File>>),
+    Bitset(BitsetDecoder<BufReader<File>>),
+}
+
+impl ChunkReader {
+    pub(super) fn open<F: FnOnce(&Header) -> Result<(), String>>(filename: &str, check: F) -> Result<Self, Error> {
+        let file = File::open(filename).map_err(|e| Error::Read(filename.to_string(), e))?;
+        let mut reader = BufReader::new(file);
+
+        let header: Header = deserialize_from(&mut reader).map_err(|e| Error::from_bincode(filename, *e, false))?;
+        check(&header).map_err(|reason| Error::Incompatible(filename.to_string(), reason))?;
+
+        Self::verify(filename, &mut reader, header.checksum)?;
+
+        let mut body = match header.codec {
+            Codec::Raw => Body::Raw(reader),
+            Codec::Zlib(_) => Body::Zlib(ZlibDecoder::new(reader)),
+            Codec::Bitset => Body::Bitset(BitsetDecoder::new(reader)),
+        };
+
+        let len: u64 = deserialize_from(&mut body).map_err(|e| Error::from_bincode(filename, *e, false))?;
+
+        if len != header.count {
+            let reason = format!("it should contain {} candidates but says it has {}.", header.count, len);
+            return Err(Error::Corrupt(filename.to_string(), reason));
+        }
+
+        Ok(Self {
+            filename: filename.to_string(),
+            body,
+            decoder: BucketDecoder::new(header.n as usize),
+            remaining: len,
+            position: 0,
+            finished: false,
+        })
+    }
+
+    /// The number of candidates that have been read from the file so far.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Skips ahead so that the next candidate is at the given position.
+    pub fn skip_to(&mut self, position: u64) -> Result<(), Error> {
+        while self.position < position && !self.finished {
+            if let Some(Err(error)) = self.next() {
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Checks the rest of the file against the checksum in the header and then
+    // goes back to where the body starts.
+    fn verify(filename: &str, reader: &mut BufReader<File>, checksum: u32) -> Result<(), Error> {
+        let read_error = |e| Error::Read(filename.to_string(), e);
+        let start = reader.stream_position().map_err(read_error)?;
+
+        let mut crc_reader = CrcReader::new(&mut *reader);
+        io::copy(&mut crc_reader, &mut io::sink()).map_err(read_error)?;
+
+        if crc_reader.crc().sum() != checksum {
+            return Err(Error::Corrupt(filename.to_string(), "the checksum does not match, so the file is corrupt.".to_string()));
+        }
+
+        reader.seek(SeekFrom::Start(start)).map_err(read_error)?;
+
+        Ok(())
+    }
+}
+
+impl Iterator for ChunkReader {
+    type Item = Result<Candidate, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        if self.remaining == 0 {
+            self.finished = true;
+            return None;
+        }
+
+        match self.decoder.read(&mut self.body) {
+            Ok(candidate) => {
+                self.remaining -= 1;
+                self.position += 1;
+
+                Some(Ok(candidate))
+            },
+            Err(e) => {
+                self.finished = true;
+                Some(Err(Error::from_bincode(&self.filename, bincode::ErrorKind::Io(e), false)))
+            },
+        }
+    }
+}
+
+impl Read for Body {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Body::Raw(reader) => reader.read(buf),
+            Body::Zlib(decoder) => decoder.read(buf),
+            Body::Bitset(decoder) => decoder.read(buf),
+        }
+    }
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::bool_assert_comparison)]
+
 use super::*;
 
 use std::fs::metadata;
@@ -5,31 +7,50 @@ use std::path::Path;
 
 type Subject = Disk;
 
-const PATH: &'static str = "/tmp/superpermutation-test";
+const PATH: &str = "/tmp/superpermutation-test";
 
-fn subject(test_id: &'static str, gzip: bool) -> Subject {
+fn subject(test_id: &'static str, codec: Codec) -> Subject {
     let path = format!("{}/{}", PATH, test_id);
-    Subject::new(path, gzip)
+    Subject::new(path, codec, 5).unwrap()
 }
 
-fn bucket() -> VecDeque<Candidate> {
+fn candidates() -> Vec<Candidate> {
     (0..1000).map(|_| Candidate::seed(5)).collect()
 }
 
+fn bucket() -> VecDeque<Candidate> {
+    candidates().into_iter().collect()
+}
+
+// Candidates are written in order of their permutations, so compare them in an
+// order that doesn't depend on that.
+fn sorted<I: IntoIterator<Item=Candidate>>(candidates: I) -> Vec<Candidate> {
+    let mut candidates = candidates.into_iter().collect::<Vec<_>>();
+    candidates.sort_by_key(|c| (c.permutations_seen.iter().collect::<Vec<_>>(), c.tail_of_string.clone(), u64::from(c.ancestry_id)));
+
+    candidates
+}
+
 mod new {
     use super::*;
 
     #[test]
     fn it_builds_the_struct_with_the_path() {
-        let subject = subject("test-1", false);
+        let subject = subject("test-1", Codec::Raw);
         assert_eq!(subject.path, "/tmp/superpermutation-test/test-1");
     }
 
     #[test]
     fn it_creates_a_directory_at_the_path() {
-        subject("test-2", false);
+        subject("test-2", Codec::Raw);
         assert_eq!(Path::new(PATH).exists(), true);
     }
+
+    #[test]
+    fn it_returns_an_error_if_the_directory_cannot_be_created() {
+        let error = Subject::new("/dev/null/test-36".to_string(), Codec::Raw, 5).err().unwrap();
+        assert_eq!(error.to_string().starts_with("Failed to create /dev/null/test-36"), true);
+    }
 }
 
 mod basename {
@@ -37,7 +58,7 @@ mod basename {
 
     #[test]
     fn it_returns_a_name_based_on_the_number_of_wasted_symbols_and_permutations() {
-        let subject = subject("test-3", false);
+        let subject = subject("test-3", Codec::Raw);
         let actual = subject.basename(3, 4);
 
         let name = "test-3/candidates-with-3-wasted-symbols-and-4-permutations.dat";
@@ -52,7 +73,7 @@ mod filename_for_reading {
 
     #[test]
     fn it_returns_none_if_no_file_exists() {
-        let subject = subject("test-4", false);
+        let subject = subject("test-4", Codec::Raw);
         let filename = subject.filename_for_reading(3, 4);
 
         assert_eq!(filename, None);
@@ -60,11 +81,11 @@ mod filename_for_reading {
 
     #[test]
     fn it_returns_the_name_of_the_first_available_file() {
-        let subject = subject("test-5", false);
+        let subject = subject("test-5", Codec::Raw);
 
-        subject.write(bucket(), 3, 4); // 0
-        subject.write(bucket(), 3, 4); // 1
-        subject.write(bucket(), 3, 4); // 2
+        subject.write(&candidates(), 3, 4).unwrap(); // 0
+        subject.write(&candidates(), 3, 4).unwrap(); // 1
+        subject.write(&candidates(), 3, 4).unwrap(); // 2
 
         let filename = subject.filename_for_reading(3, 4).unwrap();
         assert_eq!(&filename[70..], "-4-permutations.dat.0");
@@ -82,7 +103,7 @@ mod filename_for_writing {
 
     #[test]
     fn it_adds_a_suffix_to_the_basename() {
-        let subject = subject("test-6", false);
+        let subject = subject("test-6", Codec::Raw);
 
         let filename = subject.filename_for_writing(3, 4);
         assert_eq!(&filename[70..], "-4-permutations.dat.0");
@@ -90,7 +111,7 @@ mod filename_for_writing {
 
     #[test]
     fn it_increments_the_index_each_time() {
-        let subject = subject("test-7", false);
+        let subject = subject("test-7", Codec::Raw);
         let filename = subject.filename_for_writing(3, 4);
         assert_eq!(&filename[70..], "-4-permutations.dat.0");
 
@@ -107,12 +128,24 @@ mod write {
 
     #[test]
     fn it_writes_the_bucket_to_a_file() {
-        let subject = subject("test-8", false);
-        subject.write(bucket(), 3, 4);
+        let subject = subject("test-8", Codec::Raw);
+        subject.write(&candidates(), 3, 4).unwrap();
 
         let filename = subject.filename_for_reading(3, 4).unwrap();
         assert_eq!(Path::new(&filename).exists(), true);
     }
+
+    #[test]
+    fn it_reports_each_chunk_once_it_is_counted_as_on_disk() {
+        let subject = subject("test-33", Codec::Raw);
+        let mut bucket = (0..SPLIT_SIZE * 2 + 1).map(|_| Candidate::seed(5)).collect();
+        let mut chunks = vec![];
+
+        subject.write_chunks_with(&mut bucket, 3, 4, |len| chunks.push((len, subject.usage().candidates))).unwrap();
+
+        let expected = vec![(SPLIT_SIZE, SPLIT_SIZE as u64), (SPLIT_SIZE + 1, SPLIT_SIZE as u64 * 2 + 1)];
+        assert_eq!(chunks, expected);
+    }
 }
 
 mod read {
@@ -120,35 +153,391 @@ mod read {
 
     #[test]
     fn it_reads_the_bucket_from_a_file() {
-        let subject = subject("test-9", false);
-        subject.write(bucket(), 3, 4);
+        let subject = subject("test-9", Codec::Raw);
+        subject.write(&candidates(), 3, 4).unwrap();
 
         let bucket_from_file = subject.read(3, 4);
-        assert_eq!(bucket_from_file, Some(bucket()));
+        assert_eq!(bucket_from_file.unwrap(), Some(bucket()));
     }
 }
 
-mod gzip_compression {
+mod header {
     use super::*;
+    use std::fs::{read, write};
+
+    fn written(test_id: &'static str, codec: Codec) -> (Subject, String) {
+        let subject = subject(test_id, codec);
+        subject.write(&candidates(), 3, 4).unwrap();
+
+        let filename = format!("{}.0", subject.basename(3, 4));
+        (subject, filename)
+    }
+
+    fn reopen(test_id: &'static str, subject: Subject, n: usize) -> Subject {
+        Subject::open(format!("{}/{}", PATH, test_id), Codec::Raw, n, subject.index(), subject.usage()).unwrap()
+    }
+
+    #[test]
+    fn it_starts_each_file_with_a_magic_number() {
+        let (_, filename) = written("test-15", Codec::Raw);
+        assert_eq!(&read(filename).unwrap()[..8], b"LEAPBNDS");
+    }
+
+    #[test]
+    fn it_rejects_files_that_are_for_a_different_number_of_symbols() {
+        let (subject, _) = written("test-16", Codec::Raw);
+        let subject = reopen("test-16", subject, 6);
+
+        let error = subject.read(3, 4).unwrap_err().to_string();
+        assert_eq!(error.ends_with("it is for 5 symbols, but the search is for 6."), true);
+    }
+
+    #[test]
+    fn it_rejects_files_that_are_not_scratch_files() {
+        let (subject, filename) = written("test-17", Codec::Raw);
+        write(&filename, vec![0; 100]).unwrap();
+
+        let error = subject.read(3, 4).unwrap_err().to_string();
+        assert_eq!(error.ends_with("it is not a scratch file."), true);
+    }
+
+    #[test]
+    fn it_rejects_files_with_a_different_format_version() {
+        let (subject, filename) = written("test-18", Codec::Raw);
+
+        let mut bytes = read(&filename).unwrap();
+        bytes[8] = 99;
+        write(&filename, bytes).unwrap();
+
+        let error = subject.read(3, 4).unwrap_err().to_string();
+        assert_eq!(error.ends_with("it has format version 99 but this build reads version 3."), true);
+    }
+
+    #[test]
+    fn it_rejects_files_that_have_been_corrupted() {
+        let (subject, filename) = written("test-19", Codec::Raw);
+
+        let mut bytes = read(&filename).unwrap();
+        let header: Header = bincode::deserialize(&bytes).unwrap();
+
+        // Change the first symbol in the tail of the first candidate.
+        let offset = bincode::serialized_size(&header).unwrap() as usize + 9;
+        bytes[offset] ^= 1;
+        write(&filename, bytes).unwrap();
+
+        let error = subject.read(3, 4).unwrap_err().to_string();
+        assert_eq!(error.ends_with("the checksum does not match, so the file is corrupt."), true);
+    }
+}
+
+mod errors {
+    use super::*;
+    use std::fs::{read, remove_dir_all, write};
+
+    #[test]
+    fn it_returns_an_error_and_leaves_the_bucket_if_a_chunk_cannot_be_written() {
+        let subject = subject("test-21", Codec::Raw);
+        remove_dir_all(&subject.path).unwrap();
+
+        let mut bucket = bucket();
+        let error = subject.write_chunks(&mut bucket, 3, 4).unwrap_err();
+
+        assert_eq!(matches!(error, Error::Write(..)), true);
+        assert_eq!(bucket.len(), 1000);
+        assert_eq!(subject.index(), vec![vec![], vec![], vec![], vec![None, None, None, None, None]]);
+    }
+
+    #[test]
+    fn it_returns_an_error_and_can_read_again_if_the_file_is_missing() {
+        let subject = subject("test-22", Codec::Raw);
+        subject.write(&candidates(), 3, 4).unwrap();
+
+        let filename = format!("{}.0", subject.basename(3, 4));
+        let contents = read(&filename).unwrap();
+        remove_file(&filename).unwrap();
+
+        let error = subject.read(3, 4).unwrap_err();
+        assert_eq!(matches!(error, Error::Read(..)), true);
+        assert_eq!(error.is_transient(), false);
+
+        write(&filename, contents).unwrap();
+        assert_eq!(subject.read(3, 4).unwrap(), Some(bucket()));
+    }
+
+    #[test]
+    fn it_returns_an_error_if_the_file_is_truncated() {
+        let subject = subject("test-23", Codec::Raw);
+        subject.write(&candidates(), 3, 4).unwrap();
+
+        let filename = format!("{}.0", subject.basename(3, 4));
+        let contents = read(&filename).unwrap();
+        write(&filename, &contents[..contents.len() / 2]).unwrap();
+
+        // The checksum is checked before any candidates are read.
+        let error = subject.read(3, 4).unwrap_err();
+        assert_eq!(error.to_string().ends_with("the checksum does not match, so the file is corrupt."), true);
+    }
+}
+
+mod compression {
+    use super::*;
+
+    fn file_size(test_id: &'static str, codec: Codec) -> u64 {
+        let subject = subject(test_id, codec);
+        subject.write(&candidates(), 3, 4).unwrap();
+
+        let filename = format!("{}.0", subject.basename(3, 4));
+        metadata(filename).unwrap().len()
+    }
 
     #[test]
     fn it_writes_a_smaller_file_to_disk() {
-        let with_gzip = subject("test-11", true);
-        let without_gzip = subject("test-12", false);
+        let raw_size = file_size("test-11", Codec::Raw);
+        let zlib_size = file_size("test-12", Codec::Zlib(6));
+        let bitset_size = file_size("test-26", Codec::Bitset);
+
+        assert_eq!(raw_size / zlib_size > 50, true);
+        assert_eq!(bitset_size < raw_size, true);
+    }
+
+    #[test]
+    fn it_reads_back_what_each_codec_wrote() {
+        for &codec in &[Codec::Raw, Codec::Zlib(0), Codec::Zlib(9), Codec::Bitset] {
+            let subject = subject("test-27", codec);
+            let candidates = Candidate::seed(5).expand(usize::MAX, 5).flat_map(|c| c.expand(usize::MAX, 5)).collect::<Vec<_>>();
+
+            subject.write(&candidates, 3, 4).unwrap();
+            assert_eq!(sorted(subject.read(3, 4).unwrap().unwrap()), sorted(candidates));
+        }
+    }
+
+    #[test]
+    fn it_reads_files_that_were_written_with_a_different_codec() {
+        let subject = subject("test-28", Codec::Zlib(1));
+        subject.write(&candidates(), 3, 4).unwrap();
+
+        let path = format!("{}/test-28", PATH);
+        let subject = Subject::open(path, Codec::Bitset, 5, subject.index(), subject.usage()).unwrap();
+        subject.write(&candidates(), 3, 4).unwrap();
+
+        assert_eq!(subject.read(3, 4).unwrap(), Some(bucket()));
+        assert_eq!(subject.read(3, 4).unwrap(), Some(bucket()));
+    }
+}
+
+mod encoding {
+    use super::*;
+    use super::super::encoding::{BucketDecoder, BucketEncoder};
+    use std::io;
+
+    // Bitsets of every density in a shuffled order, each followed by a copy
+    // with one more permutation, so that some are written as full bitsets, some
+    // as sparse lists and some as deltas from the previous one.
+    fn candidates_with_bitsets() -> Vec<Candidate> {
+        (1..120).map(|i| i * 37 % 119 + 1).flat_map(|step| {
+            vec![candidate_with_bitset(step, false), candidate_with_bitset(step, true)]
+        }).collect()
+    }
+
+    fn candidate_with_bitset(step: usize, one_more: bool) -> Candidate {
+        let mut candidate = Candidate::seed(5);
+
+        for id in (0..120).filter(|id| (id * 7 + step) % 120 < step) {
+            candidate.permutations_seen.insert(id);
+        }
+
+        if one_more {
+            candidate.permutations_seen.insert(119);
+        }
+
+        candidate.ancestry_id = (step as u64 * 1_000_000).into();
+        candidate
+    }
+
+    #[test]
+    fn it_writes_similar_candidates_in_a_few_bytes_each() {
+        let subject = subject("test-29", Codec::Raw);
+        subject.write(&candidates(), 3, 4).unwrap();
+
+        let filename = format!("{}.0", subject.basename(3, 4));
+        assert_eq!(metadata(filename).unwrap().len() < 10 * 1000, true);
+    }
+
+    #[test]
+    fn it_reads_back_bitsets_of_every_density() {
+        let candidates = candidates_with_bitsets();
+
+        let mut encoder = BucketEncoder::new(5);
+        let mut bytes = vec![];
+
+        for candidate in &candidates {
+            encoder.write(&mut bytes, candidate).unwrap();
+        }
+
+        let mut decoder = BucketDecoder::new(5);
+        let mut reader = &bytes[..];
+
+        for candidate in candidates {
+            assert_eq!(decoder.read(&mut reader).unwrap(), candidate);
+        }
+
+        assert_eq!(reader.is_empty(), true);
+    }
+
+    #[test]
+    fn it_reads_back_bitsets_with_the_same_number_of_blocks() {
+        let subject = subject("test-31", Codec::Raw);
+        subject.write(&candidates_with_bitsets(), 3, 4).unwrap();
+
+        for candidate in subject.read(3, 4).unwrap().unwrap() {
+            assert_eq!(candidate.permutations_seen.get_ref().len(), 120);
+        }
+    }
+
+    #[test]
+    fn it_rejects_candidates_that_could_not_have_been_written() {
+        let invalid = |n, bytes: &[u8]| {
+            let result = BucketDecoder::new(n).read(&mut &bytes[..]);
+            result.err().map(|e| e.kind()) == Some(io::ErrorKind::InvalidData)
+        };
+
+        // Each is a tail, wasted symbols, an ancestry id and then a bitset.
+        assert_eq!(invalid(5, &[0x80, 0x80, 0x80, 0x80, 0x01]), true);     // a tail of 2^28 symbols
+        assert_eq!(invalid(5, &[5, 0, 1, 2, 3, 4, 0, 0, 1, 0]), true);    // a tail of n symbols
+        assert_eq!(invalid(5, &[1, 5, 0, 0, 1, 0]), true);                // a symbol that isn't < n
+        assert_eq!(invalid(5, &[1, 4, 0, 0, 1, 2, 0, 120]), true);        // a sparse id that isn't < n!
+        assert_eq!(invalid(5, &[1, 4, 0, 0, 2, 1, 0xFF, 0x01]), true);    // a delta id that isn't < n!
+        assert_eq!(invalid(3, &[1, 2, 0, 0, 0, 0x01]), true);             // a full bitset with a bit past n!
+
+        assert_eq!(invalid(3, &[1, 2, 0, 0, 0, 0x04]), false);
+    }
+}
+
+mod stream {
+    use super::*;
+    use std::fs::{read, write};
+
+    #[test]
+    fn it_reads_candidates_one_at_a_time_without_consuming_the_file() {
+        let subject = subject("test-24", Codec::Zlib(6));
+        subject.write(&candidates(), 3, 4).unwrap();
+
+        let mut reader = subject.stream(3, 4).unwrap().unwrap();
+        assert_eq!(reader.position(), 0);
+
+        assert_eq!(reader.next().unwrap().unwrap(), Candidate::seed(5));
+        assert_eq!(reader.position(), 1);
+
+        reader.skip_to(990).unwrap();
+        assert_eq!(reader.count(), 10);
+
+        assert_eq!(subject.read(3, 4).unwrap(), Some(bucket()));
+    }
+
+    #[test]
+    fn it_moves_on_to_the_next_file_when_the_file_is_consumed() {
+        let subject = subject("test-25", Codec::Raw);
+        subject.write(&candidates(), 3, 4).unwrap();
+
+        subject.consume(3, 4).unwrap();
+
+        assert_eq!(subject.stream(3, 4).unwrap().is_none(), true);
+        assert_eq!(Path::new(&format!("{}.0", subject.basename(3, 4))).exists(), false);
+    }
+
+    #[test]
+    fn it_rejects_a_corrupt_file_before_reading_any_candidates() {
+        let subject = subject("test-34", Codec::Raw);
+        subject.write(&candidates(), 3, 4).unwrap();
+
+        let filename = format!("{}.0", subject.basename(3, 4));
+        let mut bytes = read(&filename).unwrap();
+
+        // Change the last byte, which is part of the last candidate.
+        *bytes.last_mut().unwrap() ^= 1;
+        write(&filename, bytes).unwrap();
+
+        let error = subject.stream(3, 4).err().unwrap().to_string();
+        assert_eq!(error.ends_with("the checksum does not match, so the file is corrupt."), true);
+    }
+}
+
+mod usage {
+    use super::*;
+
+    #[test]
+    fn it_counts_the_candidates_on_disk_and_the_bytes_written_and_read() {
+        let subject = subject("test-32", Codec::Raw);
+
+        subject.write(&candidates(), 3, 4).unwrap();
+        subject.write(&candidates(), 3, 5).unwrap();
+
+        let size = metadata(format!("{}.0", subject.basename(3, 4))).unwrap().len();
+        assert_eq!(subject.usage(), Usage { candidates: 2000, bytes_written: size * 2, bytes_read: 0 });
+
+        subject.read(3, 4).unwrap();
+        assert_eq!(subject.usage(), Usage { candidates: 1000, bytes_written: size * 2, bytes_read: size });
+    }
+}
+
+mod open {
+    use super::*;
+
+    #[test]
+    fn it_keeps_indexed_files_and_removes_files_written_after_the_checkpoint() {
+        let subject = subject("test-13", Codec::Raw);
+        subject.write(&candidates(), 3, 4).unwrap();
+
+        let (index, usage) = (subject.index(), subject.usage());
+        subject.write(&candidates(), 3, 4).unwrap();
+
+        let path = format!("{}/test-13", PATH);
+        let subject = Subject::open(path, Codec::Raw, 5, index, usage).unwrap();
+
+        assert_eq!(subject.read(3, 4).unwrap(), Some(bucket()));
+        assert_eq!(subject.read(3, 4).unwrap(), None);
+
+        let unindexed = format!("{}.1", subject.basename(3, 4));
+        assert_eq!(Path::new(&unindexed).exists(), false);
+    }
+}
+
+mod defer_removals {
+    use super::*;
+    use std::fs::remove_file;
+
+    #[test]
+    fn it_keeps_files_that_have_been_read_until_they_are_removed() {
+        let subject = subject("test-14", Codec::Raw);
+        subject.defer_removals();
+
+        subject.write(&candidates(), 3, 4).unwrap();
+        let filename = format!("{}.0", subject.basename(3, 4));
+
+        subject.read(3, 4).unwrap();
+        assert_eq!(Path::new(&filename).exists(), true);
+
+        assert_eq!(subject.remove_consumed().len(), 0);
+        assert_eq!(Path::new(&filename).exists(), false);
+    }
+
+    #[test]
+    fn it_returns_an_error_for_each_file_that_cannot_be_removed() {
+        let subject = subject("test-35", Codec::Raw);
+        subject.defer_removals();
 
-        with_gzip.write(bucket(), 3, 4);
-        without_gzip.write(bucket(), 5, 6);
+        subject.write(&candidates(), 3, 4).unwrap();
+        subject.write(&candidates(), 3, 5).unwrap();
 
-        let file1 = with_gzip.filename_for_reading(3, 4).unwrap();
-        let file2 = without_gzip.filename_for_reading(5, 6).unwrap();
+        subject.read(3, 4).unwrap();
+        subject.read(3, 5).unwrap();
 
-        let with_gzip_size = metadata(file1).unwrap().len();
-        let without_gzip_size = metadata(file2).unwrap().len();
+        let filename = format!("{}.0", subject.basename(3, 4));
+        remove_file(&filename).unwrap();
 
-        assert_eq!(with_gzip_size > 0, true);
-        assert_eq!(with_gzip_size < without_gzip_size, true);
+        let errors = subject.remove_consumed();
 
-        let compression_rate = without_gzip_size / with_gzip_size;
-        assert_eq!(compression_rate > 200, true);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].to_string().starts_with(&format!("Failed to remove {}", filename)), true);
     }
 }
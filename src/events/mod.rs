@@ -0,0 +1,163 @@
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, LineWriter, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+// Writes what happens during the search as JSON Lines so that a run can be
+// analysed afterwards. Each line has the number of seconds since the log was
+// opened and the name of the event. Lines are written as they happen, rather
+// than buffered, because the search exits without unwinding when it's done.
+// Nothing is printed here: a `Listener` is told about each line instead, and
+// about the file if it can't be written, so that the binary can print them.
+
+/// A bucket of the frontier, as (total waste, number of permutations).
+pub type BucketID = (usize, usize);
+
+/// Where events are written. It can be cloned to share it between the bounds
+/// and the frontier.
+#[derive(Debug, Clone)]
+pub struct EventLog {
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    started: Instant,
+    file: Option<LineWriter<File>>,
+    listener: Option<Box<dyn Listener>>,
+}
+
+/// Told about each event as it's logged, with the line that's written for it,
+/// e.g. to print it as well.
+pub trait Listener: Send {
+    fn on_event(&mut self, _event: &Event, _line: &str) {}
+
+    /// The log file couldn't be written, so it has been closed. Events are
+    /// still passed to `on_event`.
+    fn on_error(&mut self, _error: &io::Error) {}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// A lower bound increased, or a bound was found for a new number of
+    /// wasted symbols.
+    Bound { wasted_symbols: usize, permutations: usize, new_index: bool },
+    /// Buckets with fewer permutations than the threshold were disabled. Only
+    /// the buckets that had candidates in them are listed.
+    Prune { wasted_symbols: usize, max_waste: usize, threshold: usize, buckets: Vec<BucketID>, candidates: usize },
+    /// A disabled bucket was enabled again.
+    Enable { bucket: BucketID, from_disk: bool, candidates: usize },
+    /// Buckets were written to disk to free memory. `spilled` is how many of
+    /// the candidates were from enabled buckets.
+    Offload { buckets: usize, candidates: usize, spilled: usize, bytes: u64, seconds: f64, failed: usize },
+    /// The first batch of a file was read back into a bucket.
+    Onload { bucket: BucketID, candidates: usize, seconds: f64 },
+    /// Reading or writing a file failed with an error that might not happen
+    /// again, so it will be tried again after a few seconds.
+    Retry { error: String, seconds: u64 },
+    /// A scratch file that had been read couldn't be removed. The search
+    /// carries on, but the file is left on disk.
+    Leftover { error: String },
+}
+
+impl EventLog {
+    /// Opens a log that writes to a file, a listener or both. Returns None if
+    /// it has nowhere to write. A resumed search appends to the log so that it
+    /// covers the whole run.
+    pub fn open(path: Option<&str>, listener: Option<Box<dyn Listener>>, append: bool) -> io::Result<Option<Self>> {
+        if path.is_none() && listener.is_none() {
+            return Ok(None);
+        }
+
+        let file = match path {
+            None => None,
+            Some(path) => {
+                let file = OpenOptions::new().create(true).write(true).append(append).truncate(!append).open(path)?;
+                Some(LineWriter::new(file))
+            },
+        };
+
+        let inner = Inner { started: Instant::now(), file, listener };
+        Ok(Some(Self { inner: Arc::new(Mutex::new(inner)) }))
+    }
+
+    /// Failing to write the log shouldn't stop the search, so the file is
+    /// closed after telling the listener and the search carries on without it.
+    pub fn emit(&self, event: Event) {
+        let inner = &mut *self.inner.lock().unwrap();
+        let line = format!("{{\"time\": {:.3}, {}}}", inner.started.elapsed().as_secs_f64(), event);
+
+        if let Some(listener) = inner.listener.as_mut() {
+            listener.on_event(&event, &line);
+        }
+
+        if let Some(file) = inner.file.as_mut() {
+            if let Err(e) = writeln!(file, "{}", line) {
+                inner.file = None;
+
+                if let Some(listener) = inner.listener.as_mut() {
+                    listener.on_error(&e);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Inner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Inner").field("started", &self.started).field("file", &self.file).finish_non_exhaustive()
+    }
+}
+
+// Bounds and Frontier hold a log but are compared in tests, so logs are equal
+// if they write to the same place.
+impl PartialEq for EventLog {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Bound { wasted_symbols, permutations, new_index } => write!(
+                f, "\"event\": \"bound\", \"wasted_symbols\": {}, \"permutations\": {}, \"new_index\": {}",
+                wasted_symbols, permutations, new_index,
+            ),
+            Event::Prune { wasted_symbols, max_waste, threshold, buckets, candidates } => write!(
+                f, "\"event\": \"prune\", \"wasted_symbols\": {}, \"max_waste\": {}, \"threshold\": {}, \"buckets\": {}, \"candidates\": {}",
+                wasted_symbols, max_waste, threshold, Self::bucket_list(buckets), candidates,
+            ),
+            Event::Enable { bucket, from_disk, candidates } => write!(
+                f, "\"event\": \"enable\", \"bucket\": [{}, {}], \"from\": \"{}\", \"candidates\": {}",
+                bucket.0, bucket.1, if *from_disk { "disk" } else { "memory" }, candidates,
+            ),
+            Event::Offload { buckets, candidates, spilled, bytes, seconds, failed } => write!(
+                f, "\"event\": \"offload\", \"buckets\": {}, \"candidates\": {}, \"spilled\": {}, \"bytes\": {}, \"seconds\": {:.3}, \"failed\": {}",
+                buckets, candidates, spilled, bytes, seconds, failed,
+            ),
+            Event::Onload { bucket, candidates, seconds } => write!(
+                f, "\"event\": \"onload\", \"bucket\": [{}, {}], \"candidates\": {}, \"seconds\": {:.3}",
+                bucket.0, bucket.1, candidates, seconds,
+            ),
+            Event::Retry { error, seconds } => write!(
+                f, "\"event\": \"retry\", \"error\": {}, \"seconds\": {}",
+                serde_json::to_string(error).unwrap(), seconds,
+            ),
+            Event::Leftover { error } => write!(
+                f, "\"event\": \"leftover\", \"error\": {}",
+                serde_json::to_string(error).unwrap(),
+            ),
+        }
+    }
+}
+
+impl Event {
+    fn bucket_list(buckets: &[BucketID]) -> String {
+        let ids = buckets.iter().map(|(w, p)| format!("[{}, {}]", w, p)).collect::<Vec<_>>();
+        format!("[{}]", ids.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod test;
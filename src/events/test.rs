@@ -0,0 +1,110 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use super::*;
+
+use std::fs::{create_dir_all, read_to_string, remove_file};
+use std::os::unix::fs::symlink;
+
+type Subject = EventLog;
+
+const PATH: &str = "/tmp/superpermutation-test";
+
+fn path(test_id: &'static str) -> String {
+    create_dir_all(PATH).unwrap();
+    format!("{}/{}.jsonl", PATH, test_id)
+}
+
+mod open {
+    use super::*;
+
+    #[test]
+    fn it_returns_none_if_there_is_nowhere_to_write_events() {
+        assert_eq!(Subject::open(None, None, false).unwrap(), None);
+    }
+
+    #[test]
+    fn it_truncates_the_file_unless_appending() {
+        let path = path("events-1");
+
+        let subject = Subject::open(Some(&path), None, false).unwrap().unwrap();
+        subject.emit(Event::Bound { wasted_symbols: 1, permutations: 5, new_index: true });
+
+        let subject = Subject::open(Some(&path), None, true).unwrap().unwrap();
+        subject.emit(Event::Bound { wasted_symbols: 2, permutations: 10, new_index: true });
+
+        assert_eq!(read_to_string(&path).unwrap().lines().count(), 2);
+
+        Subject::open(Some(&path), None, false).unwrap().unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), "");
+    }
+}
+
+mod emit {
+    use super::*;
+
+    #[test]
+    fn it_writes_one_line_per_event_with_the_time_and_name() {
+        let path = path("events-2");
+        let subject = Subject::open(Some(&path), None, false).unwrap().unwrap();
+
+        subject.emit(Event::Enable { bucket: (3, 12), from_disk: true, candidates: 4096 });
+        subject.emit(Event::Onload { bucket: (3, 12), candidates: 4096, seconds: 0.25 });
+
+        let contents = read_to_string(&path).unwrap();
+        let lines = contents.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].starts_with("{\"time\": 0.0"), true);
+        assert_eq!(lines[0].ends_with(", \"event\": \"enable\", \"bucket\": [3, 12], \"from\": \"disk\", \"candidates\": 4096}"), true);
+        assert_eq!(lines[1].ends_with(", \"event\": \"onload\", \"bucket\": [3, 12], \"candidates\": 4096, \"seconds\": 0.250}"), true);
+    }
+
+    #[test]
+    fn it_tells_the_listener_about_each_line_and_stops_writing_the_file_after_an_error() {
+        #[derive(Default, Clone)]
+        struct Recorder(Arc<Mutex<(Vec<String>, Vec<String>)>>);
+
+        impl Listener for Recorder {
+            fn on_event(&mut self, _event: &Event, line: &str) {
+                self.0.lock().unwrap().0.push(line.to_string());
+            }
+
+            fn on_error(&mut self, error: &io::Error) {
+                self.0.lock().unwrap().1.push(error.to_string());
+            }
+        }
+
+        let path = path("events-3");
+        let _ = remove_file(&path);
+        symlink("/dev/full", &path).unwrap();
+
+        let recorder = Recorder::default();
+        let subject = Subject::open(Some(&path), Some(Box::new(recorder.clone())), false).unwrap().unwrap();
+
+        subject.emit(Event::Leftover { error: "Failed to remove 0-5.bin".to_string() });
+        subject.emit(Event::Leftover { error: "Failed to remove 0-6.bin".to_string() });
+
+        let (lines, errors) = recorder.0.lock().unwrap().clone();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].ends_with(", \"event\": \"leftover\", \"error\": \"Failed to remove 0-6.bin\"}"), true);
+        assert_eq!(errors.len(), 1);
+    }
+}
+
+mod display {
+    use super::*;
+
+    #[test]
+    fn it_formats_each_event_as_json_fields() {
+        let bound = Event::Bound { wasted_symbols: 4, permutations: 23, new_index: false };
+        let prune = Event::Prune { wasted_symbols: 4, max_waste: 6, threshold: 18, buckets: vec![(4, 17), (5, 2)], candidates: 30 };
+        let offload = Event::Offload { buckets: 7, candidates: 1000, spilled: 200, bytes: 62000, seconds: 1.5, failed: 0 };
+        let retry = Event::Retry { error: "Failed to write \"0-5.bin\"".to_string(), seconds: 4 };
+
+        assert_eq!(bound.to_string(), "\"event\": \"bound\", \"wasted_symbols\": 4, \"permutations\": 23, \"new_index\": false");
+        assert_eq!(prune.to_string(), "\"event\": \"prune\", \"wasted_symbols\": 4, \"max_waste\": 6, \"threshold\": 18, \"buckets\": [[4, 17], [5, 2]], \"candidates\": 30");
+        assert_eq!(offload.to_string(), "\"event\": \"offload\", \"buckets\": 7, \"candidates\": 1000, \"spilled\": 200, \"bytes\": 62000, \"seconds\": 1.500, \"failed\": 0");
+        assert_eq!(retry.to_string(), "\"event\": \"retry\", \"error\": \"Failed to write \\\"0-5.bin\\\"\", \"seconds\": 4");
+    }
+}
@@ -0,0 +1,141 @@
+use super::super::candidate::Candidate;
+
+use bit_set::BitSet;
+use std::collections::{HashMap, VecDeque};
+
+// A candidate can never do better than another with the same tail if it has
+// seen a subset of the other's permutations and wasted at least as many
+// symbols, so it can be dropped. The most recent candidates that weren't
+// dominated are kept for each tail, up to a fixed number. There are only so
+// many tails, so this bounds the memory that is used.
+
+pub struct Dominance {
+    candidates: HashMap<Vec<u8>, VecDeque<Entry>>,
+    capacity: usize,
+    tails: usize,
+    max_bytes: u64,
+    pub dropped_on_add: u64,
+    pub dropped_on_onload: u64,
+    phase: (u64, u64),
+}
+
+struct Entry {
+    wasted_symbols: u16,
+    permutations: usize,
+    permutations_seen: BitSet,
+}
+
+impl Dominance {
+    pub fn new(capacity: usize, n: usize) -> Self {
+        let tails = Self::number_of_tails(n);
+        let bytes = super::super::Bounds::factorial(n).div_ceil(8) + n + 48;
+
+        Self {
+            candidates: HashMap::new(),
+            capacity,
+            tails,
+            max_bytes: (capacity * tails * bytes) as u64,
+            dropped_on_add: 0,
+            dropped_on_onload: 0,
+            phase: (0, 0),
+        }
+    }
+
+    /// Returns false if the candidate is dominated and should be dropped.
+    pub fn insert(&mut self, candidate: &Candidate) -> bool {
+        let permutations = candidate.number_of_permutations();
+        let entries = self.candidates.entry(candidate.tail_of_string.clone()).or_default();
+
+        if entries.iter().any(|e| Self::dominates(e, candidate, permutations)) {
+            self.dropped_on_add += 1;
+            self.phase.0 += 1;
+
+            return false;
+        }
+
+        entries.retain(|e| {
+            e.wasted_symbols < candidate.wasted_symbols ||
+            e.permutations > permutations ||
+            !e.permutations_seen.is_subset(&candidate.permutations_seen)
+        });
+
+        entries.push_back(Entry {
+            wasted_symbols: candidate.wasted_symbols,
+            permutations,
+            permutations_seen: candidate.permutations_seen.clone(),
+        });
+
+        if entries.len() > self.capacity {
+            entries.pop_front();
+        }
+
+        true
+    }
+
+    /// Candidates that are loaded from disk were inserted when they were added,
+    /// so they are only dropped if something strictly better has been seen since.
+    pub fn retain_undominated(&mut self, bucket: &mut VecDeque<Candidate>) {
+        let before = bucket.len();
+
+        bucket.retain(|candidate| {
+            let permutations = candidate.number_of_permutations();
+            let entries = match self.candidates.get(&candidate.tail_of_string) {
+                None => return true,
+                Some(entries) => entries,
+            };
+
+            !entries.iter().any(|e| {
+                Self::dominates(e, candidate, permutations) &&
+                (e.wasted_symbols < candidate.wasted_symbols || e.permutations > permutations)
+            })
+        });
+
+        let dropped = (before - bucket.len()) as u64;
+
+        self.dropped_on_onload += dropped;
+        self.phase.1 += dropped;
+    }
+
+    /// Returns how many candidates were dropped when adding and when onloading
+    /// since the last phase ended.
+    pub fn end_phase(&mut self) -> (u64, u64) {
+        let phase = self.phase;
+        self.phase = (0, 0);
+
+        phase
+    }
+
+    /// How many candidates are kept for each tail.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// How many different tails there are for this number of symbols.
+    pub fn tails(&self) -> usize {
+        self.tails
+    }
+
+    /// Roughly the most memory the candidates that are kept can use.
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.values().map(|entries| entries.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.values().all(|entries| entries.is_empty())
+    }
+
+    fn dominates(entry: &Entry, candidate: &Candidate, permutations: usize) -> bool {
+        entry.wasted_symbols <= candidate.wasted_symbols &&
+        entry.permutations >= permutations &&
+        candidate.permutations_seen.is_subset(&entry.permutations_seen)
+    }
+
+    // Tails are between 1 and n - 1 distinct symbols long.
+    fn number_of_tails(n: usize) -> usize {
+        (1..n).map(|k| (n - k + 1..=n).product::<usize>()).sum()
+    }
+}
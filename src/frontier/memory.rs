@@ -0,0 +1,197 @@
+use super::super::candidate::Candidate;
+use super::super::bounds::Bounds;
+
+use std::fs::read_to_string;
+use std::mem::size_of;
+
+// The memory limit is for the whole process, as measured by its resident set
+// in /proc. An estimate from the number of candidates leaves out the spare
+// capacity of the buckets, the queues they're kept in and whatever else the
+// process has allocated, so on its own it lets the process grow well past the
+// limit. Reading /proc is much slower than adding a candidate though, so it's
+// only measured every so often and the estimate fills in the changes since.
+//
+// Memory that's freed, e.g. when buckets are offloaded, usually stays resident
+// because the allocator keeps it for the candidates that are added next. It's
+// counted as released until the frontier grows back into it, or the resident
+// set shrinks, so that it doesn't cause another offload straight away. There's
+// other memory the estimate can't see, such as what the allocator holds on to
+// for itself, so after an offload there isn't another until the resident set
+// has been measured again. Otherwise a limit that's too small to reach would
+// offload whatever was added after every add.
+//
+// If the process is still over the limit when it's measured after an offload,
+// e.g. because it used more than that before it had any candidates, the wait
+// for the next one doubles, up to `MAX_BACKOFF` measurements. It goes back to
+// one measurement once an offload brings the process under the limit.
+
+pub(super) const MEASURE_EVERY: usize = 1024;
+const MAX_BACKOFF: usize = 64;
+
+// Enabled buckets are spilled until the memory in use is back down to this
+// much of the limit, so that it doesn't happen again on the next add.
+const SPILL_TO: f64 = 0.75;
+
+// The allocator rounds each allocation up to 16 bytes, with 8 for its header
+// and at least 32 in total.
+const ALLOCATION_HEADER: usize = 8;
+const ALLOCATION_ALIGN: usize = 16;
+const ALLOCATION_MIN: usize = 32;
+
+pub(super) struct Memory {
+    limit: u64,
+    per_candidate: u64,
+    baseline: u64,
+    resident: Option<u64>,
+    released: u64,
+    measured_estimate: u64,
+    adds: usize,
+    offloaded: bool,
+    backoff: usize,
+    waiting: usize,
+}
+
+/// How many bytes the frontier uses for each of its structures, estimated from
+/// their sizes and capacities.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Accounting {
+    /// The candidates themselves, including their bitsets and tails.
+    pub candidates: u64,
+    /// Space the buckets have allocated but aren't using.
+    pub spare_capacity: u64,
+    /// The queues of buckets, including the empty ones.
+    pub queues: u64,
+    /// The set of disabled buckets.
+    pub disabled: u64,
+    /// The fingerprints kept to drop duplicate candidates.
+    pub transpositions: u64,
+}
+
+impl Accounting {
+    pub fn total(&self) -> u64 {
+        self.candidates + self.spare_capacity + self.queues + self.disabled + self.transpositions
+    }
+}
+
+impl Memory {
+    pub fn new(memory_limit: f64, n: usize) -> Self {
+        let per_candidate = Self::per_candidate(n);
+        let limit = (memory_limit * 1024. * 1024. * 1024.) as u64;
+        let resident = resident_bytes();
+
+        Self {
+            limit,
+            per_candidate,
+            baseline: resident.unwrap_or(0),
+            resident,
+            released: 0,
+            measured_estimate: 0,
+            adds: 0,
+            offloaded: false,
+            backoff: 1,
+            waiting: 0,
+        }
+    }
+
+    /// Whether the process is using more than the limit, with `candidates` in
+    /// the frontier. The resident set is measured every `MEASURE_EVERY` calls.
+    pub fn over_limit(&mut self, candidates: usize) -> bool {
+        let estimate = self.estimate(candidates);
+
+        self.adds += 1;
+
+        if self.adds.is_multiple_of(MEASURE_EVERY) {
+            self.measure(estimate);
+        }
+
+        self.waiting == 0 && self.in_use(estimate) > self.limit
+    }
+
+    /// Waits for the next measurement, or more if offloading hasn't been
+    /// bringing the process under the limit, before it's over the limit again.
+    pub fn offloaded(&mut self) {
+        self.offloaded = true;
+        self.waiting = self.backoff;
+    }
+
+    /// How many candidates would have to be freed to bring the memory in use
+    /// down to `SPILL_TO` of the limit, with `candidates` in the frontier.
+    pub fn candidates_to_free(&self, candidates: usize) -> usize {
+        let target = (self.limit as f64 * SPILL_TO) as u64;
+        let excess = self.in_use(self.estimate(candidates)).saturating_sub(target);
+
+        excess.div_ceil(self.per_candidate) as usize
+    }
+
+    pub fn estimate(&self, candidates: usize) -> u64 {
+        candidates as u64 * self.per_candidate
+    }
+
+    pub fn baseline(&self) -> u64 {
+        self.baseline
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    pub fn bytes_per_candidate(&self) -> u64 {
+        self.per_candidate
+    }
+
+    fn in_use(&self, estimate: u64) -> u64 {
+        match self.resident {
+            None => self.baseline + estimate,
+            Some(resident) => (resident + estimate).saturating_sub(self.measured_estimate + self.released),
+        }
+    }
+
+    fn measure(&mut self, estimate: u64) {
+        if let Some(resident) = resident_bytes() {
+            let released = (self.released + self.measured_estimate).saturating_sub(estimate);
+            let shrunk = self.resident.map_or(0, |r| r.saturating_sub(resident));
+
+            self.released = released.saturating_sub(shrunk).min(resident.saturating_sub(self.baseline));
+            self.resident = Some(resident);
+            self.measured_estimate = estimate;
+        }
+
+        if self.offloaded {
+            self.offloaded = false;
+
+            self.backoff = match self.in_use(estimate) > self.limit {
+                true => (self.backoff * 2).min(MAX_BACKOFF),
+                false => 1,
+            };
+        }
+
+        self.waiting = self.waiting.saturating_sub(1);
+    }
+
+    pub fn per_candidate(n: usize) -> u64 {
+        let factorial = Bounds::factorial(n);
+
+        let bitset_bytes = allocation(factorial.div_ceil(32) * 4);
+        let tail_bytes = allocation(n - 1);
+
+        (size_of::<Candidate>() + bitset_bytes + tail_bytes) as u64
+    }
+}
+
+/// The bytes the allocator sets aside for an allocation of `bytes`.
+pub(super) fn allocation(bytes: usize) -> usize {
+    match bytes {
+        0 => 0,
+        b => (b + ALLOCATION_HEADER).next_multiple_of(ALLOCATION_ALIGN).max(ALLOCATION_MIN),
+    }
+}
+
+/// The resident set of the process, or None if it can't be read, e.g. because
+/// there's no /proc on this system.
+pub(super) fn resident_bytes() -> Option<u64> {
+    let status = read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+
+    Some(kilobytes * 1024)
+}
@@ -1,105 +1,567 @@
+mod dominance;
+mod memory;
+mod prefetch;
+mod priority;
+mod transpositions;
+mod writer;
+
 use super::candidate::Candidate;
-use super::disk::Disk;
-use super::ui::UI;
+use super::disk::{self, ChunkReader, Codec, Disk, Index, Usage};
+use super::events::{Event, EventLog};
 
 use ::bucket_queue::*;
+use bincode::{serialize_into, deserialize_from};
 
+use std::cmp::Reverse;
 use std::collections::VecDeque;
-use std::collections::HashSet;
-use rayon::prelude::*;
-
-type PriorityQueue = BucketQueue<BucketQueue<VecDeque<Candidate>>>;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::{Read, Write};
+use std::mem::size_of;
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+pub use self::dominance::Dominance;
+pub use self::memory::Accounting;
+pub use self::priority::Priority;
+pub use self::transpositions::Transpositions;
+
+use self::memory::{Memory, allocation, resident_bytes};
+use self::prefetch::{Batch, Prefetch};
+use self::priority::{Bucket, Tiebreak};
+use self::writer::{Job, Writer};
+
+type PriorityQueue = BucketQueue<BucketQueue<Bucket>>;
 type BucketID = (usize, usize);
-
+type Spilled = BTreeSet<(usize, Reverse<usize>)>;
+
+const RETRY_SECONDS: [u64; 3] = [1, 4, 16];
+const STREAM_BATCH: usize = 4096;
+
+/// The candidates waiting to be expanded, in buckets by their total waste and
+/// number of permutations. Candidates are taken from the enabled buckets with
+/// the least waste and then the most permutations, and then by the `Priority`
+/// within a bucket. Buckets that are pruned are disabled until the bounds allow
+/// them again, and when the process uses more memory than the limit buckets are
+/// written to scratch files on disk: the disabled ones first and then the
+/// enabled ones with the lowest priority. They're written on a background
+/// thread, and the bucket that's likely to be enabled next is read back ahead
+/// of time.
 pub struct Frontier {
     enabled_queue: PriorityQueue,
     disabled_queue: PriorityQueue,
     disabled: HashSet<BucketID>,
-    disk: Disk,
-    queue_limit: usize,
-    verbose: bool,
+    /// Enabled buckets that have files on disk, in the order they're taken.
+    spilled: Spilled,
+    disk: Arc<Disk>,
+    writer: Arc<Writer>,
+    prefetch: Option<Prefetch>,
+    streams: HashMap<BucketID, ChunkReader>,
+    transpositions: Option<Transpositions>,
+    dominance: Option<Dominance>,
+    tiebreak: Option<Tiebreak>,
+    storage_error: Option<disk::Error>,
+    memory: Memory,
+    expanded: u64,
+    log: Option<EventLog>,
+}
+
+/// A snapshot of where the candidates are, for the status line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub enabled: usize,
+    pub disabled: usize,
+    pub on_disk: u64,
+    pub expanded: u64,
+    pub bytes_written: u64,
+    pub bytes_read: u64,
+    /// The memory the process is estimated to use, from the accounting of the
+    /// frontier and what the process used before it had any candidates.
+    pub estimated_bytes: u64,
+    /// The memory the process actually uses, if it can be measured.
+    pub resident_bytes: Option<u64>,
 }
 
 impl Frontier {
-    pub fn new(memory_limit: f64, gzip: bool, verbose: bool, n: usize) -> Self {
-        Frontier {
+    /// Creates an empty frontier that offloads candidates to disk when the
+    /// process uses more than `memory_limit` GiB. The scratch directory is
+    /// emptied.
+    pub fn new(memory_limit: f64, scratch_dir: &str, codec: Codec, n: usize) -> Result<Self, disk::Error> {
+        let disk = Arc::new(Disk::new(scratch_dir.to_string(), codec, n)?);
+
+        Ok(Frontier {
             enabled_queue: PriorityQueue::new(),
             disabled_queue: PriorityQueue::new(),
             disabled: HashSet::new(),
-            disk: Disk::new("scratch-files".to_string(), gzip),
-            queue_limit: Self::queue_limit(memory_limit, n),
-            verbose,
+            spilled: Spilled::new(),
+            writer: Arc::new(Writer::new(disk.clone())),
+            prefetch: None,
+            disk,
+            streams: HashMap::new(),
+            transpositions: None,
+            dominance: None,
+            tiebreak: None,
+            storage_error: None,
+            memory: Memory::new(memory_limit, n),
+            expanded: 0,
+            log: None,
+        })
+    }
+
+    /// Reads a frontier that was written by `save`, reopening the scratch files
+    /// it had written.
+    pub fn resume<R: Read>(memory_limit: f64, scratch_dir: &str, codec: Codec, n: usize, reader: &mut R) -> bincode::Result<Self> {
+        let disabled: HashSet<BucketID> = deserialize_from(&mut *reader)?;
+        let index: Index = deserialize_from(&mut *reader)?;
+        let usage: Usage = deserialize_from(&mut *reader)?;
+        let transpositions: Option<Transpositions> = deserialize_from(&mut *reader)?;
+        let expanded: u64 = deserialize_from(&mut *reader)?;
+        let positions: Vec<(BucketID, u64)> = deserialize_from(&mut *reader)?;
+        let disk = Disk::open(scratch_dir.to_string(), codec, n, index, usage).map_err(|e| bincode::ErrorKind::Custom(e.to_string()))?;
+        let disk = Arc::new(disk);
+
+        let mut frontier = Frontier {
+            enabled_queue: PriorityQueue::new(),
+            disabled_queue: PriorityQueue::new(),
+            disabled,
+            spilled: Spilled::new(),
+            writer: Arc::new(Writer::new(disk.clone())),
+            prefetch: None,
+            disk,
+            streams: HashMap::new(),
+            transpositions,
+            dominance: None,
+            tiebreak: None,
+            storage_error: None,
+            memory: Memory::new(memory_limit, n),
+            expanded,
+            log: None,
+        };
+
+        while let Some((enabled, bucket_id, bucket)) = deserialize_from::<_, Option<(bool, BucketID, VecDeque<Candidate>)>>(&mut *reader)? {
+            let queue = match enabled {
+                true => &mut frontier.enabled_queue,
+                false => &mut frontier.disabled_queue,
+            };
+
+            Self::merge_into(queue, &bucket_id, priority::bucket(bucket, None));
+        }
+
+        for (bucket_id, position) in positions {
+            frontier.resume_stream(&bucket_id, position).map_err(|e| bincode::ErrorKind::Custom(e.to_string()))?;
+        }
+
+        // Buckets on disk that aren't disabled or being streamed were spilled.
+        for bucket_id in frontier.disk.buckets() {
+            if !frontier.disabled.contains(&bucket_id) && !frontier.streams.contains_key(&bucket_id) {
+                frontier.spilled.insert(Self::spilled_key(&bucket_id));
+            }
+        }
+
+        Ok(frontier)
+    }
+
+    // Files that were part way through being streamed are opened again and the
+    // candidates that were read before the checkpoint was saved are skipped.
+    fn resume_stream(&mut self, bucket_id: &BucketID, position: u64) -> Result<(), disk::Error> {
+        let mut reader = match self.disk.stream(bucket_id.0, bucket_id.1)? {
+            None => return Ok(()),
+            Some(reader) => reader,
+        };
+
+        reader.skip_to(position)?;
+        self.streams.insert(*bucket_id, reader);
+
+        if Self::bucket_len(&self.enabled_queue, bucket_id) == 0 {
+            self.refill(bucket_id);
+        }
+
+        Ok(())
+    }
+
+    /// Writes everything in memory, and the index of the scratch files, so the
+    /// frontier can be resumed. Waits for buckets that are being offloaded to be
+    /// written first, and saves any that couldn't be with the rest in memory.
+    pub fn save<W: Write>(&self, writer: &mut W) -> bincode::Result<()> {
+        self.writer.flush();
+
+        serialize_into(&mut *writer, &self.disabled)?;
+        serialize_into(&mut *writer, &self.disk.index())?;
+        serialize_into(&mut *writer, &self.disk.usage())?;
+        serialize_into(&mut *writer, &self.transpositions)?;
+        serialize_into(&mut *writer, &self.expanded)?;
+
+        let positions: Vec<(BucketID, u64)> = self.streams.iter().map(|(id, r)| (*id, r.position())).collect();
+        serialize_into(&mut *writer, &positions)?;
+
+        for &(enabled, queue) in &[(true, &self.enabled_queue), (false, &self.disabled_queue)] {
+            for (bucket_id, bucket) in Self::buckets(queue) {
+                let candidates = priority::keys(bucket).flatten().collect::<Vec<_>>();
+                serialize_into(&mut *writer, &Some((enabled, bucket_id, candidates)))?;
+            }
+        }
+
+        self.writer.for_each_failure(|(bucket, w, p)| {
+            serialize_into(&mut *writer, &Some((!self.disabled.contains(&(*w, *p)), (*w, *p), bucket)))
+        })?;
+
+        serialize_into(writer, &None::<(bool, BucketID, &VecDeque<Candidate>)>)
+    }
+
+    /// Emits events to the log when buckets are pruned, enabled, offloaded and
+    /// onloaded.
+    pub fn log_to(&mut self, log: EventLog) {
+        self.writer.log_to(log.clone());
+        self.log = Some(log);
+    }
+
+    /// Keeps scratch files that have been read until the next checkpoint.
+    pub fn defer_disk_removals(&mut self) {
+        self.disk.defer_removals();
+    }
+
+    /// Files that can't be removed are left on disk and logged.
+    pub fn remove_consumed_disk_files(&self) {
+        for error in self.disk.remove_consumed() {
+            self.emit(Event::Leftover { error: error.to_string() });
+        }
+    }
+
+    /// Candidates that are identical to one already added are dropped. This is
+    /// most useful when candidates have been put into a canonical form first.
+    pub fn drop_duplicates(&mut self) {
+        if self.transpositions.is_none() {
+            self.transpositions = Some(Transpositions::default());
+        }
+    }
+
+    pub fn transpositions(&self) -> Option<&Transpositions> {
+        self.transpositions.as_ref()
+    }
+
+    /// Candidates that can't do better than one that has already been added are
+    /// dropped, both when adding them and when loading them from disk.
+    pub fn check_dominance(&mut self, capacity: usize, n: usize) {
+        self.dominance = Some(Dominance::new(capacity, n));
+    }
+
+    pub fn dominance(&mut self) -> Option<&mut Dominance> {
+        self.dominance.as_mut()
+    }
+
+    /// Chooses how candidates in the same bucket are ordered. The default is
+    /// first in, first out, which is the fastest.
+    pub fn prioritize(&mut self, priority: Priority, n: usize) {
+        self.tiebreak = match priority {
+            Priority::Fifo => None,
+            _ => Some(Tiebreak::new(priority, n)),
+        };
+
+        // Candidates that were added before are keyed again.
+        for queue in [&mut self.enabled_queue, &mut self.disabled_queue] {
+            let bucket_ids = Self::buckets(queue).map(|(bucket_id, _)| bucket_id).collect::<Vec<_>>();
+
+            for (w, p) in bucket_ids {
+                let mut waste_bucket = queue.bucket(w);
+                let bucket = priority::flatten(waste_bucket.replace(p, None).unwrap());
+
+                waste_bucket.replace(p, Some(priority::bucket(bucket, self.tiebreak.as_ref())));
+            }
         }
     }
 
+    /// Adds a candidate to its bucket, unless it's a duplicate or dominated,
+    /// and offloads buckets to disk if the process is over the memory limit.
     pub fn add(&mut self, candidate: Candidate, n: usize) {
         let wasted_symbols = candidate.total_waste(n);
         let permutations = candidate.permutations_seen.len();
 
+        if let Some(transpositions) = &mut self.transpositions {
+            if !transpositions.insert((wasted_symbols, permutations), &candidate) {
+                return;
+            }
+        }
+
+        if let Some(dominance) = &mut self.dominance {
+            if !dominance.insert(&candidate) {
+                return;
+            }
+        }
+
+        let key = self.tiebreak.as_ref().map_or(0, |t| t.key(&candidate));
+
         self.queue_for(&(wasted_symbols, permutations))
             .bucket_for_adding(wasted_symbols)
-            .enqueue(candidate, permutations);
+            .bucket_for_adding(permutations)
+            .enqueue(candidate, key);
 
         self.offload_buckets_to_disk();
     }
 
-    pub fn next(&mut self) -> Option<Candidate> {
-        let waste = self.min_waste()?;
-        let bucket = self.enabled_queue.bucket_for_removing(waste)?;
+    /// The number of candidates in memory, whether enabled or disabled.
+    pub fn len(&self) -> usize {
+        self.enabled_queue.len() + self.disabled_queue.len()
+    }
 
-        bucket.dequeue_max()
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
+    /// Whether there are disabled candidates in memory or on disk, including
+    /// those that are being written. This looks for the files themselves
+    /// rather than trusting the count of candidates on disk.
+    pub fn has_disabled(&self) -> bool {
+        !self.disabled_queue.is_empty() || self.disabled.iter().any(|bucket_id| self.on_disk(bucket_id))
+    }
+
+    /// Disables the buckets with `wasted_symbols` total waste, or more if it's
+    /// `eager`, that have fewer permutations than the threshold.
     pub fn prune(&mut self, wasted_symbols: usize, threshold: usize, eager: bool) -> Option<()> {
         let max = match eager {
             true => self.max_waste()?,
             false => wasted_symbols,
         };
 
+        let mut buckets = vec![];
+        let mut candidates = 0;
+
         for w in wasted_symbols..=max {
             for p in 0..threshold {
-                self.disable(&(w, p));
+                if self.disable(&(w, p)) && self.log.is_some() {
+                    buckets.push((w, p));
+                    candidates += Self::bucket_len(&self.disabled_queue, &(w, p));
+                }
             }
         }
 
+        self.emit(Event::Prune { wasted_symbols, max_waste: max, threshold, buckets, candidates });
+
         None
     }
 
+    /// When the search moves on to a new number of wasted symbols, enables the
+    /// first disabled bucket that could now improve the bounds and returns its
+    /// waste. Otherwise the waste that was given is returned.
     pub fn unprune(&mut self, wasted_symbols: usize, lower_bounds: &[usize], upper_bounds: &[usize]) -> usize {
+        self.restore_failures();
+
         if wasted_symbols < lower_bounds.len() {
             return wasted_symbols;
         }
 
+        for bucket_id in Self::unprune_order(wasted_symbols, lower_bounds, upper_bounds) {
+            if self.enable(&bucket_id) {
+                return bucket_id.0;
+            }
+        }
+
+        wasted_symbols
+    }
+
+    /// Starts reading the bucket that `unprune` would enable if the search
+    /// moved on to the next number of wasted symbols with these bounds, if
+    /// it's on disk. This should be called each time the bounds improve.
+    pub fn prefetch(&mut self, lower_bounds: &[usize], upper_bounds: &[usize]) {
+        if lower_bounds.is_empty() {
+            return;
+        }
+
+        let next = Self::unprune_order(lower_bounds.len(), lower_bounds, upper_bounds).find(|bucket_id| {
+            self.disabled.contains(bucket_id) && (self.on_disk(bucket_id) || Self::bucket_len(&self.disabled_queue, bucket_id) > 0)
+        });
+
+        let next = next.filter(|bucket_id| self.on_disk(bucket_id) && !self.streams.contains_key(bucket_id));
+
+        if self.prefetch.as_ref().map(|p| p.bucket_id()) == next.as_ref() {
+            return;
+        }
+
+        // A prefetch that isn't needed any more finishes in the background.
+        self.prefetch = next.map(|bucket_id| Prefetch::start(bucket_id, self.disk.clone(), self.writer.clone()));
+    }
+
+    // The buckets that could improve the lower bound for one less than
+    // `wasted_symbols`, in the order they're checked: the most waste first,
+    // since it leaves the least for the rest of the string, and then the most
+    // permutations.
+    fn unprune_order<'a>(wasted_symbols: usize, lower_bounds: &[usize], upper_bounds: &'a [usize]) -> impl Iterator<Item=BucketID> + 'a {
         let previous_waste = wasted_symbols - 1;
         let lower_bound = lower_bounds[previous_waste];
 
-        for w in (1..previous_waste).rev() {
+        (1..previous_waste).rev().flat_map(move |w| {
             let allowed_waste = previous_waste - w;
             let max_permutations = upper_bounds[allowed_waste];
 
-            let min = lower_bound + 1 - max_permutations;
+            let min = (lower_bound + 1).saturating_sub(max_permutations);
             let max = upper_bounds[w];
 
-            for p in (min..max).rev() {
-                if self.enable(&(w, p)) {
-                    return w;
-                }
-            }
+            (min..max).rev().map(move |p| (w, p))
+        })
+    }
+
+    // Whether the bucket has files on disk or is being written to them.
+    fn on_disk(&self, bucket_id: &BucketID) -> bool {
+        self.disk.has_files(bucket_id.0, bucket_id.1) || self.writer.is_writing(bucket_id)
+    }
+
+    /// If the disk can't be read from or written to, the candidates are kept in
+    /// memory and the error is held here. The search can't continue correctly
+    /// without the candidates on disk so it should stop when this is set.
+    pub fn storage_error(&self) -> Option<&disk::Error> {
+        self.storage_error.as_ref()
+    }
+
+    pub fn stats(&self) -> Stats {
+        let usage = self.disk.usage();
+
+        Stats {
+            enabled: self.enabled_queue.len(),
+            disabled: self.disabled_queue.len(),
+            on_disk: usage.candidates + self.writer.candidates() as u64,
+            expanded: self.expanded,
+            bytes_written: usage.bytes_written,
+            bytes_read: usage.bytes_read,
+            estimated_bytes: self.memory.baseline() + self.accounting().total(),
+            resident_bytes: resident_bytes(),
         }
+    }
 
-        wasted_symbols
+    /// The memory limit for the process, in bytes.
+    pub fn memory_limit(&self) -> u64 {
+        self.memory.limit()
     }
 
-    pub fn len(&self) -> usize {
-        self.enabled_queue.len() + self.disabled_queue.len()
+    /// Roughly how many bytes each candidate uses, including its heap
+    /// allocations.
+    pub fn bytes_per_candidate(&self) -> u64 {
+        self.memory.bytes_per_candidate()
     }
 
+    /// Estimates the memory used by each structure in the frontier. This walks
+    /// every bucket, so it's meant for reporting rather than every step.
+    pub fn accounting(&self) -> Accounting {
+        let mut accounting = Accounting {
+            candidates: self.memory.estimate(self.len()),
+            disabled: (self.disabled.capacity() * (size_of::<BucketID>() + 1)) as u64,
+            transpositions: self.transpositions.as_ref().map_or(0, |t| t.bytes()),
+            ..Accounting::default()
+        };
+
+        for queue in &[&self.enabled_queue, &self.disabled_queue] {
+            let outer_slots = queue.max_priority().map_or(0, |w| w + 1);
+            accounting.queues += (outer_slots * size_of::<Option<BucketQueue<Bucket>>>()) as u64;
+
+            for w in 0..outer_slots {
+                let inner_slots = queue.bucket_for_peeking(w).and_then(|b| b.max_priority()).map_or(0, |p| p + 1);
+                accounting.queues += (inner_slots * size_of::<Option<Bucket>>()) as u64;
+            }
+
+            for (_, bucket) in Self::buckets(queue) {
+                let key_slots = bucket.max_priority().map_or(0, |k| k + 1);
+                accounting.queues += (key_slots * size_of::<Option<VecDeque<Candidate>>>()) as u64;
+
+                for candidates in priority::keys(bucket) {
+                    let spare = allocation(candidates.capacity() * size_of::<Candidate>()) - candidates.len() * size_of::<Candidate>();
+                    accounting.spare_capacity += spare as u64;
+                }
+            }
+        }
+
+        accounting
+    }
+
+    /// The least total waste of the enabled candidates, including those that
+    /// were spilled to disk.
     pub fn min_waste(&self) -> Option<usize> {
-        self.enabled_queue.min_priority()
+        let spilled = self.spilled.first().map(|&(w, _)| w);
+        self.enabled_queue.min_priority().into_iter().chain(spilled).min()
     }
 
     pub fn max_waste(&self) -> Option<usize> {
-        self.enabled_queue.max_priority()
+        let spilled = self.spilled.last().map(|&(w, _)| w);
+        self.enabled_queue.max_priority().into_iter().chain(spilled).max()
+    }
+
+    /// Takes up to `size` candidates from the best enabled bucket, so they all
+    /// have the same total waste and number of permutations.
+    pub fn next_batch(&mut self, size: usize) -> Vec<Candidate> {
+        let mut batch = vec![];
+
+        self.onload_spilled();
+        let bucket_id = self.best_bucket();
+
+        while batch.len() < size && bucket_id.is_some() && self.best_bucket() == bucket_id {
+            batch.extend(self.next());
+        }
+
+        batch
+    }
+
+    // The best enabled bucket in memory.
+    fn best_bucket(&self) -> Option<BucketID> {
+        let waste = self.enabled_queue.min_priority()?;
+        let permutations = self.enabled_queue.bucket_for_peeking(waste)?.max_priority()?;
+
+        Some((waste, permutations))
+    }
+
+    // Spilled buckets are ordered by their key in the same way as the enabled
+    // queue takes them: the least waste and then the most permutations.
+    fn spilled_key(bucket_id: &BucketID) -> (usize, Reverse<usize>) {
+        (bucket_id.0, Reverse(bucket_id.1))
+    }
+
+    // Streams each spilled bucket that's at least as good as the best one in
+    // memory, so that `next` takes candidates in the same order as if they had
+    // never been spilled.
+    fn onload_spilled(&mut self) {
+        self.restore_failures();
+
+        while let Some(&(w, Reverse(p))) = self.spilled.first() {
+            if self.best_bucket().is_some_and(|best| Self::spilled_key(&best) < (w, Reverse(p))) {
+                return;
+            }
+
+            self.spilled.pop_first();
+
+            if !self.stream_spilled(&(w, p)) {
+                return;
+            }
+        }
+    }
+
+    // A bucket that was spilled part way through streaming a file carries on
+    // with that file. Candidates that were added since it was spilled are
+    // taken first and the file is read when they run out.
+    fn stream_spilled(&mut self, bucket_id: &BucketID) -> bool {
+        let started = Instant::now();
+
+        if !self.streams.contains_key(bucket_id) {
+            self.writer.wait_for(bucket_id);
+            let disk = &self.disk;
+
+            let reader = match Self::retry(self.log.as_ref(), || disk.stream(bucket_id.0, bucket_id.1)) {
+                Ok(None) => return true,
+                Ok(Some(reader)) => reader,
+                Err(error) => {
+                    self.storage_error = Some(error);
+                    return false;
+                },
+            };
+
+            self.streams.insert(*bucket_id, reader);
+        }
+
+        if Self::bucket_len(&self.enabled_queue, bucket_id) == 0 {
+            self.refill(bucket_id);
+
+            let candidates = Self::bucket_len(&self.enabled_queue, bucket_id);
+            let seconds = started.elapsed().as_secs_f64();
+
+            self.emit(Event::Onload { bucket: *bucket_id, candidates, seconds });
+        }
+
+        self.storage_error.is_none()
     }
 
     fn enable(&mut self, bucket_id: &BucketID) -> bool {
@@ -107,23 +569,41 @@ impl Frontier {
             return false;
         }
 
-        if self.onload_from_disk(bucket_id) {
-            if self.verbose {
-                println!("  unpruning {:?} from disk ..... queue: {}", bucket_id, self.len());
+        let onloaded = self.onload_from_disk(bucket_id);
+
+        // A bucket with files that couldn't be read stays disabled, so that
+        // they're read when it's enabled again after resuming.
+        if self.storage_error.is_some() && self.disk.has_files(bucket_id.0, bucket_id.1) {
+            return false;
+        }
+
+        if onloaded {
+            // The rest of its files are streamed in turn, as if it had been
+            // spilled, and the candidates in memory are taken after the ones
+            // from disk since they were added later.
+            self.disabled.remove(bucket_id);
+
+            if let Some(newer) = self.disabled_queue.bucket(bucket_id.0).replace(bucket_id.1, None) {
+                let mut waste_bucket = self.enabled_queue.bucket(bucket_id.0);
+                let mut bucket = waste_bucket.replace(bucket_id.1, None).unwrap_or_else(Bucket::new);
+
+                priority::append(&mut bucket, newer);
+                waste_bucket.replace(bucket_id.1, Some(bucket));
             }
 
+            let candidates = Self::bucket_len(&self.enabled_queue, bucket_id);
+            self.emit(Event::Enable { bucket: *bucket_id, from_disk: true, candidates });
+
             return true;
         }
 
-        if self.disabled.remove(bucket_id) {
-            if Self::bucket_len(&self.disabled_queue, bucket_id) > 0 {
-                if self.verbose {
-                    println!("  unpruning {:?} from memory ... queue: {}", bucket_id, self.len());
-                }
+        if self.disabled.remove(bucket_id) && Self::bucket_len(&self.disabled_queue, bucket_id) > 0 {
+            Self::swap(&mut self.disabled_queue, &mut self.enabled_queue, bucket_id);
 
-                Self::swap(&mut self.disabled_queue, &mut self.enabled_queue, bucket_id);
-                return true;
-            }
+            let candidates = Self::bucket_len(&self.enabled_queue, bucket_id);
+            self.emit(Event::Enable { bucket: *bucket_id, from_disk: false, candidates });
+
+            return true;
         }
 
         false
@@ -131,6 +611,8 @@ impl Frontier {
 
     fn disable(&mut self, bucket_id: &BucketID) -> bool {
         if self.disabled.insert(*bucket_id) {
+            self.spilled.remove(&Self::spilled_key(bucket_id));
+
             Self::swap(&mut self.enabled_queue, &mut self.disabled_queue, bucket_id).is_some()
         } else {
             false
@@ -159,41 +641,226 @@ impl Frontier {
         }
     }
 
+    // Files are streamed into the bucket a batch at a time, rather than read
+    // all at once, so that onloading doesn't need much memory. A bucket that
+    // was disabled part way through streaming a file carries on with it, since
+    // opening the file again would read its candidates twice.
     fn onload_from_disk(&mut self, bucket_id: &BucketID) -> bool {
-        let bucket = match self.disk.read(bucket_id.0, bucket_id.1) {
-            None => return false,
-            Some(bucket) => bucket,
-        };
+        let started = Instant::now();
 
         if Self::bucket_len(&self.enabled_queue, bucket_id) > 0 {
             panic!("about to overwrite data");
         }
 
-        self.enabled_queue
-            .bucket(bucket_id.0)
-            .replace(bucket_id.1, Some(bucket));
+        let prefetched = match self.streams.contains_key(bucket_id) {
+            true => None,
+            false => {
+                let (reader, prefetched) = match self.take_prefetched(bucket_id) {
+                    Some((reader, batch, error)) => (reader, Some((batch, error))),
+                    None => {
+                        self.writer.wait_for(bucket_id);
+                        let disk = &self.disk;
+
+                        match Self::retry(self.log.as_ref(), || disk.stream(bucket_id.0, bucket_id.1)) {
+                            Ok(None) => return false,
+                            Ok(Some(reader)) => (reader, None),
+                            Err(error) => {
+                                self.storage_error = Some(error);
+                                return false;
+                            },
+                        }
+                    },
+                };
 
-        true
+                self.streams.insert(*bucket_id, reader);
+                prefetched
+            },
+        };
+
+        self.refill_with(bucket_id, prefetched);
+
+        let candidates = Self::bucket_len(&self.enabled_queue, bucket_id);
+
+        if candidates > 0 {
+            let seconds = started.elapsed().as_secs_f64();
+            self.emit(Event::Onload { bucket: *bucket_id, candidates, seconds });
+
+            return true;
+        }
+
+        // Every candidate in the file was dominated, so try the next one.
+        self.storage_error.is_none() && self.onload_from_disk(bucket_id)
+    }
+
+    // The prefetched batch is only used if it's for this bucket. Otherwise it's
+    // left to finish, since the bounds may still come round to it.
+    fn take_prefetched(&mut self, bucket_id: &BucketID) -> Option<Batch> {
+        match &self.prefetch {
+            Some(prefetch) if prefetch.bucket_id() == bucket_id => self.prefetch.take()?.finish(),
+            _ => None,
+        }
+    }
+
+    fn refill(&mut self, bucket_id: &BucketID) {
+        self.refill_with(bucket_id, None);
+    }
+
+    // A batch that was prefetched is used first, as if it had been read here.
+    fn refill_with(&mut self, bucket_id: &BucketID, mut prefetched: Option<(VecDeque<Candidate>, Option<disk::Error>)>) {
+        loop {
+            let reader = match self.streams.get_mut(bucket_id) {
+                None => return,
+                Some(reader) => reader,
+            };
+
+            let (mut batch, error) = prefetched.take().unwrap_or_else(|| Self::read_batch(reader));
+
+            let exhausted = batch.len() < STREAM_BATCH;
+            self.disk.mark_read(batch.len());
+
+            if let Some(dominance) = &mut self.dominance {
+                dominance.retain_undominated(&mut batch);
+            }
+
+            if exhausted {
+                let failed = error.is_some();
+                self.streams.remove(bucket_id);
+
+                match error {
+                    Some(error) => self.storage_error = Some(error),
+                    None => if let Err(error) = self.disk.consume(bucket_id.0, bucket_id.1) {
+                        self.emit(Event::Leftover { error: error.to_string() });
+                    },
+                }
+
+                // An enabled bucket can be spilled more than once, so it goes
+                // back in line if it has more files.
+                if !failed && !self.disabled.contains(bucket_id) && self.on_disk(bucket_id) {
+                    self.spilled.insert(Self::spilled_key(bucket_id));
+                }
+            }
+
+            if !batch.is_empty() {
+                let bucket = priority::bucket(batch, self.tiebreak.as_ref());
+                self.enabled_queue.bucket(bucket_id.0).replace(bucket_id.1, Some(bucket));
+                return;
+            }
+
+            if exhausted {
+                return;
+            }
+        }
+    }
+
+    fn read_batch(reader: &mut ChunkReader) -> (VecDeque<Candidate>, Option<disk::Error>) {
+        let mut batch = VecDeque::with_capacity(STREAM_BATCH);
+
+        for result in reader.by_ref().take(STREAM_BATCH) {
+            match result {
+                Ok(candidate) => batch.push_back(candidate),
+                Err(error) => return (batch, Some(error)),
+            }
+        }
+
+        (batch, None)
+    }
+
+    fn forget_transpositions(&mut self, waste: usize) {
+        let transpositions = match &mut self.transpositions {
+            None => return,
+            Some(t) => t,
+        };
+
+        if !transpositions.should_forget(waste) {
+            return;
+        }
+
+        let disabled_waste = self.disabled_queue.min_priority().unwrap_or(waste);
+        let disk_waste = self.disk.min_waste().unwrap_or(waste);
+        let writer_waste = self.writer.min_waste().unwrap_or(waste);
+
+        transpositions.forget_below(waste.min(disabled_waste).min(disk_waste).min(writer_waste));
     }
 
+    // Disabled buckets are offloaded first, since they're only needed again if
+    // the bounds allow them. If that doesn't free enough memory, enabled
+    // buckets are spilled as well, starting with the lowest priority: the most
+    // waste and then the fewest permutations. The best bucket is always kept so
+    // that the search can carry on, and spilled buckets are onloaded again when
+    // `next` gets to them. The buckets are written in the background.
     fn offload_buckets_to_disk(&mut self) {
-        if self.len() <= self.queue_limit {
+        if !self.memory.over_limit(self.len()) || self.storage_error.is_some() {
             return;
         }
 
-        print!("running low on memory, offloading to disk... ");
-        UI::flush();
-        if self.verbose { println!(); }
+        let to_free = self.memory.candidates_to_free(self.len());
+
+        // Each offload is reported by the writer, in the event log.
+        if self.offload(to_free) {
+            self.memory.offloaded();
+        }
+    }
+
+    // Returns false if there was nothing to offload.
+    fn offload(&mut self, to_free: usize) -> bool {
+        let mut jobs = Self::take_disabled(&mut self.disabled_queue);
+        let freed = jobs.iter().map(|(bucket, _, _)| bucket.len()).sum::<usize>();
+
+        let mut spilled = vec![];
+
+        if freed < to_free {
+            spilled = self.take_lowest_enabled(to_free - freed);
+        }
+
+        if jobs.is_empty() && spilled.is_empty() {
+            return false;
+        }
+
+        let spilled_candidates = spilled.iter().map(|(bucket, _, _)| bucket.len()).sum();
+
+        for &(_, w, p) in &spilled {
+            self.spilled.insert(Self::spilled_key(&(w, p)));
+        }
+
+        jobs.extend(spilled);
+        self.writer.submit(jobs, spilled_candidates);
 
-        let queue = &mut self.disabled_queue;
+        true
+    }
+
+    // Buckets that couldn't be written are put back with any candidates that
+    // have been added to them since, and the search stops.
+    fn restore_failures(&mut self) {
+        for ((bucket, w, p), error) in self.writer.take_failures() {
+            self.spilled.remove(&Self::spilled_key(&(w, p)));
+            let bucket = priority::bucket(bucket, self.tiebreak.as_ref());
+            Self::merge_into(self.queue_for(&(w, p)), &(w, p), bucket);
+            self.storage_error.get_or_insert(error);
+        }
+    }
 
-        let waste_min = queue.min_priority().unwrap();
-        let waste_max = queue.max_priority().unwrap();
+    // The candidates that are merged in go first, since they were added before
+    // the ones that are already there.
+    fn merge_into(queue: &mut PriorityQueue, bucket_id: &BucketID, mut bucket: Bucket) {
+        let mut waste_bucket = queue.bucket(bucket_id.0);
+
+        if let Some(existing) = waste_bucket.replace(bucket_id.1, None) {
+            priority::append(&mut bucket, existing);
+        }
+
+        waste_bucket.replace(bucket_id.1, Some(bucket));
+    }
+
+    fn take_disabled(queue: &mut PriorityQueue) -> Vec<Job> {
+        let (waste_min, waste_max) = match (queue.min_priority(), queue.max_priority()) {
+            (Some(min), Some(max)) => (min, max),
+            _ => return vec![],
+        };
 
         let mut jobs = vec![];
 
         for w in waste_min..=waste_max {
-            let mut waste_bucket = self.disabled_queue.bucket(w);
+            let mut waste_bucket = queue.bucket(w);
 
             let perm_min = match waste_bucket.min_priority() {
                 None => continue,
@@ -202,33 +869,99 @@ impl Frontier {
 
             let perm_max = waste_bucket.max_priority().unwrap();
 
-            if self.verbose {
-                print!("  {:02}, {:03}..{:03} | ", w, perm_min, perm_max);
-            }
-
             for p in perm_min..=perm_max {
                 let bucket = match waste_bucket.replace(p, None) {
                     None => continue,
                     Some(b) => b,
                 };
 
-                if self.verbose {
-                    print!("{} ", bucket.len());
+                jobs.push((priority::flatten(bucket), w, p));
+            }
+        }
+
+        jobs
+    }
+
+    // Takes whole buckets until at least `to_free` candidates have been taken,
+    // so a bucket may be spilled even if only some of it needed to be.
+    fn take_lowest_enabled(&mut self, to_free: usize) -> Vec<Job> {
+        let best = match self.best_bucket() {
+            None => return vec![],
+            Some(best) => best,
+        };
+
+        let mut jobs = vec![];
+        let mut taken = 0;
+
+        for w in (best.0..=self.enabled_queue.max_priority().unwrap()).rev() {
+            let mut waste_bucket = self.enabled_queue.bucket(w);
+
+            let (perm_min, perm_max) = match (waste_bucket.min_priority(), waste_bucket.max_priority()) {
+                (Some(min), Some(max)) => (min, max),
+                _ => continue,
+            };
+
+            for p in perm_min..=perm_max {
+                if taken >= to_free || (w, p) == best {
+                    return jobs;
                 }
 
-                jobs.push((bucket, w, p));
+                if let Some(bucket) = waste_bucket.replace(p, None) {
+                    taken += bucket.len();
+                    jobs.push((priority::flatten(bucket), w, p));
+                }
             }
+        }
+
+        jobs
+    }
 
-            if self.verbose {
-                println!();
+    fn emit(&self, event: Event) {
+        if let Some(log) = &self.log {
+            log.emit(event);
+        }
+    }
+
+    fn retry<T, F: FnMut() -> Result<T, disk::Error>>(log: Option<&EventLog>, mut f: F) -> Result<T, disk::Error> {
+        for &seconds in &RETRY_SECONDS {
+            match f() {
+                Err(error) if error.is_transient() => {
+                    if let Some(log) = log {
+                        log.emit(Event::Retry { error: error.to_string(), seconds });
+                    }
+
+                    sleep(Duration::from_secs(seconds));
+                },
+                result => return result,
             }
         }
 
-        jobs.into_par_iter().for_each(|job| {
-            self.disk.write_chunks(job.0, job.1, job.2);
-        });
+        f()
+    }
+
+    fn buckets(queue: &PriorityQueue) -> impl Iterator<Item=(BucketID, &Bucket)> {
+        let waste_range = match (queue.min_priority(), queue.max_priority()) {
+            (Some(min), Some(max)) => min..(max + 1),
+            _ => 0..0,
+        };
+
+        waste_range.flat_map(move |w| {
+            let waste_bucket = queue.bucket_for_peeking(w);
 
-        println!("done");
+            let perm_range = match waste_bucket.map(|b| (b.min_priority(), b.max_priority())) {
+                Some((Some(min), Some(max))) => min..(max + 1),
+                _ => 0..0,
+            };
+
+            perm_range.filter_map(move |p| {
+                let bucket = waste_bucket?.bucket_for_peeking(p)?;
+
+                match bucket.is_empty() {
+                    true => None,
+                    false => Some(((w, p), bucket)),
+                }
+            })
+        })
     }
 
     fn bucket_len(queue: &PriorityQueue, bucket_id: &BucketID) -> usize {
@@ -240,28 +973,39 @@ impl Frontier {
             },
         }
     }
+}
 
-    fn queue_limit(memory_limit: f64, n: usize) -> usize {
-        let bytes = Self::memory_per_candidate(n);
-        let gigabytes = memory_limit * 1024. * 1024. * 1024.;
+/// Takes the best enabled candidate: the one with the least total waste and
+/// then the most permutations, chosen by the priority if there's more than one.
+impl Iterator for Frontier {
+    type Item = Candidate;
 
-        let limit = (gigabytes / bytes as f64).floor() as usize;
-        println!("The queue limit has been set to {}GiB / {}B = {} candidates.\n", memory_limit, bytes, limit);
+    fn next(&mut self) -> Option<Candidate> {
+        self.onload_spilled();
 
-        limit
-    }
+        let waste = self.enabled_queue.min_priority()?;
+        self.forget_transpositions(waste);
+
+        let bucket = self.enabled_queue.bucket_for_removing(waste)?;
 
-    fn memory_per_candidate(n: usize) -> usize {
-        let factorial = super::Bounds::factorial(n);
+        let permutations = bucket.max_priority()?;
+        let bucket = bucket.bucket_for_removing(permutations)?;
 
-        let bitset_bytes = (factorial + 7) / 8;
-        let tail_bytes = (n - 1) * 8;
-        let waste_bytes = 8;
+        let candidate = match &mut self.tiebreak {
+            None => bucket.dequeue_max()?,
+            Some(tiebreak) => tiebreak.take(bucket)?,
+        };
+        self.expanded += 1;
+
+        if !self.streams.is_empty() {
+            let bucket_id = (waste, candidate.number_of_permutations());
 
-        let bytes = bitset_bytes + tail_bytes + waste_bytes;
-        println!("\nEach candidate string consumes approximately {} bytes of memory.", bytes);
+            if self.streams.contains_key(&bucket_id) && Self::bucket_len(&self.enabled_queue, &bucket_id) == 0 {
+                self.refill(&bucket_id);
+            }
+        }
 
-        bytes
+        Some(candidate)
     }
 }
 
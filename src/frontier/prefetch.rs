@@ -0,0 +1,48 @@
+use super::{BucketID, Frontier};
+use super::writer::Writer;
+use super::super::candidate::Candidate;
+use super::super::disk::{self, ChunkReader, Disk};
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+// When the search moves on to a new number of wasted symbols, `unprune` enables
+// a disabled bucket, which is often on disk. The prefetcher reads the first
+// batch of the bucket it would enable, given the bounds so far, on a background
+// thread so that it's ready by the time it's needed. The bounds can improve
+// before then, so the guess is made again each time they do, and a batch that
+// isn't used is dropped without marking anything as read.
+
+/// A file that has been opened and the first batch read from it.
+pub(super) type Batch = (ChunkReader, VecDeque<Candidate>, Option<disk::Error>);
+
+pub(super) struct Prefetch {
+    bucket_id: BucketID,
+    thread: JoinHandle<Option<Batch>>,
+}
+
+impl Prefetch {
+    pub fn start(bucket_id: BucketID, disk: Arc<Disk>, writer: Arc<Writer>) -> Self {
+        let thread = thread::spawn(move || {
+            writer.wait_for(&bucket_id);
+
+            let mut reader = disk.stream(bucket_id.0, bucket_id.1).ok()??;
+            let (batch, error) = Frontier::read_batch(&mut reader);
+
+            Some((reader, batch, error))
+        });
+
+        Self { bucket_id, thread }
+    }
+
+    pub fn bucket_id(&self) -> &BucketID {
+        &self.bucket_id
+    }
+
+    /// Waits for the batch. Returns None if the file couldn't be opened, in
+    /// which case it should be opened again as usual so the error is handled.
+    pub fn finish(self) -> Option<Batch> {
+        self.thread.join().ok()?
+    }
+}
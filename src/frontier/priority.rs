@@ -0,0 +1,154 @@
+use super::super::candidate::Candidate;
+
+use ::bucket_queue::*;
+use std::collections::VecDeque;
+
+// Buckets are always taken in order of total waste and then permutations, as
+// the bounds depend on it. The priority only decides which candidate is taken
+// from the best bucket, so each bucket is split again by the priority's key
+// for its candidates, which is small enough to be a level of the queue.
+
+/// The candidates in a bucket, by their key for the priority.
+pub type Bucket = BucketQueue<VecDeque<Candidate>>;
+
+/// How to choose between candidates with the same total waste and number of
+/// permutations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Priority {
+    /// The candidate that was added first.
+    Fifo,
+    /// The candidate with the longest tail, which is closest to being able to
+    /// see a new permutation.
+    LongestTail,
+    /// The candidate that can see the most new permutations in a row without
+    /// wasting a symbol.
+    Reachable,
+    /// A candidate at random, from a seed so that the search can be repeated.
+    Random(u64),
+}
+
+pub struct Tiebreak {
+    priority: Priority,
+    state: u64,
+    n: usize,
+}
+
+impl Tiebreak {
+    pub fn new(priority: Priority, n: usize) -> Self {
+        let state = match priority {
+            Priority::Random(seed) => seed,
+            _ => 0,
+        };
+
+        Self { priority, state, n }
+    }
+
+    /// The key a candidate is added to its bucket with. The highest key is
+    /// taken first and ties are broken by the order they were added in.
+    pub fn key(&self, candidate: &Candidate) -> usize {
+        match self.priority {
+            Priority::Fifo | Priority::Random(_) => 0,
+            Priority::LongestTail => candidate.tail_of_string.len(),
+            Priority::Reachable => Self::reachable(candidate, self.n),
+        }
+    }
+
+    /// Removes the chosen candidate. A random one is swapped with the last so
+    /// that it doesn't have to shift the rest of the bucket.
+    pub fn take(&mut self, bucket: &mut Bucket) -> Option<Candidate> {
+        match self.priority {
+            Priority::Random(_) => {
+                let index = (self.next_random() % bucket.len().max(1) as u64) as usize;
+                bucket.bucket_for_removing(0)?.swap_remove_back(index)
+            },
+            _ => bucket.dequeue_max(),
+        }
+    }
+
+    // Only these need more than one key.
+    fn is_keyed(&self) -> bool {
+        matches!(self.priority, Priority::LongestTail | Priority::Reachable)
+    }
+
+    // The symbols in a full tail are distinct, so only the missing symbol can
+    // see a new permutation. After that, appending the first symbol of the
+    // tail each time sees each rotation of that permutation in turn.
+    fn reachable(candidate: &Candidate, n: usize) -> usize {
+        let tail = &candidate.tail_of_string;
+
+        if tail.len() < n - 1 {
+            return 0;
+        }
+
+        let missing = (0..n as u8).find(|s| !tail.contains(s)).unwrap();
+        let mut permutation = tail.clone();
+        permutation.push(missing);
+
+        (0..n).take_while(|_| {
+            let id = Candidate::permutation_id(&permutation[..n - 1], permutation[n - 1]);
+            permutation.rotate_left(1);
+
+            !candidate.permutations_seen.contains(id)
+        }).count()
+    }
+
+    // SplitMix64, which is fine for any seed, including zero.
+    fn next_random(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+
+        z ^ (z >> 31)
+    }
+}
+
+/// Puts the candidates into a bucket by their key, in the order they're in for
+/// each key. Without a tiebreak they all have the same key.
+pub fn bucket(candidates: VecDeque<Candidate>, tiebreak: Option<&Tiebreak>) -> Bucket {
+    let mut bucket = Bucket::new();
+
+    match tiebreak.filter(|t| t.is_keyed()) {
+        None => { bucket.replace(0, Some(candidates)); },
+        Some(tiebreak) => for candidate in candidates {
+            let key = tiebreak.key(&candidate);
+            bucket.enqueue(candidate, key);
+        },
+    }
+
+    bucket
+}
+
+/// Takes the candidates out of a bucket in the order they would be taken by
+/// `next`, other than for a random priority.
+pub fn flatten(mut bucket: Bucket) -> VecDeque<Candidate> {
+    let mut candidates = VecDeque::new();
+
+    while let Some(key) = bucket.max_priority() {
+        candidates.append(&mut bucket.replace(key, None).unwrap());
+    }
+
+    candidates
+}
+
+/// Moves the candidates in `other` after those in `bucket` with the same key.
+pub fn append(bucket: &mut Bucket, mut other: Bucket) {
+    while let Some(key) = other.max_priority() {
+        let mut newer = other.replace(key, None).unwrap();
+        let mut candidates = bucket.replace(key, None).unwrap_or_default();
+
+        candidates.append(&mut newer);
+        bucket.replace(key, Some(candidates));
+    }
+}
+
+/// The candidates in a bucket for each key, from the highest key.
+pub fn keys(bucket: &Bucket) -> impl Iterator<Item=&VecDeque<Candidate>> {
+    let range = match (bucket.min_priority(), bucket.max_priority()) {
+        (Some(min), Some(max)) => min..(max + 1),
+        _ => 0..0,
+    };
+
+    range.rev().filter_map(move |key| bucket.bucket_for_peeking(key))
+}
@@ -1,6 +1,9 @@
+#![allow(clippy::bool_assert_comparison)]
+
 use super::*;
-use std::usize::MAX;
 use bit_set::BitSet;
+use super::super::bounds::Bounds;
+use super::super::events::EventLog;
 
 type Subject = Frontier;
 
@@ -8,7 +11,7 @@ const N: usize = 5;
 const F: bool = false;
 
 fn subject() -> Subject {
-    Subject::new(1.0, true, true, N)
+    Subject::new(1.0, "scratch-files", Codec::Zlib(6), N).unwrap()
 }
 
 mod new {
@@ -47,7 +50,7 @@ mod add {
         let mut subject = subject();
 
         let seed = Candidate::seed(N);
-        let candidate = seed.expand(MAX, N).last().unwrap();
+        let candidate = seed.expand(usize::MAX, N).last().unwrap();
 
         let total_waste = candidate.total_waste(N);
         let permutations = candidate.number_of_permutations();
@@ -67,7 +70,7 @@ mod add {
             let mut subject = subject();
             let seed = Candidate::seed(N);
 
-            let candidate = seed.expand(MAX, N).last().unwrap();
+            let candidate = seed.expand(usize::MAX, N).last().unwrap();
 
             let total_waste = candidate.total_waste(N);
             let permutations = candidate.number_of_permutations();
@@ -81,6 +84,734 @@ mod add {
     }
 }
 
+mod drop_duplicates {
+    use super::*;
+
+    fn duplicate(candidate: &Candidate) -> Candidate {
+        Candidate {
+            permutations_seen: candidate.permutations_seen.clone(),
+            tail_of_string: candidate.tail_of_string.clone(),
+            wasted_symbols: candidate.wasted_symbols,
+            ancestry_id: 123.into(),
+        }
+    }
+
+    #[test]
+    fn it_drops_candidates_that_are_identical_to_one_already_added() {
+        let mut subject = subject();
+        subject.drop_duplicates();
+
+        let candidate = Candidate::seed(N);
+
+        subject.add(duplicate(&candidate), N);
+        subject.add(duplicate(&candidate), N);
+        assert_eq!(subject.len(), 1);
+
+        let transpositions = subject.transpositions().unwrap();
+        assert_eq!(transpositions.added, 1);
+        assert_eq!(transpositions.duplicates, 1);
+        assert_eq!(transpositions.len(), 1);
+    }
+
+    #[test]
+    fn it_keeps_candidates_that_differ() {
+        let mut subject = subject();
+        subject.drop_duplicates();
+
+        for c in Candidate::seed(N).expand(usize::MAX, N) {
+            subject.add(c, N);
+        }
+
+        assert_eq!(subject.len(), 4);
+        assert_eq!(subject.transpositions().unwrap().duplicates, 0);
+    }
+
+    #[test]
+    fn it_does_not_drop_anything_unless_enabled() {
+        let mut subject = subject();
+        let candidate = Candidate::seed(N);
+
+        subject.add(duplicate(&candidate), N);
+        subject.add(duplicate(&candidate), N);
+
+        assert_eq!(subject.len(), 2);
+        assert_eq!(subject.transpositions().is_none(), true);
+    }
+
+    // Fingerprints are saved in checkpoints, so they mustn't change between
+    // builds.
+    #[test]
+    fn it_fingerprints_candidates_the_same_way_in_every_build() {
+        let seed = Candidate::seed(N);
+        let child = seed.clone().expand(usize::MAX, N).last().unwrap();
+
+        assert_eq!(Transpositions::fingerprint(&seed), 0xb739d0d161bb485e41e589b166a5dbf1);
+        assert_eq!(Transpositions::fingerprint(&child), 0xe0170c37e5ad6357f1848d0a07aca161);
+    }
+
+    #[test]
+    fn it_forgets_fingerprints_for_waste_that_no_longer_appears_in_the_frontier() {
+        let mut subject = subject();
+        subject.drop_duplicates();
+
+        for c in Candidate::seed(N).expand(usize::MAX, N) {
+            subject.add(c, N);
+        }
+
+        subject.next();
+        assert_eq!(subject.transpositions().unwrap().forgotten, 0);
+
+        subject.disable(&(2, 1));
+
+        subject.next();
+        assert_eq!(subject.transpositions().unwrap().forgotten, 1);
+
+        // Waste 2 is still in the disabled queue so only waste 1 is forgotten.
+        subject.next();
+        assert_eq!(subject.transpositions().unwrap().forgotten, 2);
+        assert_eq!(subject.transpositions().unwrap().len(), 2);
+    }
+}
+
+mod check_dominance {
+    use super::*;
+
+    fn candidate(string: &[u8]) -> Candidate {
+        string[N..].iter().fold(Candidate::seed(N), |c, &s| c.expand_one(s, false, N))
+    }
+
+    // Both reach the tail 1234 but the second has wasted more symbols.
+    fn better_and_worse() -> (Candidate, Candidate) {
+        let better = candidate(&[0, 1, 2, 3, 4, 0, 1, 2, 3, 4]);
+        let worse = candidate(&[0, 1, 2, 3, 4, 1, 2, 3, 4]);
+
+        assert_eq!(better.tail_of_string, worse.tail_of_string);
+        assert_eq!(worse.permutations_seen.is_subset(&better.permutations_seen), true);
+
+        (better, worse)
+    }
+
+    #[test]
+    fn it_drops_candidates_that_are_dominated_by_one_already_added() {
+        let mut subject = subject();
+        subject.check_dominance(16, N);
+
+        let (better, worse) = better_and_worse();
+
+        subject.add(better, N);
+        subject.add(worse, N);
+
+        assert_eq!(subject.len(), 1);
+        assert_eq!(subject.dominance().unwrap().dropped_on_add, 1);
+        assert_eq!(subject.dominance().unwrap().end_phase(), (1, 0));
+        assert_eq!(subject.dominance().unwrap().end_phase(), (0, 0));
+    }
+
+    #[test]
+    fn it_estimates_the_most_memory_it_can_use() {
+        let mut subject = subject();
+        subject.check_dominance(16, N);
+
+        // Tails of 1 to 4 distinct symbols, each with a bitset of 120 bits.
+        let dominance = subject.dominance().unwrap();
+
+        assert_eq!(dominance.tails(), 5 + 20 + 60 + 120);
+        assert_eq!(dominance.max_bytes(), 16 * 205 * (15 + 5 + 48));
+    }
+
+    #[test]
+    fn it_keeps_candidates_that_are_not_dominated() {
+        let mut subject = subject();
+        subject.check_dominance(16, N);
+
+        let (better, worse) = better_and_worse();
+
+        subject.add(worse, N);
+        subject.add(better, N);
+
+        assert_eq!(subject.len(), 2);
+        assert_eq!(subject.dominance().unwrap().dropped_on_add, 0);
+
+        // The better candidate replaces the worse one that it dominates.
+        assert_eq!(subject.dominance().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn it_only_keeps_the_given_number_of_candidates_for_each_tail() {
+        let mut subject = subject();
+        subject.check_dominance(1, N);
+
+        subject.add(candidate(&[0, 1, 2, 3, 4, 0, 1, 2, 3, 4]), N);
+        subject.add(candidate(&[0, 1, 2, 3, 4, 2, 1, 0, 3, 4, 1, 2, 3, 4]), N);
+
+        assert_eq!(subject.dominance().unwrap().len(), 1);
+
+        let (_, worse) = better_and_worse();
+        subject.add(worse, N);
+
+        assert_eq!(subject.len(), 3);
+    }
+
+    #[test]
+    fn it_drops_candidates_loaded_from_disk_that_have_since_been_dominated() {
+        let mut subject = Subject::new(1.0, "/tmp/superpermutation-test/frontier-1", Codec::Raw, N).unwrap();
+        subject.check_dominance(16, N);
+
+        let (better, worse) = better_and_worse();
+        let bucket_id = (worse.total_waste(N), worse.number_of_permutations());
+
+        subject.add(worse, N);
+        subject.disable(&bucket_id);
+
+        let mut bucket = priority::flatten(subject.disabled_queue.bucket(bucket_id.0).replace(bucket_id.1, None).unwrap());
+        subject.disk.write_chunks(&mut bucket, bucket_id.0, bucket_id.1).unwrap();
+
+        subject.add(better, N);
+
+        assert_eq!(subject.enable(&bucket_id), false);
+        assert_eq!(subject.len(), 1);
+        assert_eq!(subject.dominance().unwrap().dropped_on_onload, 1);
+    }
+
+    #[test]
+    fn it_finds_the_same_bounds_for_three_to_five_symbols() {
+        for &(n, max_bounds) in &[(3, 10), (4, 10), (5, 26)] {
+            let scratch_dir = format!("/tmp/superpermutation-test/frontier-{}", n);
+
+            let expected = lower_bounds(Subject::new(1.0, &scratch_dir, Codec::Raw, n).unwrap(), n, max_bounds);
+
+            let mut subject = Subject::new(1.0, &scratch_dir, Codec::Raw, n).unwrap();
+            subject.check_dominance(4, n);
+
+            assert_eq!(lower_bounds(subject, n, max_bounds), expected);
+        }
+    }
+
+    fn lower_bounds(mut subject: Subject, n: usize, max_bounds: usize) -> Vec<usize> {
+        let mut bounds = Bounds::new(n);
+        subject.add(Candidate::seed(n), n);
+
+        while let Some(mut wasted_symbols) = subject.min_waste() {
+            wasted_symbols = subject.unprune(wasted_symbols, &bounds.lower_bounds, &bounds.upper_bounds);
+
+            let candidate = subject.next().unwrap();
+
+            if bounds.update(wasted_symbols, candidate.number_of_permutations()) {
+                let threshold = bounds.thresholds[wasted_symbols];
+                subject.prune(wasted_symbols, threshold, true);
+            }
+
+            if bounds.found_for_superpermutation() || bounds.lower_bounds.len() > max_bounds {
+                break;
+            }
+
+            for child in candidate.expand(bounds.upper(wasted_symbols), n) {
+                subject.add(child, n);
+            }
+        }
+
+        bounds.lower_bounds.truncate(max_bounds);
+        bounds.lower_bounds
+    }
+}
+
+mod storage_error {
+    use super::*;
+    use std::fs::{remove_dir_all, remove_file, rename};
+
+    #[test]
+    fn it_keeps_the_bucket_in_memory_if_it_cannot_be_offloaded() {
+        let path = "/tmp/superpermutation-test/frontier-6";
+        let mut subject = Subject::new(0.000_000_001, path, Codec::Raw, N).unwrap();
+
+        let candidate = Candidate::seed(N);
+        let bucket_id = (candidate.total_waste(N), candidate.number_of_permutations());
+
+        remove_dir_all(path).unwrap();
+
+        subject.disable(&bucket_id);
+        subject.add(candidate, N);
+
+        // The bucket is written in the background and put back when it fails.
+        subject.writer.flush();
+        subject.restore_failures();
+
+        assert_eq!(subject.storage_error().is_some(), true);
+        assert_eq!(subject.disabled_queue.len(), 1);
+
+        assert_eq!(subject.enable(&bucket_id), true);
+        assert_eq!(subject.next().is_some(), true);
+    }
+
+    #[test]
+    fn it_does_not_enable_the_bucket_if_it_cannot_be_onloaded() {
+        let path = "/tmp/superpermutation-test/frontier-7";
+        let mut subject = Subject::new(1.0, path, Codec::Raw, N).unwrap();
+
+        let candidate = Candidate::seed(N);
+        let bucket_id = (candidate.total_waste(N), candidate.number_of_permutations());
+
+        subject.disable(&bucket_id);
+        subject.disk.write_chunks(&mut vec![candidate].into(), bucket_id.0, bucket_id.1).unwrap();
+
+        remove_file(format!("{}.0", subject.disk.basename(bucket_id.0, bucket_id.1))).unwrap();
+
+        assert_eq!(subject.enable(&bucket_id), false);
+        assert_eq!(subject.storage_error().is_some(), true);
+    }
+
+    #[test]
+    fn it_still_has_the_candidates_when_resumed_after_the_problem_is_fixed() {
+        let path = "/tmp/superpermutation-test/frontier-28";
+        let mut subject = Subject::new(1.0, path, Codec::Raw, N).unwrap();
+
+        let candidate = Candidate::seed(N);
+        let bucket_id = (candidate.total_waste(N), candidate.number_of_permutations());
+
+        subject.disable(&bucket_id);
+        subject.disk.write_chunks(&mut vec![candidate.clone()].into(), bucket_id.0, bucket_id.1).unwrap();
+
+        let filename = format!("{}.0", subject.disk.basename(bucket_id.0, bucket_id.1));
+        rename(&filename, format!("{}.moved", filename)).unwrap();
+
+        assert_eq!(subject.enable(&bucket_id), false);
+        assert_eq!(subject.disabled.contains(&bucket_id), true);
+
+        let mut checkpoint = vec![];
+        subject.save(&mut checkpoint).unwrap();
+
+        rename(format!("{}.moved", filename), &filename).unwrap();
+        let mut subject = Subject::resume(1.0, path, Codec::Raw, N, &mut &checkpoint[..]).unwrap();
+
+        assert_eq!(subject.enable(&bucket_id), true);
+        assert_eq!(subject.next(), Some(candidate));
+    }
+}
+
+mod streaming {
+    use super::*;
+
+    fn subject_with_file_on_disk(path: &str, count: usize) -> (Subject, BucketID) {
+        let mut subject = Subject::new(1.0, path, Codec::Raw, N).unwrap();
+
+        let candidate = Candidate::seed(N);
+        let bucket_id = (candidate.total_waste(N), candidate.number_of_permutations());
+
+        let mut bucket = (0..count).map(|_| Candidate::seed(N)).collect();
+
+        subject.disable(&bucket_id);
+        subject.disk.write_chunks(&mut bucket, bucket_id.0, bucket_id.1).unwrap();
+
+        (subject, bucket_id)
+    }
+
+    #[test]
+    fn it_onloads_a_batch_at_a_time_and_refills_the_bucket_when_it_is_empty() {
+        let (mut subject, bucket_id) = subject_with_file_on_disk("/tmp/superpermutation-test/frontier-8", 10_000);
+
+        assert_eq!(subject.enable(&bucket_id), true);
+        assert_eq!(subject.enabled_queue.len(), STREAM_BATCH);
+
+        for _ in 0..STREAM_BATCH {
+            subject.next().unwrap();
+        }
+
+        assert_eq!(subject.enabled_queue.len(), STREAM_BATCH);
+
+        let mut remaining = 0;
+        while subject.next().is_some() { remaining += 1; }
+
+        assert_eq!(remaining, 10_000 - STREAM_BATCH);
+        assert_eq!(subject.streams.is_empty(), true);
+        assert_eq!(subject.disk.min_waste(), None);
+    }
+
+    #[test]
+    fn it_continues_streaming_from_the_same_place_when_resumed_from_a_checkpoint() {
+        let path = "/tmp/superpermutation-test/frontier-9";
+        let (mut subject, bucket_id) = subject_with_file_on_disk(path, 10_000);
+
+        subject.enable(&bucket_id);
+
+        for _ in 0..5000 {
+            subject.next().unwrap();
+        }
+
+        let mut checkpoint = vec![];
+        subject.save(&mut checkpoint).unwrap();
+
+        let mut subject = Subject::resume(1.0, path, Codec::Raw, N, &mut &checkpoint[..]).unwrap();
+
+        let mut remaining = 0;
+        while subject.next().is_some() { remaining += 1; }
+
+        assert_eq!(remaining, 5000);
+    }
+}
+
+mod stats {
+    use super::*;
+
+    #[test]
+    fn it_counts_the_candidates_in_each_queue_and_on_disk() {
+        let mut subject = Subject::new(1.0, "/tmp/superpermutation-test/frontier-10", Codec::Raw, N).unwrap();
+
+        for candidate in Candidate::seed(N).expand(usize::MAX, N) {
+            subject.add(candidate, N);
+        }
+
+        let candidate = Candidate::seed(N);
+        let bucket_id = (candidate.total_waste(N), candidate.number_of_permutations());
+
+        subject.disable(&bucket_id);
+        subject.disk.write_chunks(&mut (0..10).map(|_| Candidate::seed(N)).collect(), bucket_id.0, bucket_id.1).unwrap();
+        subject.next();
+
+        let stats = subject.stats();
+
+        assert_eq!((stats.enabled, stats.disabled, stats.on_disk, stats.expanded), (3, 0, 10, 1));
+        assert_eq!(stats.bytes_written > 0, true);
+
+        subject.enable(&bucket_id);
+
+        assert_eq!(subject.stats().enabled, 13);
+        assert_eq!(subject.stats().on_disk, 0);
+        assert_eq!(subject.stats().bytes_read, stats.bytes_written);
+    }
+
+    #[test]
+    fn it_keeps_the_number_expanded_when_resumed_from_a_checkpoint() {
+        let path = "/tmp/superpermutation-test/frontier-32";
+        let mut subject = Subject::new(1.0, path, Codec::Raw, N).unwrap();
+
+        for candidate in Candidate::seed(N).expand(usize::MAX, N) {
+            subject.add(candidate, N);
+        }
+
+        subject.next();
+        subject.next();
+
+        let mut checkpoint = vec![];
+        subject.save(&mut checkpoint).unwrap();
+
+        let subject = Subject::resume(1.0, path, Codec::Raw, N, &mut &checkpoint[..]).unwrap();
+
+        assert_eq!(subject.stats().expanded, 2);
+    }
+}
+
+mod accounting {
+    use super::*;
+    use super::super::memory::Memory;
+
+    #[test]
+    fn it_estimates_the_memory_used_by_each_structure() {
+        let mut subject = Subject::new(1.0, "/tmp/superpermutation-test/frontier-16", Codec::Raw, N).unwrap();
+
+        for candidate in Candidate::seed(N).expand(usize::MAX, N) {
+            subject.add(candidate, N);
+        }
+
+        let accounting = subject.accounting();
+
+        assert_eq!(accounting.candidates, 4 * Memory::per_candidate(N));
+        assert_eq!(accounting.spare_capacity > 0, true);
+        assert_eq!(accounting.queues > 0, true);
+        assert_eq!(accounting.disabled, 0);
+        assert_eq!(accounting.transpositions, 0);
+
+        subject.disable(&(100, 0));
+        assert_eq!(subject.accounting().disabled > 0, true);
+    }
+
+    #[test]
+    fn it_includes_the_fingerprints_of_the_candidates_that_were_added() {
+        let mut subject = Subject::new(1.0, "/tmp/superpermutation-test/frontier-33", Codec::Raw, N).unwrap();
+        subject.drop_duplicates();
+
+        for candidate in Candidate::seed(N).expand(usize::MAX, N) {
+            subject.add(candidate, N);
+        }
+
+        let accounting = subject.accounting();
+        let fingerprints = subject.transpositions().unwrap().len() as u64;
+
+        assert_eq!(accounting.transpositions >= fingerprints * 17, true);
+    }
+
+    #[test]
+    fn it_reports_the_estimate_and_the_measured_memory_in_the_stats() {
+        let subject = Subject::new(1.0, "/tmp/superpermutation-test/frontier-17", Codec::Raw, N).unwrap();
+        let stats = subject.stats();
+
+        assert_eq!(stats.estimated_bytes >= subject.accounting().total(), true);
+        assert_eq!(stats.resident_bytes.is_some(), true);
+    }
+
+    #[test]
+    fn it_includes_the_heap_allocations_of_each_candidate() {
+        let inline = std::mem::size_of::<Candidate>() as u64;
+
+        // 120 permutations fit in 4 blocks of the bitset and the tail has 4
+        // symbols, which are each rounded up to the smallest allocation.
+        assert_eq!(Memory::per_candidate(N), inline + 32 + 32);
+        assert_eq!(super::super::memory::allocation(0), 0);
+        assert_eq!(super::super::memory::allocation(100), 112);
+    }
+
+    #[test]
+    fn it_is_over_the_limit_if_the_process_uses_more_memory_than_it() {
+        assert_eq!(Memory::new(0.000_000_001, N).over_limit(0), true);
+        assert_eq!(Memory::new(1.0, N).over_limit(1000), false);
+    }
+
+    #[test]
+    fn it_waits_longer_after_each_offload_that_leaves_it_over_the_limit() {
+        let measure_every = super::super::memory::MEASURE_EVERY;
+        let mut memory = Memory::new(0.000_000_001, N);
+        let mut offloads = vec![];
+
+        for add in 1..=measure_every * 16 {
+            if memory.over_limit(0) {
+                memory.offloaded();
+                offloads.push(add / measure_every);
+            }
+        }
+
+        assert_eq!(offloads, vec![0, 1, 3, 7, 15]);
+    }
+}
+
+mod spill {
+    use super::*;
+
+    const TINY: f64 = 0.000_000_001;
+
+    fn candidates(count: usize) -> Vec<Candidate> {
+        let mut candidates = vec![];
+        let mut queue = VecDeque::from(vec![Candidate::seed(N)]);
+
+        while candidates.len() < count {
+            for child in queue.pop_front().unwrap().expand(usize::MAX, N) {
+                queue.push_back(child.clone());
+                candidates.push(child);
+            }
+        }
+
+        candidates
+    }
+
+    fn bucket_ids(subject: &mut Subject) -> Vec<BucketID> {
+        subject.map(|c| (c.total_waste(N), c.number_of_permutations())).collect()
+    }
+
+    #[test]
+    fn it_spills_enabled_buckets_if_nothing_is_disabled_and_keeps_the_best_one() {
+        let mut subject = Subject::new(TINY, "/tmp/superpermutation-test/frontier-18", Codec::Raw, N).unwrap();
+
+        for candidate in candidates(100) {
+            subject.add(candidate, N);
+        }
+
+        let best = Subject::spilled_key(&subject.best_bucket().unwrap());
+
+        assert_eq!(subject.stats().on_disk > 0, true);
+        assert_eq!(subject.spilled.is_empty(), false);
+        assert_eq!(subject.spilled.iter().all(|&bucket| bucket > best), true);
+        assert_eq!(subject.max_waste(), subject.spilled.last().map(|&(w, _)| w).max(subject.enabled_queue.max_priority()));
+    }
+
+    #[test]
+    fn it_onloads_spilled_buckets_in_the_same_order_as_if_they_had_stayed_in_memory() {
+        let mut expected = Subject::new(1.0, "/tmp/superpermutation-test/frontier-19", Codec::Raw, N).unwrap();
+        let mut subject = Subject::new(TINY, "/tmp/superpermutation-test/frontier-20", Codec::Raw, N).unwrap();
+
+        for candidate in candidates(100) {
+            expected.add(candidate.clone(), N);
+            subject.add(candidate, N);
+        }
+
+        assert_eq!(subject.min_waste(), expected.min_waste());
+        assert_eq!(bucket_ids(&mut subject), bucket_ids(&mut expected));
+
+        assert_eq!(subject.spilled.is_empty(), true);
+        assert_eq!(subject.stats().on_disk, 0);
+    }
+
+    #[test]
+    fn it_streams_the_rest_of_a_bucket_that_is_spilled_while_it_is_being_enabled() {
+        let mut subject = Subject::new(1.0, "/tmp/superpermutation-test/frontier-27", Codec::Raw, N).unwrap();
+
+        let seed = Candidate::seed(N);
+        let wasteful = seed.clone().expand(usize::MAX, N).find(|c| c.wasted_symbols > 0).unwrap();
+        let bucket_id = (wasteful.total_waste(N), wasteful.number_of_permutations());
+
+        subject.disable(&bucket_id);
+
+        for _ in 0..10_002 {
+            subject.add(wasteful.clone(), N);
+        }
+
+        subject.offload(0);
+        assert_eq!(subject.enable(&bucket_id), true);
+
+        // The seed is the best bucket, so the one being streamed is spilled.
+        subject.add(seed, N);
+        subject.offload(usize::MAX);
+        subject.writer.flush();
+
+        let mut taken = 0;
+        while subject.next().is_some() { taken += 1; }
+
+        assert_eq!(taken, 10_003);
+        assert_eq!(subject.stats().on_disk, 0);
+    }
+
+    #[test]
+    fn it_finds_the_spilled_buckets_again_when_resumed_from_a_checkpoint() {
+        let path = "/tmp/superpermutation-test/frontier-21";
+        let mut subject = Subject::new(TINY, path, Codec::Raw, N).unwrap();
+        let candidates = candidates(100);
+        let count = candidates.len();
+
+        for candidate in candidates {
+            subject.add(candidate, N);
+        }
+
+        let spilled = subject.spilled.clone();
+        let mut checkpoint = vec![];
+        subject.save(&mut checkpoint).unwrap();
+
+        let mut subject = Subject::resume(1.0, path, Codec::Raw, N, &mut &checkpoint[..]).unwrap();
+        assert_eq!(subject.spilled, spilled);
+
+        let mut remaining = 0;
+        while subject.next().is_some() { remaining += 1; }
+
+        assert_eq!(remaining, count);
+    }
+}
+
+mod writer {
+    use super::*;
+
+    #[test]
+    fn it_writes_offloaded_buckets_in_the_background() {
+        let mut subject = Subject::new(0.000_000_001, "/tmp/superpermutation-test/frontier-24", Codec::Raw, N).unwrap();
+
+        let candidate = Candidate::seed(N);
+        let bucket_id = (candidate.total_waste(N), candidate.number_of_permutations());
+
+        subject.disable(&bucket_id);
+        subject.add(candidate, N);
+
+        assert_eq!(subject.disabled_queue.len(), 0);
+        assert_eq!(subject.has_disabled(), true);
+
+        subject.writer.flush();
+
+        assert_eq!(subject.disk.has_files(bucket_id.0, bucket_id.1), true);
+        assert_eq!(subject.stats().on_disk, 1);
+    }
+
+    #[test]
+    fn it_waits_for_a_bucket_to_be_written_before_reading_it() {
+        let mut subject = Subject::new(1.0, "/tmp/superpermutation-test/frontier-25", Codec::Raw, N).unwrap();
+
+        let candidate = Candidate::seed(N);
+        let bucket_id = (candidate.total_waste(N), candidate.number_of_permutations());
+
+        subject.disable(&bucket_id);
+        subject.writer.submit(vec![(vec![candidate.clone()].into(), bucket_id.0, bucket_id.1)], 0);
+
+        assert_eq!(subject.enable(&bucket_id), true);
+        assert_eq!(subject.next(), Some(candidate));
+    }
+
+    #[test]
+    fn it_finishes_writing_before_saving_a_checkpoint() {
+        let path = "/tmp/superpermutation-test/frontier-26";
+        let mut subject = Subject::new(1.0, path, Codec::Raw, N).unwrap();
+
+        let candidate = Candidate::seed(N);
+        let bucket_id = (candidate.total_waste(N), candidate.number_of_permutations());
+
+        subject.disable(&bucket_id);
+        subject.writer.submit(vec![(vec![candidate.clone()].into(), bucket_id.0, bucket_id.1)], 0);
+
+        let mut checkpoint = vec![];
+        subject.save(&mut checkpoint).unwrap();
+
+        let mut subject = Subject::resume(1.0, path, Codec::Raw, N, &mut &checkpoint[..]).unwrap();
+
+        assert_eq!(subject.enable(&bucket_id), true);
+        assert_eq!(subject.next(), Some(candidate));
+    }
+}
+
+mod has_disabled {
+    use super::*;
+
+    #[test]
+    fn it_only_counts_files_for_disabled_buckets() {
+        let mut subject = Subject::new(1.0, "/tmp/superpermutation-test/frontier-29", Codec::Raw, N).unwrap();
+
+        let candidate = Candidate::seed(N);
+        let bucket_id = (candidate.total_waste(N), candidate.number_of_permutations());
+
+        subject.disk.write_chunks(&mut vec![candidate].into(), bucket_id.0, bucket_id.1).unwrap();
+        assert_eq!(subject.has_disabled(), false);
+
+        subject.disable(&bucket_id);
+        assert_eq!(subject.has_disabled(), true);
+    }
+
+    #[test]
+    fn it_does_not_trust_the_count_of_candidates_on_disk() {
+        let mut subject = Subject::new(1.0, "/tmp/superpermutation-test/frontier-30", Codec::Raw, N).unwrap();
+
+        let candidate = Candidate::seed(N);
+        let bucket_id = (candidate.total_waste(N), candidate.number_of_permutations());
+
+        subject.disable(&bucket_id);
+        subject.disk.write_chunks(&mut vec![candidate].into(), bucket_id.0, bucket_id.1).unwrap();
+        subject.disk.consume(bucket_id.0, bucket_id.1).unwrap();
+
+        assert_eq!(subject.stats().on_disk, 1);
+        assert_eq!(subject.has_disabled(), false);
+    }
+}
+
+mod log_to {
+    use super::*;
+    use std::fs::read_to_string;
+
+    #[test]
+    fn it_logs_the_buckets_that_are_pruned_and_enabled() {
+        let path = "/tmp/superpermutation-test/frontier-11";
+        let log_path = format!("{}.jsonl", path);
+        let mut subject = Subject::new(1.0, path, Codec::Raw, N).unwrap();
+
+        subject.log_to(EventLog::open(Some(&log_path), None, false).unwrap().unwrap());
+
+        let candidate = Candidate::seed(N);
+        let bucket_id = (candidate.total_waste(N), candidate.number_of_permutations());
+        subject.add(candidate, N);
+
+        subject.prune(bucket_id.0, bucket_id.1 + 1, false);
+        subject.enable(&bucket_id);
+
+        let contents = read_to_string(&log_path).unwrap();
+        let lines = contents.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].contains(&format!("\"event\": \"prune\", \"wasted_symbols\": {}", bucket_id.0)), true);
+        assert_eq!(lines[0].contains(&format!("\"buckets\": [[{}, {}]], \"candidates\": 1", bucket_id.0, bucket_id.1)), true);
+        assert_eq!(lines[1].contains("\"event\": \"enable\""), true);
+        assert_eq!(lines[1].contains("\"from\": \"memory\", \"candidates\": 1"), true);
+    }
+}
+
 mod prune {
     use super::*;
 
@@ -89,7 +820,7 @@ mod prune {
         let mut subject = subject();
         let candidate = Candidate::seed(N);
 
-        for c in candidate.expand(MAX, N) {
+        for c in candidate.expand(usize::MAX, N) {
             subject.add(c, N);
         }
 
@@ -128,7 +859,7 @@ mod prune {
             let mut subject = subject();
             let candidate = Candidate::seed(N);
 
-            for c in candidate.expand(MAX, N) {
+            for c in candidate.expand(usize::MAX, N) {
                 subject.add(c, N);
             }
 
@@ -249,6 +980,83 @@ mod unprune {
         assert_eq!(subject.unprune(4, &lower_bounds, &upper_bounds), 4);
     }
 
+    #[test]
+    fn it_prefetches_the_bucket_it_would_unprune_next_if_it_is_on_disk() {
+        let mut subject = Subject::new(1.0, "/tmp/superpermutation-test/frontier-22", Codec::Raw, N).unwrap();
+
+        add_pruned_candidate(&mut subject, 1, 5);
+        add_pruned_candidate(&mut subject, 2, 9);
+
+        let jobs = Subject::take_disabled(&mut subject.disabled_queue);
+        subject.writer.submit(jobs, 0);
+        subject.writer.flush();
+
+        let usage = subject.disk.usage();
+        let index = subject.disk.index();
+
+        let lower_bounds = vec![4, 8, 12, 14];
+        let upper_bounds = vec![4, 8, 12, 16];
+
+        subject.prefetch(&lower_bounds, &upper_bounds);
+        assert_eq!(prefetching(&subject), Some((2, 9)));
+
+        // A batch that's dropped leaves the files as they were.
+        let (_, batch, error) = subject.prefetch.take().unwrap().finish().unwrap();
+        assert_eq!((batch.len(), error.is_none()), (1, true));
+        drop(batch);
+
+        assert_eq!(subject.disk.usage(), usage);
+        assert_eq!(subject.disk.index(), index);
+
+        // With a better lower bound, only the bucket with less waste can
+        // improve it, and then the prefetch is replaced when it's worse again.
+        subject.prefetch(&[4, 8, 12, 17], &[4, 8, 14, 20]);
+        assert_eq!(prefetching(&subject), Some((1, 5)));
+
+        subject.prefetch(&lower_bounds, &upper_bounds);
+        assert_eq!(prefetching(&subject), Some((2, 9)));
+
+        assert_eq!(subject.disk.usage(), usage);
+        assert_eq!(subject.disk.index(), index);
+        assert_eq!(subject.stats().on_disk, 2);
+
+        subject.unprune(4, &lower_bounds, &upper_bounds);
+        assert_eq!(prefetching(&subject), None);
+        assert_eq!(last_unpruned(&mut subject), (2, 9));
+
+        let stats = subject.stats();
+        assert_eq!((stats.on_disk, stats.bytes_read > 0), (1, true));
+        assert_eq!(subject.disk.has_files(2, 9), false);
+        assert_eq!(subject.disk.has_files(1, 5), true);
+
+        subject.prefetch(&lower_bounds, &upper_bounds);
+        assert_eq!(prefetching(&subject), Some((1, 5)));
+
+        subject.unprune(4, &lower_bounds, &upper_bounds);
+        assert_eq!(last_unpruned(&mut subject), (1, 5));
+        assert_eq!(subject.stats().on_disk, 0);
+        assert_eq!(subject.stats().bytes_read, usage.bytes_written);
+    }
+
+    #[test]
+    fn it_does_not_prefetch_if_the_next_bucket_is_in_memory() {
+        let mut subject = Subject::new(1.0, "/tmp/superpermutation-test/frontier-23", Codec::Raw, N).unwrap();
+
+        add_pruned_candidate(&mut subject, 1, 5);
+
+        let jobs = Subject::take_disabled(&mut subject.disabled_queue);
+        subject.writer.submit(jobs, 0);
+
+        add_pruned_candidate(&mut subject, 2, 9);
+
+        subject.prefetch(&[4, 8, 12, 14], &[4, 8, 12, 16]);
+        assert_eq!(prefetching(&subject), None);
+    }
+
+    fn prefetching(subject: &Subject) -> Option<BucketID> {
+        subject.prefetch.as_ref().map(|p| *p.bucket_id())
+    }
+
     fn add_pruned_candidate(frontier: &mut Frontier, wasted_symbols: u16, permutations: usize) {
         let mut permutations_seen = BitSet::new();
 
@@ -259,7 +1067,8 @@ mod unprune {
         let candidate = Candidate {
             permutations_seen,
             tail_of_string: vec![0, 1, 2, 3],
-            wasted_symbols
+            wasted_symbols,
+            ancestry_id: 0.into(),
         };
 
         frontier.add(candidate, N);
@@ -282,7 +1091,7 @@ mod next {
         let mut subject = subject();
         let candidate = Candidate::seed(N);
 
-        for c in candidate.expand(MAX, N) {
+        for c in candidate.expand(usize::MAX, N) {
             subject.add(c, N);
         }
 
@@ -310,7 +1119,7 @@ mod next {
         let mut subject = subject();
         let candidate = Candidate::seed(N);
 
-        for c in candidate.expand(MAX, N) {
+        for c in candidate.expand(usize::MAX, N) {
             subject.add(c, N);
         }
 
@@ -331,6 +1140,142 @@ mod next {
     }
 }
 
+mod prioritize {
+    use super::*;
+    use super::super::super::search::{Search, Step};
+
+    fn candidate(tail_of_string: Vec<u8>, wasted_symbols: u16, ancestry_id: u64) -> Candidate {
+        let mut permutations_seen = BitSet::new();
+        permutations_seen.insert(0);
+
+        Candidate { permutations_seen, tail_of_string, wasted_symbols, ancestry_id: ancestry_id.into() }
+    }
+
+    #[test]
+    fn it_breaks_ties_within_a_bucket() {
+        for &(priority, expected) in &[(Priority::Fifo, 1), (Priority::LongestTail, 2)] {
+            let mut subject = subject();
+            subject.prioritize(priority, N);
+
+            // Both have a total waste of 2 and one permutation.
+            subject.add(candidate(vec![1, 2, 3], 1, 1), N);
+            subject.add(candidate(vec![1, 2, 3, 4], 2, 2), N);
+
+            assert_eq!(u64::from(subject.next().unwrap().ancestry_id), expected);
+            assert_eq!(subject.len(), 1);
+        }
+    }
+
+    #[test]
+    fn it_keys_the_candidates_that_were_added_before_it_was_set() {
+        let mut subject = subject();
+
+        subject.add(candidate(vec![1, 2, 3], 1, 1), N);
+        subject.add(candidate(vec![1, 2, 3, 4], 2, 2), N);
+        subject.add(candidate(vec![2, 3, 4, 1], 2, 3), N);
+
+        subject.prioritize(Priority::LongestTail, N);
+
+        let ancestry_ids = subject.map(|c| u64::from(c.ancestry_id)).collect::<Vec<_>>();
+        assert_eq!(ancestry_ids, vec![2, 3, 1]);
+    }
+
+    #[test]
+    fn it_takes_them_in_the_same_order_after_they_have_been_on_disk() {
+        let mut subject = Subject::new(1.0, "/tmp/superpermutation-test/frontier-31", Codec::Raw, N).unwrap();
+        subject.prioritize(Priority::LongestTail, N);
+
+        subject.add(candidate(vec![1, 2, 3], 1, 1), N);
+        subject.add(candidate(vec![1, 2, 3, 4], 2, 2), N);
+        subject.add(candidate(vec![2, 3], 0, 3), N);
+        subject.add(candidate(vec![2, 3, 4, 1], 2, 4), N);
+
+        let bucket_id = (2, 1);
+        subject.disable(&bucket_id);
+        subject.offload(usize::MAX);
+        subject.writer.flush();
+
+        assert_eq!(subject.len(), 0);
+        assert_eq!(subject.enable(&bucket_id), true);
+
+        let ancestry_ids = subject.map(|c| u64::from(c.ancestry_id)).collect::<Vec<_>>();
+        assert_eq!(ancestry_ids, vec![2, 4, 1, 3]);
+    }
+
+    #[test]
+    fn it_still_takes_the_bucket_with_the_least_waste_then_most_permutations() {
+        let mut subject = subject();
+        subject.prioritize(Priority::Random(7), N);
+
+        for c in Candidate::seed(N).expand(usize::MAX, N) {
+            subject.add(c, N);
+        }
+
+        let buckets = subject.by_ref().map(|c| (c.total_waste(N), c.number_of_permutations())).collect::<Vec<_>>();
+        assert_eq!(buckets, vec![(0, 2), (1, 1), (2, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn it_finds_the_same_bounds_with_each_priority() {
+        let priorities = [Priority::LongestTail, Priority::Reachable, Priority::Random(0), Priority::Random(42)];
+
+        for &(n, max_bounds) in &[(4, 7), (5, 20)] {
+            let scratch_dir = format!("/tmp/superpermutation-test/frontier-{}", 10 + n);
+            let expected = lower_bounds(Subject::new(1.0, &scratch_dir, Codec::Raw, n).unwrap(), n, max_bounds);
+
+            for &priority in &priorities {
+                let mut subject = Subject::new(1.0, &scratch_dir, Codec::Raw, n).unwrap();
+                subject.prioritize(priority, n);
+
+                assert_eq!(lower_bounds(subject, n, max_bounds), expected, "{:?}", priority);
+            }
+        }
+    }
+
+    fn lower_bounds(subject: Subject, n: usize, max_bounds: usize) -> Vec<usize> {
+        let mut search = Search::new(subject, Bounds::new(n), n);
+        let step = search.run_until(|s| s.bounds().lower_bounds.len() > max_bounds);
+
+        assert_eq!(step == Step::Running || matches!(step, Step::Finished(_)), true);
+        search.bounds().lower_bounds[..max_bounds].to_vec()
+    }
+}
+
+mod next_batch {
+    use super::*;
+
+    #[test]
+    fn it_only_takes_candidates_from_the_best_bucket() {
+        let mut subject = subject();
+
+        subject.add(Candidate::seed(N), N);
+        subject.add(Candidate::seed(N), N);
+
+        for c in Candidate::seed(N).expand(usize::MAX, N) {
+            subject.add(c, N);
+        }
+
+        let batch = subject.next_batch(10);
+        assert_eq!(batch.iter().map(|c| (c.total_waste(N), c.number_of_permutations())).collect::<Vec<_>>(), vec![(0, 2)]);
+
+        let batch = subject.next_batch(10);
+        assert_eq!(batch.iter().map(|c| (c.total_waste(N), c.number_of_permutations())).collect::<Vec<_>>(), vec![(0, 1), (0, 1)]);
+    }
+
+    #[test]
+    fn it_takes_at_most_the_size_of_the_batch() {
+        let mut subject = subject();
+
+        for _ in 0..5 {
+            subject.add(Candidate::seed(N), N);
+        }
+
+        assert_eq!(subject.next_batch(3).len(), 3);
+        assert_eq!(subject.next_batch(3).len(), 2);
+        assert_eq!(subject.next_batch(3).len(), 0);
+    }
+}
+
 mod min_waste {
     use super::*;
 
@@ -339,7 +1284,7 @@ mod min_waste {
         let mut subject = subject();
         let candidate = Candidate::seed(N);
 
-        for c in candidate.expand(MAX, N) {
+        for c in candidate.expand(usize::MAX, N) {
             subject.add(c, N);
         }
 
@@ -361,7 +1306,7 @@ mod max_waste {
         let mut subject = subject();
         let candidate = Candidate::seed(N);
 
-        for c in candidate.expand(MAX, N) {
+        for c in candidate.expand(usize::MAX, N) {
             subject.add(c, N);
         }
 
@@ -389,7 +1334,7 @@ mod enable_and_disable {
         let mut subject = subject();
 
         let seed = Candidate::seed(N);
-        let candidate = seed.expand(MAX, N).last().unwrap();
+        let candidate = seed.expand(usize::MAX, N).last().unwrap();
 
         let total_waste = candidate.total_waste(N);
         let permutations = candidate.number_of_permutations();
@@ -417,7 +1362,7 @@ mod enable_and_disable {
         let mut subject = subject();
 
         let seed = Candidate::seed(N);
-        let candidate = seed.expand(MAX, N).last().unwrap();
+        let candidate = seed.expand(usize::MAX, N).last().unwrap();
 
         let total_waste = candidate.total_waste(N);
         let permutations = candidate.number_of_permutations();
@@ -0,0 +1,106 @@
+use super::BucketID;
+use super::super::candidate::Candidate;
+
+use siphasher::sip::SipHasher13;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hasher;
+use std::mem::size_of;
+
+// Different paths can reach exactly the same candidate, so a fingerprint of
+// each candidate is kept for its bucket and candidates that match one are
+// dropped. Children never have less total waste than their parent, so once no
+// candidate in the frontier has a given waste, nothing can be added with that
+// waste again and its fingerprints can be forgotten.
+//
+// Fingerprints are saved in checkpoints, so they're hashed with a fixed
+// algorithm and byte order rather than the standard library's default hasher,
+// which may change between Rust releases.
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Transpositions {
+    fingerprints: HashMap<BucketID, HashSet<u128>>,
+    min_waste: usize,
+    #[serde(skip)]
+    checked_waste: Option<usize>,
+    pub added: u64,
+    pub duplicates: u64,
+    pub forgotten: u64,
+}
+
+impl Transpositions {
+    pub fn insert(&mut self, bucket_id: BucketID, candidate: &Candidate) -> bool {
+        let bucket = self.fingerprints.entry(bucket_id).or_default();
+
+        if bucket.insert(Self::fingerprint(candidate)) {
+            self.added += 1;
+            true
+        } else {
+            self.duplicates += 1;
+            false
+        }
+    }
+
+    /// Working out the lowest waste in the frontier means looking through the
+    /// disk index, so this is only done when the next candidate's waste changes.
+    pub fn should_forget(&mut self, next_waste: usize) -> bool {
+        let changed = self.checked_waste != Some(next_waste);
+        self.checked_waste = Some(next_waste);
+
+        changed && next_waste > self.min_waste
+    }
+
+    pub fn forget_below(&mut self, wasted_symbols: usize) {
+        if wasted_symbols <= self.min_waste {
+            return;
+        }
+
+        let mut forgotten = 0;
+
+        self.fingerprints.retain(|&(w, _), bucket| {
+            let keep = w >= wasted_symbols;
+            if !keep { forgotten += bucket.len() as u64; }
+            keep
+        });
+
+        self.forgotten += forgotten;
+        self.min_waste = wasted_symbols;
+    }
+
+    pub fn len(&self) -> usize {
+        self.fingerprints.values().map(|bucket| bucket.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fingerprints.values().all(|bucket| bucket.is_empty())
+    }
+
+    /// Estimates the memory used by the fingerprints from the capacities of
+    /// their tables, each of which has a control byte per slot.
+    pub fn bytes(&self) -> u64 {
+        let buckets = self.fingerprints.capacity() * (size_of::<(BucketID, HashSet<u128>)>() + 1);
+        let fingerprints = self.fingerprints.values().map(|bucket| bucket.capacity() * (size_of::<u128>() + 1)).sum::<usize>();
+
+        (buckets + fingerprints) as u64
+    }
+
+    // Two 64-bit hashes are combined so that the chance of a collision (which
+    // would wrongly drop a candidate) is negligible, even for billions of them.
+    pub(super) fn fingerprint(candidate: &Candidate) -> u128 {
+        let mut hashes = [SipHasher13::new(), SipHasher13::new()];
+        hashes[1].write(&[1]);
+
+        for hasher in hashes.iter_mut() {
+            let tail = &candidate.tail_of_string;
+
+            hasher.write(&(tail.len() as u64).to_le_bytes());
+            hasher.write(tail);
+            hasher.write(&candidate.wasted_symbols.to_le_bytes());
+
+            for block in candidate.permutations_seen.get_ref().blocks() {
+                hasher.write(&block.to_le_bytes());
+            }
+        }
+
+        ((hashes[0].finish() as u128) << 64) | hashes[1].finish() as u128
+    }
+}
@@ -0,0 +1,188 @@
+use super::{BucketID, Frontier};
+use super::super::candidate::Candidate;
+use super::super::disk::{self, Disk};
+use super::super::events::{Event, EventLog};
+
+use rayon::prelude::*;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+// Buckets that are offloaded are written to disk on a background thread so that
+// the search can carry on expanding candidates in the meantime. Only one offload
+// is written at a time. If memory runs low again before it has been written, the
+// search waits for it, so that memory doesn't keep growing when the disk can't
+// keep up. A bucket can't be read while it's being written, so reading one
+// waits for that as well.
+
+/// A bucket to write, by its total waste and number of permutations.
+pub(super) type Job = (VecDeque<Candidate>, usize, usize);
+
+pub(super) struct Writer {
+    sender: Option<Sender<Offload>>,
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct Offload {
+    jobs: Vec<Job>,
+    spilled: usize,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    written: Condvar,
+    log: Mutex<Option<EventLog>>,
+}
+
+#[derive(Default)]
+struct State {
+    /// How many jobs are being written for each bucket.
+    pending: HashMap<BucketID, usize>,
+    /// How many candidates haven't been written yet. Each chunk is taken off
+    /// as soon as it's counted as on disk, so it isn't counted twice.
+    candidates: usize,
+    /// Buckets that couldn't be written, to be put back in memory.
+    failed: Vec<(Job, disk::Error)>,
+}
+
+impl Writer {
+    pub fn new(disk: Arc<Disk>) -> Self {
+        let (sender, receiver) = channel::<Offload>();
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            written: Condvar::new(),
+            log: Mutex::new(None),
+        });
+
+        let writing = shared.clone();
+        let thread = thread::spawn(move || {
+            for offload in receiver {
+                writing.write(&disk, offload);
+            }
+        });
+
+        Self { sender: Some(sender), shared, thread: Some(thread) }
+    }
+
+    pub fn log_to(&self, log: EventLog) {
+        *self.shared.log.lock().unwrap() = Some(log);
+    }
+
+    /// Starts writing the buckets once the previous offload has been written.
+    /// `spilled` is how many of the candidates are from enabled buckets.
+    pub fn submit(&self, jobs: Vec<Job>, spilled: usize) {
+        let mut state = self.shared.wait_until(|s| s.pending.is_empty());
+
+        for (bucket, w, p) in &jobs {
+            *state.pending.entry((*w, *p)).or_insert(0) += 1;
+            state.candidates += bucket.len();
+        }
+
+        drop(state);
+        self.sender.as_ref().unwrap().send(Offload { jobs, spilled }).expect("The writer thread has stopped.");
+    }
+
+    /// Waits until the bucket isn't being written.
+    pub fn wait_for(&self, bucket_id: &BucketID) {
+        drop(self.shared.wait_until(|s| !s.pending.contains_key(bucket_id)));
+    }
+
+    /// Waits until every bucket has been written.
+    pub fn flush(&self) {
+        drop(self.shared.wait_until(|s| s.pending.is_empty()));
+    }
+
+    /// How many candidates are waiting to be written.
+    pub fn candidates(&self) -> usize {
+        self.shared.state.lock().unwrap().candidates
+    }
+
+    pub fn is_writing(&self, bucket_id: &BucketID) -> bool {
+        self.shared.state.lock().unwrap().pending.contains_key(bucket_id)
+    }
+
+    pub fn min_waste(&self) -> Option<usize> {
+        self.shared.state.lock().unwrap().pending.keys().map(|&(w, _)| w).min()
+    }
+
+    /// Takes the buckets that couldn't be written.
+    pub fn take_failures(&self) -> Vec<(Job, disk::Error)> {
+        std::mem::take(&mut self.shared.state.lock().unwrap().failed)
+    }
+
+    /// Calls `f` with each bucket that couldn't be written and hasn't been
+    /// taken yet, e.g. to save them in a checkpoint.
+    pub fn for_each_failure<F: FnMut(&Job) -> bincode::Result<()>>(&self, mut f: F) -> bincode::Result<()> {
+        self.shared.state.lock().unwrap().failed.iter().try_for_each(|(job, _)| f(job))
+    }
+}
+
+impl Shared {
+    fn wait_until<F: Fn(&State) -> bool>(&self, ready: F) -> std::sync::MutexGuard<'_, State> {
+        let state = self.state.lock().unwrap();
+        self.written.wait_while(state, |s| !ready(s)).unwrap()
+    }
+
+    fn write(&self, disk: &Disk, offload: Offload) {
+        let started = Instant::now();
+        let bytes_written = disk.usage().bytes_written;
+
+        let ids = offload.jobs.iter().map(|(_, w, p)| (*w, *p)).collect::<Vec<_>>();
+        let buckets = offload.jobs.len();
+        let candidates = offload.jobs.iter().map(|(bucket, _, _)| bucket.len()).sum();
+        let log = self.log.lock().unwrap().clone();
+
+        let failures: Vec<_> = offload.jobs.into_par_iter().filter_map(|(mut bucket, w, p)| {
+            let written = |len| self.state.lock().unwrap().candidates -= len;
+
+            match Frontier::retry(log.as_ref(), || disk.write_chunks_with(&mut bucket, w, p, written)) {
+                Ok(()) => None,
+                Err(error) => Some(((bucket, w, p), error)),
+            }
+        }).collect();
+
+        if let Some(log) = log {
+            log.emit(Event::Offload {
+                buckets,
+                candidates,
+                spilled: offload.spilled,
+                bytes: disk.usage().bytes_written - bytes_written,
+                seconds: started.elapsed().as_secs_f64(),
+                failed: failures.len(),
+            });
+        }
+
+        let mut state = self.state.lock().unwrap();
+
+        for bucket_id in ids {
+            let jobs = state.pending.get_mut(&bucket_id).unwrap();
+            *jobs -= 1;
+
+            if *jobs == 0 {
+                state.pending.remove(&bucket_id);
+            }
+        }
+
+        // The candidates that couldn't be written go back in memory.
+        state.candidates -= failures.iter().map(|((bucket, _, _), _)| bucket.len()).sum::<usize>();
+        state.failed.extend(failures);
+
+        self.written.notify_all();
+    }
+}
+
+// The thread finishes writing whatever it was given before it stops.
+impl Drop for Writer {
+    fn drop(&mut self) {
+        self.sender.take();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! Finds bounds on the number of permutations of `n` symbols that fit in a
+//! string with a given number of wasted symbols, which leads to the length of
+//! the shortest superpermutation.
+//!
+//! The search is best-first. A [`Candidate`] is a string that has been built so
+//! far, described by the permutations it has seen and the last few symbols. It
+//! is expanded by appending each symbol in turn. Candidates are kept in a
+//! [`Frontier`], ordered by the number of symbols they've wasted and then by the
+//! number of permutations they've seen, and the [`Bounds`] table is updated as
+//! candidates are taken from it. The bounds then prune the frontier so that
+//! candidates that can't improve on them aren't expanded.
+//!
+//! ```
+//! use leaps_and_bounds::{Bounds, Candidate};
+//!
+//! let n = 3;
+//! let bounds = Bounds::new(n);
+//! let children = Candidate::seed(n).expand(bounds.upper(0), n).collect::<Vec<_>>();
+//!
+//! // The seed is the string "012", so appending "0" sees the permutation "120"
+//! // and appending "1" wastes a symbol.
+//! assert_eq!(children.len(), 2);
+//! assert_eq!(children[0].number_of_permutations(), 2);
+//! assert_eq!(children[1].wasted_symbols, 1);
+//! ```
+//!
+//! The `leaps-and-bounds` binary runs the whole search from the command line.
+
+extern crate bit_set;
+extern crate bucket_queue;
+extern crate rayon;
+
+#[macro_use]
+extern crate serde_derive;
+extern crate serde;
+extern crate serde_json;
+extern crate serde_bytes;
+extern crate bincode;
+extern crate siphasher;
+
+pub mod bounds;
+pub mod candidate;
+pub mod cluster;
+pub mod disk;
+pub mod events;
+pub mod frontier;
+pub mod search;
+pub mod split;
+pub mod symmetry;
+pub mod verify;
+pub mod witness;
+
+pub use self::bounds::Bounds;
+pub use self::candidate::Candidate;
+pub use self::cluster::{Address, Coordinator, Worker};
+pub use self::disk::Disk;
+pub use self::events::{Event, EventLog};
+pub use self::frontier::Frontier;
+pub use self::search::{Observer, Outcome, Search, Step};
+pub use self::split::Job;
+pub use self::symmetry::Symmetry;
+pub use self::verify::Verifier;
+pub use self::witness::Witnesses;
@@ -1,74 +1,604 @@
-extern crate bit_set;
-extern crate bucket_queue;
-extern crate rayon;
+extern crate leaps_and_bounds;
 
 #[macro_use]
 extern crate serde_derive;
-extern crate serde;
-extern crate serde_bytes;
 extern crate bincode;
 
-mod bounds;
-mod candidate;
-mod disk;
-mod frontier;
+mod args;
+mod checkpoint;
+mod status;
 mod ui;
 
-use self::bounds::Bounds;
-use self::candidate::Candidate;
-use self::frontier::Frontier;
+use leaps_and_bounds::{bounds, candidate, cluster, disk, frontier, witness};
+use leaps_and_bounds::{Address, Bounds, Candidate, Coordinator, Event, EventLog, Frontier, Job, Observer, Outcome, Search, Step, Symmetry, Verifier, Witnesses, Worker};
+use leaps_and_bounds::bounds::Export;
+use leaps_and_bounds::events::Listener;
+use leaps_and_bounds::verify::Report;
+
+use self::args::{Args, Command, CoordinateOptions, Options, SplitOptions};
+use self::checkpoint::Checkpoint;
+use self::status::Status;
 use self::ui::UI;
 
+use std::env;
+use std::fs::create_dir_all;
+use std::io;
 use std::process::exit;
 
 fn main() {
-    UI::print_introduction();
-    let n = UI::ask_for_n();
-    let memory = UI::ask_for_memory();
-    let gzip = UI::ask_for_gzip();
-    let verbose = UI::ask_for_verbose();
-    UI::print_running();
-
-    let candidate = Candidate::seed(n);
-    let mut frontier = Frontier::new(memory, gzip, verbose, n);
-    let mut bounds = Bounds::new(n);
-
-    frontier.add(candidate, n);
-
-    while let Some(mut wasted_symbols) = frontier.min_waste() {
-        wasted_symbols = frontier.unprune(
-            wasted_symbols,
-            &bounds.lower_bounds,
-            &bounds.upper_bounds,
-        );
-
-        let candidate = frontier.next().unwrap();
-        let permutations = candidate.number_of_permutations();
-
-        if bounds.update(wasted_symbols, permutations) {
-            let threshold = bounds.thresholds[wasted_symbols];
-            frontier.prune(wasted_symbols, threshold, true);
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let options = match Args::parse(&args) {
+        Ok(Command::Interactive) => {
+            UI::print_introduction();
+            let options = UI::ask_for_options();
+            UI::print_running();
+
+            options
+        },
+        Ok(Command::Search(options)) => options,
+        Ok(Command::Verify(string, n)) => {
+            verify(&string, n);
+            exit(0);
+        },
+        Ok(Command::Split(options)) => {
+            split(&options);
+            exit(0);
+        },
+        Ok(Command::Merge(jobs_dir, exports)) => {
+            merge(&jobs_dir, &exports);
+            exit(0);
+        },
+        Ok(Command::Coordinate(options)) => {
+            coordinate(&options);
+            exit(0);
+        },
+        Ok(Command::Help) => {
+            println!("{}", Args::usage());
+            exit(0);
+        },
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, Args::usage());
+            exit(2);
+        },
+    };
+
+    search(options);
+}
+
+fn search(options: Options) {
+    if let Some(address) = &options.connect {
+        work(&options, address);
+        exit(0);
+    }
+
+    // A job records the number of symbols it was split for.
+    let job = options.job.as_deref().map(load_job);
+    let options = match &job {
+        Some(job) => Options { n: job.n, ..options },
+        None => options,
+    };
+
+    let n = options.n;
+
+    let log = open_log(&options);
+
+    let mut search = match options.resume {
+        true => resume(&options, log),
+        false => start(&options, log, job),
+    };
+
+    configure(&mut search, &options);
+
+    let mut checkpoint = options.checkpoint.map(|minutes| Checkpoint::new(&options.scratch_dir, minutes));
+    let mut status = options.status.map(Status::new);
+
+    if checkpoint.is_some() {
+        search.frontier_mut().defer_disk_removals();
+    }
+
+    search.observe(Box::new(Reporter { exports: options.exports.clone(), verbose: options.verbose }));
+    export_bounds(&options.exports, search.bounds(), n);
+
+    loop {
+        let step = search.run_until(|_| {
+            checkpoint.as_ref().is_some_and(|c| c.is_due()) || status.as_ref().is_some_and(|s| s.is_due())
+        });
+
+        match step {
+            Step::Running => {
+                if let Some(c) = checkpoint.as_mut() {
+                    if c.is_due() {
+                        save_checkpoint(&options, c, &mut search);
+                    }
+                }
+
+                if let Some(s) = status.as_mut() {
+                    if s.is_due() {
+                        s.print(&search);
+                    }
+                }
+            },
+            Step::Finished(outcome) => {
+                print_outcome(&mut search, &outcome);
+
+                if options.job.is_some() {
+                    println!("These bounds are only for the strings that start with the job's prefix. Merge every job to combine them.");
+                    println!();
+                }
+
+                exit(0);
+            },
+            Step::Stopped => {
+                match search.witness_error() {
+                    Some(error) => eprintln!("\nFailed to record witnesses: {}", error),
+                    None => eprintln!("\n{}", search.frontier().storage_error().unwrap()),
+                }
+
+                stop(&options, checkpoint.as_mut(), &mut search);
+            },
+            Step::Exhausted => return,
+        }
+    }
+}
+
+// Prints each bound for a new number of wasted symbols as it's found, which
+// means the bound for the previous number is exact.
+struct Reporter {
+    exports: Vec<Export>,
+    verbose: bool,
+}
+
+impl Observer for Reporter {
+    fn on_bound(&mut self, search: &mut Search, wasted_symbols: usize, permutations: usize, new_index: bool) {
+        if !new_index {
+            return;
         }
 
-        let upper_bound = bounds.upper(wasted_symbols);
-        for child in candidate.expand(upper_bound, n) {
-            frontier.add(child, n);
+        println!("{} wasted symbols: at most {} permutations", wasted_symbols - 1, permutations);
+
+        if let Some(w) = search.witnesses_mut() {
+            print_witness(w, wasted_symbols - 1);
         }
 
-        if bounds.found_for_superpermutation() {
+        export_bounds(&self.exports, search.bounds(), search.n());
+        print_dominance(search.frontier_mut());
+
+        if self.verbose {
+            print_transpositions(search.frontier());
+        }
+    }
+
+    fn on_contradiction(&mut self, _search: &mut Search, wasted_symbols: usize, known: usize, found: usize) {
+        eprintln!("Warning: the known bounds say at most {} permutations fit with {} wasted symbols, but the search found {}.",
+                  known, wasted_symbols, found);
+    }
+
+    fn on_complete(&mut self, search: &mut Search, outcome: &Outcome) {
+        let factorial = Bounds::factorial(outcome.n);
+        println!("{} wasted symbols: at most {} permutations", outcome.wasted_symbols, factorial);
+
+        if let Some(w) = search.witnesses_mut() {
+            print_witness(w, outcome.wasted_symbols);
+        }
+
+        export_bounds(&self.exports, search.bounds(), search.n());
+    }
+}
+
+fn print_outcome(search: &mut Search, outcome: &Outcome) {
+    let Outcome { n, wasted_symbols, length, .. } = *outcome;
+    let factorial = Bounds::factorial(n);
+
+    println!();
+    println!("--->>> Done!");
+    println!();
+    println!("A maximum of {} wasted symbols can fit all {}! = {} permutations.", wasted_symbols, n, factorial);
+    println!("The shortest superpermutation contains {} + {} + {} = {} symbols.", n - 1, factorial, wasted_symbols, length);
+    println!();
+
+    let frontier = search.frontier_mut();
+
+    if frontier.transpositions().is_some() {
+        print_transpositions(frontier);
+        println!();
+    }
+
+    if let Some(d) = frontier.dominance() {
+        println!("  dominance dropped {} candidates when adding and {} when loading from disk, {} kept to compare against",
+                 d.dropped_on_add, d.dropped_on_onload, d.len());
+        println!();
+    }
+}
+
+// The bounds found so far are still correct, but the search can't continue
+// without the candidates that couldn't be read from or written to disk. If
+// witnesses couldn't be recorded, a new checkpoint would have candidates whose
+// strings are missing from the ancestry file, so the last one is kept instead.
+fn stop(options: &Options, checkpoint: Option<&mut Checkpoint>, search: &mut Search) -> ! {
+    match checkpoint {
+        Some(_) if search.witness_error().is_some() => {
+            eprintln!("Stopped the search. Fix the problem then run again with --resume to continue from the last checkpoint.");
+        },
+        Some(c) => {
+            save_checkpoint(options, c, search);
+            eprintln!("Stopped the search. Fix the problem then run again with --resume to continue.");
+        },
+        None => {
+            eprintln!("Stopped the search. Use --checkpoint to be able to resume after a problem like this.");
+        },
+    }
+
+    exit(1);
+}
+
+// If the checkpoint can't be saved, the previous one is left as it was, so the
+// search stops and can be resumed from there instead.
+fn save_checkpoint(options: &Options, checkpoint: &mut Checkpoint, search: &mut Search) {
+    print!("saving checkpoint... ");
+    UI::flush();
+
+    let (frontier, bounds, witnesses) = search.parts();
+
+    if let Err(e) = checkpoint.save(options, frontier, bounds, witnesses) {
+        eprintln!("\nFailed to save the checkpoint: {}", e);
+        eprintln!("Stopped the search. Fix the problem then run again with --resume to continue from the last checkpoint.");
+        exit(1);
+    }
+
+    println!("done");
+}
+
+fn print_report(report: &Report, symbols: &[u8]) {
+    println!("{}", Witnesses::to_string(symbols));
+    println!("{}", report.marks());
+    println!();
+    println!("  + adds a new permutation, 1 wastes a symbol, 2 wastes this and the next symbol");
+    println!();
+    println!("Symbols:        {}", report.n);
+    println!("Length:         {}", report.length);
+    println!("Permutations:   {} of {}", report.permutations, Bounds::factorial(report.n));
+    println!("Wasted symbols: {}", report.wasted_symbols);
+
+    if report.actual_waste() != report.wasted_symbols {
+        println!();
+        println!("Note: the search counts {} wasted symbols for this string, but only {} are", report.wasted_symbols, report.actual_waste());
+        println!("wasted because a double waste was followed by a symbol that was wasted again.");
+    }
+}
+
+fn verify(string: &str, n: Option<usize>) {
+    let result = Verifier::parse(string)
+        .and_then(|symbols| Verifier::verify(&symbols, n).map(|report| (symbols, report)));
+
+    match result {
+        Ok((symbols, report)) => print_report(&report, &symbols),
+        Err(message) => {
+            eprintln!("{}", message);
+            exit(1);
+        },
+    }
+}
+
+// The options that change how candidates are expanded and kept, whether the
+// search is new, resumed or a job from a coordinator.
+fn configure(search: &mut Search, options: &Options) {
+    let n = search.n();
+
+    if options.symmetry {
+        search.use_symmetry(Symmetry::new(n));
+    }
+
+    search.expand_in_batches(options.batch);
+
+    let frontier = search.frontier_mut();
+    let per_candidate = frontier.bytes_per_candidate();
+
+    println!("\nEach candidate string consumes approximately {} bytes of memory.", per_candidate);
+    println!("The memory limit has been set to {}GiB, or about {} candidates.\n", options.memory, frontier.memory_limit() / per_candidate);
+
+    if options.symmetry || options.drop_duplicates {
+        frontier.drop_duplicates();
+    }
+
+    if let Some(capacity) = options.dominance {
+        frontier.check_dominance(capacity, n);
+
+        let dominance = frontier.dominance().unwrap();
+        let megabytes = dominance.max_bytes() as f64 / 1024. / 1024.;
+
+        println!("Dominance checks keep up to {} candidates for each of {} tails, about {:.1}MiB.\n", dominance.capacity(), dominance.tails(), megabytes);
+    }
+
+    frontier.prioritize(options.priority, n);
+}
+
+fn start(options: &Options, log: Option<EventLog>, job: Option<Job>) -> Search {
+    let Options { memory, codec, ref scratch_dir, .. } = *options;
+    let n = job.as_ref().map_or(options.n, |job| job.n);
+
+    let mut frontier = Frontier::new(memory, scratch_dir, codec, n).unwrap_or_else(|e| fail(e.to_string()));
+    let (mut bounds, prefix) = match (job, &options.known_bounds) {
+        (Some(job), _) => (job.bounds, job.prefix),
+        (None, Some(path)) => (load_bounds(path, n), Candidate::seed(n)),
+        (None, None) => (Bounds::new(n), Candidate::seed(n)),
+    };
+
+    if let Some(log) = log {
+        frontier.log_to(log.clone());
+        bounds.log_to(log);
+    }
+
+    let mut search = Search::from_prefix(frontier, bounds, prefix, n);
+
+    if let Some(path) = &options.witnesses {
+        let witnesses = Witnesses::new(scratch_dir, path, options.symmetry, n)
+            .unwrap_or_else(|e| fail(format!("Failed to start recording witnesses: {}", e)));
+
+        search.record_witnesses(witnesses);
+    }
+
+    search
+}
+
+fn load_job(path: &str) -> Job {
+    match Job::load(path) {
+        Ok(job) => {
+            println!("Searching the strings that start with the prefix in {}.\n", path);
+            job
+        },
+        Err(message) => {
+            eprintln!("{}", message);
+            exit(1);
+        },
+    }
+}
+
+fn split(options: &SplitOptions) {
+    let SplitOptions { n, depth, ref jobs_dir, ref known_bounds } = *options;
+
+    let bounds = match known_bounds {
+        Some(path) => load_bounds(path, n),
+        None => Bounds::new(n),
+    };
+
+    create_dir_all(jobs_dir).unwrap_or_else(|e| fail(format!("Failed to create {}: {}", jobs_dir, e)));
+
+    // Merging reads every job in the directory, so old ones would be mixed in.
+    if !Job::paths(jobs_dir).unwrap_or_else(|e| fail(format!("Failed to read jobs from {}: {}", jobs_dir, e))).is_empty() {
+        eprintln!("{} already has jobs in it. Use an empty directory for each split.", jobs_dir);
+        exit(1);
+    }
+
+    let jobs = Job::split(bounds, depth, n);
+
+    for (i, job) in jobs.iter().enumerate() {
+        job.save(&Job::filename(jobs_dir, i)).unwrap_or_else(|e| fail(format!("Failed to write jobs to {}: {}", jobs_dir, e)));
+    }
+
+    println!("Wrote {} jobs to {}.", jobs.len(), jobs_dir);
+    println!("Search each with --job {} --export {}, then combine them with merge --jobs {}.",
+             Job::filename(jobs_dir, 0), Job::filename(jobs_dir, 0).replace(".job", ".json"), jobs_dir);
+}
+
+// A job that hasn't exported anything yet still has the bounds it started
+// with, which are exact for the strings shorter than its prefix.
+fn merge(jobs_dir: &str, exports: &[Export]) {
+    let paths = match Job::paths(jobs_dir) {
+        Ok(paths) if !paths.is_empty() => paths,
+        Ok(_) => fail(format!("There are no jobs in {}.", jobs_dir)),
+        Err(e) => fail(format!("Failed to read jobs from {}: {}", jobs_dir, e)),
+    };
+
+    let mut parts = vec![];
+    let mut unfinished = 0;
+    let mut n = 0;
+
+    for path in &paths {
+        let job = Job::load(path).unwrap_or_else(fail);
+        n = job.n;
+
+        match Job::result_path(path) {
+            Some(result) => parts.push(Bounds::load(&result, n).unwrap_or_else(fail)),
+            None => {
+                parts.push(job.bounds);
+                unfinished += 1;
+            },
+        }
+    }
+
+    let bounds = Bounds::merge(&parts, n).unwrap_or_else(fail);
+
+    if unfinished > 0 {
+        println!("{} of {} jobs haven't exported any bounds yet.\n", unfinished, paths.len());
+    }
+
+    for (w, &lower_bound) in bounds.lower_bounds.iter().enumerate() {
+        match w < bounds.trusted {
+            true => println!("{} wasted symbols: at most {} permutations", w, lower_bound),
+            false => println!("{} wasted symbols: at least {} permutations, but not every job has finished", w, lower_bound),
+        }
+    }
+
+    export_bounds(exports, &bounds, n);
+}
+
+fn coordinate(options: &CoordinateOptions) {
+    let CoordinateOptions { n, depth, ref address, ref known_bounds, ref exports } = *options;
+
+    let bounds = match known_bounds {
+        Some(path) => load_bounds(path, n),
+        None => Bounds::new(n),
+    };
+
+    let jobs = Job::split(bounds, depth, n);
+    let count = jobs.len();
+
+    let coordinator = Coordinator::bind(address, jobs, n).unwrap_or_else(|e| fail(format!("Failed to listen on {}: {}", address, e)));
+    let address = coordinator.address();
+
+    println!("Listening on {} with {} jobs. Start workers with --connect {}.\n", address, count, address);
+
+    let mut exact_rows = 0;
+    let mut exported: Option<Bounds> = None;
+
+    let bounds = coordinator.run(|bounds| {
+        for (w, lower_bound) in bounds.lower_bounds.iter().enumerate().take(bounds.trusted).skip(exact_rows) {
+            println!("{} wasted symbols: at most {} permutations", w, lower_bound);
+        }
+
+        exact_rows = exact_rows.max(bounds.trusted);
+
+        if exported.as_ref() != Some(bounds) {
+            export_bounds(exports, bounds, n);
+            exported = Some(bounds.clone());
+        }
+    }, |e| eprintln!("Failed to accept a worker: {}", e)).unwrap_or_else(fail);
+
+    println!();
+    println!("--->>> Done!");
+    println!();
+
+    if bounds.found_for_superpermutation() {
+        let wasted_symbols = bounds.lower_bounds.len() - 1;
+        let factorial = bounds.max;
+
+        println!("A maximum of {} wasted symbols can fit all {}! = {} permutations.", wasted_symbols, n, factorial);
+        println!("The shortest superpermutation contains {} + {} + {} = {} symbols.", n - 1, factorial, wasted_symbols, n - 1 + factorial + wasted_symbols);
+        println!();
+    }
+
+    println!("Workers searched {} jobs, including the ones they gave away.", coordinator.jobs());
+}
+
+// Each job is searched in the same scratch directory, which is cleared when
+// the next one starts.
+fn work(options: &Options, address: &Address) {
+    let log = open_log(options);
+    let mut worker = Worker::connect(address).unwrap_or_else(fail);
+
+    println!("Connected to the coordinator at {}.\n", address);
+
+    let searched = worker.run(|id, job| {
+        println!("Searching job {}.", id);
+
+        let mut search = start(options, log.clone(), Some(job));
+        configure(&mut search, options);
+
+        search
+    }).unwrap_or_else(fail);
+
+    println!("\nThe coordinator has no more jobs. This worker searched {} of them.", searched);
+}
+
+fn fail<T>(message: String) -> T {
+    eprintln!("{}", message);
+    exit(1);
+}
+
+fn load_bounds(path: &str, n: usize) -> Bounds {
+    match Bounds::load(path, n) {
+        Ok(bounds) => {
             let waste = bounds.lower_bounds.len() - 1;
-            let factorial = Bounds::factorial(n);
-            let length = n - 1 + factorial + waste;
+            println!("Starting from the bounds for up to {} wasted symbols in {}.\n", waste, path);
+
+            bounds
+        },
+        Err(message) => {
+            eprintln!("{}", message);
+            exit(1);
+        },
+    }
+}
 
-            println!("{} wasted symbols: at most {} permutations", waste, factorial);
-            println!();
-            println!("--->>> Done!");
-            println!();
-            println!("A maximum of {} wasted symbols can fit all {}! = {} permutations.", waste, n, factorial);
-            println!("The shortest superpermutation contains {} + {} + {} = {} symbols.", n - 1, factorial, waste, length);
-            println!();
+fn resume(options: &Options, log: Option<EventLog>) -> Search {
+    match Checkpoint::load(options) {
+        Ok((mut frontier, mut bounds, witnesses)) => {
+            println!("Resuming from the checkpoint in {}.\n", options.scratch_dir);
 
-            exit(0);
+            if let Some(log) = log {
+                frontier.log_to(log.clone());
+                bounds.log_to(log);
+            }
+
+            let mut search = Search::resume(frontier, bounds, options.n);
+
+            if let Some(w) = witnesses {
+                search.record_witnesses(w);
+            }
+
+            search
+        },
+        Err(message) => {
+            eprintln!("{}", message);
+            exit(1);
+        },
+    }
+}
+
+// The log is opened even without --log so that problems the search carries on
+// after, such as scratch files that couldn't be removed, are printed. With
+// --verbose every event is printed as well.
+fn open_log(options: &Options) -> Option<EventLog> {
+    let printer = Box::new(LogPrinter { verbose: options.verbose });
+
+    match EventLog::open(options.log.as_deref(), Some(printer), options.resume) {
+        Ok(log) => log,
+        Err(e) => {
+            eprintln!("Failed to open the event log {}: {}", options.log.as_deref().unwrap_or(""), e);
+            exit(1);
+        },
+    }
+}
+
+struct LogPrinter {
+    verbose: bool,
+}
+
+impl Listener for LogPrinter {
+    fn on_event(&mut self, event: &Event, line: &str) {
+        match event {
+            _ if self.verbose => println!("  {}", line),
+            Event::Leftover { error } => eprintln!("{}", error),
+            _ => {},
+        }
+    }
+
+    fn on_error(&mut self, error: &io::Error) {
+        eprintln!("Failed to write to the event log: {}", error);
+    }
+}
+
+// A file that can't be written is reported but doesn't stop the search.
+fn export_bounds(exports: &[Export], bounds: &Bounds, n: usize) {
+    for export in exports {
+        if let Err(e) = export.write(bounds, n) {
+            eprintln!("Failed to write {}: {}", export.path, e);
         }
     }
 }
+
+fn print_transpositions(frontier: &Frontier) {
+    if let Some(t) = frontier.transpositions() {
+        let total = t.added + t.duplicates;
+        let percent = 100. * t.duplicates as f64 / total.max(1) as f64;
+
+        println!("  dropped {} of {} candidates as duplicates ({:.1}%), {} fingerprints held, {} forgotten",
+                 t.duplicates, total, percent, t.len(), t.forgotten);
+    }
+}
+
+fn print_dominance(frontier: &mut Frontier) {
+    if let Some(d) = frontier.dominance() {
+        let (added, onloaded) = d.end_phase();
+        println!("  dominance dropped {} candidates when adding and {} when loading from disk", added, onloaded);
+    }
+}
+
+fn print_witness(witnesses: &mut Witnesses, wasted_symbols: usize) {
+    match witnesses.publish(wasted_symbols) {
+        Ok(Some(string)) => println!("  e.g. {}", string),
+        Ok(None) => {},
+        Err(e) => eprintln!("  Failed to publish a witness: {}", e),
+    }
+}
@@ -0,0 +1,387 @@
+use super::bounds::Bounds;
+use super::candidate::Candidate;
+use super::frontier::Frontier;
+use super::symmetry::Symmetry;
+use super::witness::Witnesses;
+
+use rayon::prelude::*;
+use std::io;
+use std::mem;
+use std::time::{Duration, Instant};
+
+// The best-first loop. Each step takes the best candidate from the frontier,
+// or a batch from the best bucket, updates the bounds with it, prunes the
+// frontier if a bound improved and then adds the children. Observers are told
+// about each of these as they happen so that callers can report progress
+// without changing the loop.
+
+/// A search for the bounds of one number of symbols.
+pub struct Search {
+    n: usize,
+    frontier: Frontier,
+    bounds: Bounds,
+    symmetry: Option<Symmetry>,
+    witnesses: Option<Witnesses>,
+    witness_error: Option<io::Error>,
+    observers: Vec<Box<dyn Observer>>,
+    batch_size: usize,
+    phase_started: Instant,
+    outcome: Option<Outcome>,
+}
+
+/// What happened when the search took a step.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    /// A candidate was expanded and there's more to do.
+    Running,
+    /// Every permutation fits, so the shortest superpermutation is known.
+    Finished(Outcome),
+    /// The frontier has a storage error, or witnesses couldn't be recorded,
+    /// and the search can't continue correctly.
+    Stopped,
+    /// The frontier ran out of candidates before finishing.
+    Exhausted,
+}
+
+/// The result of a finished search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub n: usize,
+    /// The fewest wasted symbols that fit every permutation.
+    pub wasted_symbols: usize,
+    /// The length of the shortest superpermutation.
+    pub length: usize,
+    /// The most permutations that fit with each number of wasted symbols.
+    pub lower_bounds: Vec<usize>,
+}
+
+/// Callbacks for what happens during the search. Each has a default that does
+/// nothing so that only the ones that are needed have to be implemented. The
+/// search is passed in so that observers can look at (or change) its state.
+pub trait Observer {
+    /// A lower bound improved. The bound is for a new number of wasted symbols
+    /// if `new_index` is true, which means the previous one is now exact.
+    fn on_bound(&mut self, _search: &mut Search, _wasted_symbols: usize, _permutations: usize, _new_index: bool) {}
+
+    /// Buckets were disabled because they can't improve the bounds.
+    fn on_prune(&mut self, _search: &mut Search, _wasted_symbols: usize, _threshold: usize) {}
+
+    /// The search found more permutations than a trusted row of the known
+    /// bounds said would fit, so they're wrong.
+    fn on_contradiction(&mut self, _search: &mut Search, _wasted_symbols: usize, _known: usize, _found: usize) {}
+
+    /// A disabled bucket was enabled again for the current phase.
+    fn on_unprune(&mut self, _search: &mut Search, _wasted_symbols: usize) {}
+
+    /// The search finished.
+    fn on_complete(&mut self, _search: &mut Search, _outcome: &Outcome) {}
+}
+
+impl Search {
+    /// Starts a new search from the seed, pruning with any bounds that are
+    /// already known.
+    pub fn new(frontier: Frontier, bounds: Bounds, n: usize) -> Self {
+        Self::from_prefix(frontier, bounds, Candidate::seed(n), n)
+    }
+
+    /// Starts a new search of the strings that start with `prefix`. The bounds
+    /// should be in prefix mode, as they are in a `Job`.
+    pub fn from_prefix(mut frontier: Frontier, bounds: Bounds, prefix: Candidate, n: usize) -> Self {
+        frontier.add(prefix, n);
+
+        for (wasted_symbols, &threshold) in bounds.thresholds.iter().enumerate() {
+            frontier.prune(wasted_symbols, threshold, false);
+        }
+
+        Self::resume(frontier, bounds, n)
+    }
+
+    /// Continues a search from a frontier and bounds that were saved.
+    pub fn resume(frontier: Frontier, bounds: Bounds, n: usize) -> Self {
+        Self {
+            n,
+            frontier,
+            bounds,
+            symmetry: None,
+            witnesses: None,
+            witness_error: None,
+            observers: vec![],
+            batch_size: 1,
+            phase_started: Instant::now(),
+            outcome: None,
+        }
+    }
+
+    /// Puts each child into a canonical form before it's added to the frontier.
+    pub fn use_symmetry(&mut self, symmetry: Symmetry) {
+        self.symmetry = Some(symmetry);
+    }
+
+    /// Records how each child was built so that witnesses can be published.
+    pub fn record_witnesses(&mut self, witnesses: Witnesses) {
+        self.witnesses = Some(witnesses);
+    }
+
+    /// Takes up to `size` candidates from the best bucket at a time and expands
+    /// them in parallel. Candidates in the same bucket can't improve on each
+    /// other's bounds, so the bounds are the same as expanding one at a time,
+    /// but a batch may expand a few candidates that would have been pruned.
+    pub fn expand_in_batches(&mut self, size: usize) {
+        self.batch_size = size.max(1);
+    }
+
+    pub fn observe(&mut self, observer: Box<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    pub fn frontier(&self) -> &Frontier {
+        &self.frontier
+    }
+
+    pub fn frontier_mut(&mut self) -> &mut Frontier {
+        &mut self.frontier
+    }
+
+    pub fn bounds(&self) -> &Bounds {
+        &self.bounds
+    }
+
+    pub fn witnesses_mut(&mut self) -> Option<&mut Witnesses> {
+        self.witnesses.as_mut()
+    }
+
+    /// Why a child's witness couldn't be recorded, which stops the search.
+    pub fn witness_error(&self) -> Option<&io::Error> {
+        self.witness_error.as_ref()
+    }
+
+    /// Borrows everything that's saved in a checkpoint at once.
+    pub fn parts(&mut self) -> (&Frontier, &Bounds, Option<&mut Witnesses>) {
+        (&self.frontier, &self.bounds, self.witnesses.as_mut())
+    }
+
+    /// How long it has been since a bound was found for a new number of wasted
+    /// symbols, or since the search was created.
+    pub fn phase_elapsed(&self) -> Duration {
+        self.phase_started.elapsed()
+    }
+
+    /// Expands the best candidate in the frontier, or a batch of them.
+    pub fn step(&mut self) -> Step {
+        if let Some(outcome) = &self.outcome {
+            return Step::Finished(outcome.clone());
+        }
+
+        let min_waste = self.frontier.min_waste();
+
+        if min_waste.is_none() && !self.frontier.has_disabled() {
+            return Step::Exhausted;
+        }
+
+        // The search moves on one number of wasted symbols at a time, checking
+        // each for disabled candidates that could improve it, even if every
+        // enabled candidate has wasted more or there are none. This can happen
+        // when the bounds came from elsewhere, such as for a job.
+        let next_waste = min_waste.map_or(self.bounds.lower_bounds.len(), |w| w.min(self.bounds.lower_bounds.len()));
+        let wasted_symbols = self.frontier.unprune(next_waste, &self.bounds.lower_bounds, self.bounds.suffix_bounds());
+
+        if wasted_symbols != next_waste {
+            self.notify(|o, search| o.on_unprune(search, wasted_symbols));
+        }
+
+        if self.frontier.storage_error().is_some() || self.witness_error.is_some() {
+            return Step::Stopped;
+        }
+
+        // Nothing has this much waste, so the bound for one less is exact and
+        // carries over.
+        if self.frontier.min_waste() != Some(wasted_symbols) {
+            if self.bounds.found_for_superpermutation() {
+                return self.finish();
+            }
+
+            let permutations = *self.bounds.lower_bounds.last().unwrap();
+
+            self.bounds.update(wasted_symbols, permutations);
+            self.phase_started = Instant::now();
+            self.improved(wasted_symbols, permutations, true);
+
+            return Step::Running;
+        }
+
+        // Candidates that were spilled to disk are onloaded here, which can fail.
+        // One at a time, the children go straight into the frontier, and only
+        // a batch is expanded in parallel.
+        if self.batch_size == 1 {
+            let candidate = match self.frontier.next() {
+                Some(candidate) => candidate,
+                None => return self.nothing_taken(),
+            };
+
+            self.update_bounds(wasted_symbols, &candidate);
+            let upper_bound = self.bounds.upper(wasted_symbols);
+
+            for child in candidate.expand(upper_bound, self.n) {
+                self.add_child(child);
+            }
+        } else {
+            let candidates = self.frontier.next_batch(self.batch_size);
+
+            if candidates.is_empty() {
+                return self.nothing_taken();
+            }
+
+            // The candidates in a batch have the same waste and permutations, so
+            // only the first can improve the bounds.
+            self.update_bounds(wasted_symbols, &candidates[0]);
+            let upper_bound = self.bounds.upper(wasted_symbols);
+
+            for children in self.expand(candidates, upper_bound) {
+                for child in children {
+                    self.add_child(child);
+                }
+            }
+        }
+
+        if self.witness_error.is_some() {
+            return Step::Stopped;
+        }
+
+        match self.bounds.found_for_superpermutation() {
+            true => self.finish(),
+            false => Step::Running,
+        }
+    }
+
+    /// Raises the lower bound for the number of wasted symbols being searched
+    /// to one that was found elsewhere, such as by another worker, and prunes
+    /// with it. Bounds for other numbers of wasted symbols are ignored, since
+    /// the earlier ones are already exact for these strings and the later ones
+    /// would skip part of the search. Returns true if the bound improved.
+    pub fn raise_lower_bound(&mut self, wasted_symbols: usize, permutations: usize) -> bool {
+        if wasted_symbols + 1 != self.bounds.lower_bounds.len() {
+            return false;
+        }
+
+        if !self.bounds.update(wasted_symbols, permutations) {
+            return false;
+        }
+
+        self.improved(wasted_symbols, permutations, false);
+        true
+    }
+
+    /// Takes steps until the search finishes or can't continue, or until
+    /// `stop` returns true after a step. Returns the last step.
+    pub fn run_until<F: FnMut(&Search) -> bool>(&mut self, mut stop: F) -> Step {
+        loop {
+            match self.step() {
+                Step::Running if !stop(self) => continue,
+                step => return step,
+            }
+        }
+    }
+
+    /// Takes steps until the search finishes or can't continue.
+    pub fn run(&mut self) -> Step {
+        self.run_until(|_| false)
+    }
+
+    fn finish(&mut self) -> Step {
+        let wasted_symbols = self.bounds.lower_bounds.len() - 1;
+        let outcome = Outcome {
+            n: self.n,
+            wasted_symbols,
+            length: self.n - 1 + self.bounds.max + wasted_symbols,
+            lower_bounds: self.bounds.lower_bounds.clone(),
+        };
+
+        self.outcome = Some(outcome.clone());
+        self.notify(|o, search| o.on_complete(search, &outcome));
+
+        Step::Finished(outcome)
+    }
+
+    // The frontier had nothing to take, which stops the search if it's because
+    // of a storage error.
+    fn nothing_taken(&self) -> Step {
+        match self.frontier.storage_error() {
+            Some(_) => Step::Stopped,
+            None => Step::Running,
+        }
+    }
+
+    fn update_bounds(&mut self, wasted_symbols: usize, candidate: &Candidate) {
+        let permutations = candidate.number_of_permutations();
+        let previous_len = self.bounds.lower_bounds.len();
+
+        if self.bounds.update(wasted_symbols, permutations) {
+            let new_index = self.bounds.lower_bounds.len() > previous_len;
+
+            if let Some(w) = self.witnesses.as_mut() {
+                w.improve(wasted_symbols, permutations, candidate.ancestry_id.into());
+            }
+
+            if new_index {
+                self.phase_started = Instant::now();
+            }
+
+            self.improved(wasted_symbols, permutations, new_index);
+        }
+    }
+
+    fn add_child(&mut self, mut child: Candidate) {
+        let relabelling = self.symmetry.as_mut().map(|s| s.canonicalize(&mut child));
+
+        // Once one fails, the rest aren't recorded, as the search stops.
+        if let (Some(w), None) = (self.witnesses.as_mut(), &self.witness_error) {
+            self.witness_error = w.record(&mut child, relabelling.as_deref()).err();
+        }
+
+        self.frontier.add(child, self.n);
+    }
+
+    fn improved(&mut self, wasted_symbols: usize, permutations: usize, new_index: bool) {
+        let threshold = self.bounds.thresholds[wasted_symbols];
+
+        self.frontier.prune(wasted_symbols, threshold, true);
+        self.frontier.prefetch(&self.bounds.lower_bounds, self.bounds.suffix_bounds());
+        self.notify(|o, search| o.on_prune(search, wasted_symbols, threshold));
+        self.notify(|o, search| o.on_bound(search, wasted_symbols, permutations, new_index));
+
+        for c in self.bounds.take_contradictions() {
+            self.notify(|o, search| o.on_contradiction(search, c.wasted_symbols, c.known, c.found));
+        }
+    }
+
+    // Children are kept in the order of their parents so that a batch adds them
+    // to the frontier in the same order as expanding one at a time would.
+    fn expand(&self, candidates: Vec<Candidate>, upper_bound: usize) -> Vec<Vec<Candidate>> {
+        let n = self.n;
+
+        match candidates.len() {
+            1 => candidates.into_iter().map(|c| c.expand(upper_bound, n).collect()).collect(),
+            _ => candidates.into_par_iter().map(|c| c.expand(upper_bound, n).collect()).collect(),
+        }
+    }
+
+    // Observers are taken out of the search while they're called so that they
+    // can be given it mutably. Any they add in the meantime are kept.
+    fn notify<F: FnMut(&mut dyn Observer, &mut Search)>(&mut self, mut f: F) {
+        let mut observers = mem::take(&mut self.observers);
+
+        for observer in observers.iter_mut() {
+            f(observer.as_mut(), self);
+        }
+
+        observers.append(&mut self.observers);
+        self.observers = observers;
+    }
+}
+
+#[cfg(test)]
+mod test;
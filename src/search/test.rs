@@ -0,0 +1,278 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use super::*;
+use super::super::disk::Codec;
+use super::super::split::Job;
+
+use std::cell::RefCell;
+use std::fs::{create_dir_all, remove_file};
+use std::os::unix::fs::symlink;
+use std::rc::Rc;
+
+type Subject = Search;
+
+fn subject(n: usize, test_id: &'static str) -> Subject {
+    let scratch_dir = format!("/tmp/superpermutation-test/{}", test_id);
+    let frontier = Frontier::new(1.0, &scratch_dir, Codec::Raw, n).unwrap();
+
+    Subject::new(frontier, Bounds::new(n), n)
+}
+
+#[derive(Default)]
+struct Recorder {
+    bounds: Rc<RefCell<Vec<(usize, usize, bool)>>>,
+    outcomes: Rc<RefCell<Vec<Outcome>>>,
+}
+
+impl Observer for Recorder {
+    fn on_bound(&mut self, _search: &mut Search, wasted_symbols: usize, permutations: usize, new_index: bool) {
+        self.bounds.borrow_mut().push((wasted_symbols, permutations, new_index));
+    }
+
+    fn on_complete(&mut self, _search: &mut Search, outcome: &Outcome) {
+        self.outcomes.borrow_mut().push(outcome.clone());
+    }
+}
+
+mod new {
+    use super::*;
+
+    #[test]
+    fn it_seeds_the_frontier() {
+        let subject = subject(3, "search-1");
+
+        assert_eq!(subject.frontier().len(), 1);
+        assert_eq!(subject.bounds().lower_bounds, vec![0]);
+    }
+}
+
+mod step {
+    use super::*;
+
+    #[test]
+    fn it_expands_one_candidate_at_a_time() {
+        let mut subject = subject(3, "search-2");
+
+        assert_eq!(subject.step(), Step::Running);
+        assert_eq!(subject.frontier().len(), 2);
+        assert_eq!(subject.bounds().lower_bounds, vec![1]);
+    }
+}
+
+mod step_past_the_bounds {
+    use super::*;
+
+    #[test]
+    fn it_moves_on_one_number_of_wasted_symbols_at_a_time() {
+        let prefix = Candidate::seed(4).expand(24, 4)
+            .flat_map(|c| c.expand(24, 4))
+            .find(|c| c.total_waste(4) == 2)
+            .unwrap();
+
+        let frontier = Frontier::new(1.0, "/tmp/superpermutation-test/search-11", Codec::Raw, 4).unwrap();
+        let mut subject = Subject::from_prefix(frontier, Bounds::new(4), prefix, 4);
+
+        assert_eq!(subject.step(), Step::Running);
+        assert_eq!(subject.bounds().lower_bounds, vec![0, 0]);
+
+        assert_eq!(subject.step(), Step::Running);
+        assert_eq!(subject.bounds().lower_bounds.len(), 3);
+    }
+}
+
+// When nothing enabled has the waste being searched, the bound for one less is
+// exact and carries over, and the search moves on to check the disabled
+// candidates with the next number of wasted symbols.
+mod carry_over {
+    use super::*;
+
+    #[test]
+    fn it_is_exhausted_when_there_is_nothing_left_to_expand() {
+        let mut subject = subject(4, "search-12");
+        subject.frontier_mut().next();
+
+        assert_eq!(subject.step(), Step::Exhausted);
+        assert_eq!(subject.bounds().lower_bounds, vec![0]);
+    }
+
+    #[test]
+    fn it_carries_the_bound_over_when_every_candidate_is_disabled() {
+        let mut bounds = Bounds::new(4);
+
+        for (wasted_symbols, &permutations) in [4, 8, 12].iter().enumerate() {
+            bounds.update(wasted_symbols, permutations);
+        }
+
+        let prefix = Candidate::seed(4).expand(24, 4).find(|c| c.wasted_symbols == 1).unwrap();
+        let frontier = Frontier::new(1.0, "/tmp/superpermutation-test/search-13", Codec::Raw, 4).unwrap();
+        let mut subject = Subject::from_prefix(frontier, bounds, prefix, 4);
+
+        let recorder = Recorder::default();
+        let recorded = recorder.bounds.clone();
+        subject.observe(Box::new(recorder));
+
+        assert_eq!(subject.frontier().min_waste(), None);
+        assert_eq!(subject.step(), Step::Running);
+
+        assert_eq!(subject.bounds().lower_bounds, vec![4, 8, 12, 12]);
+        assert_eq!(*recorded.borrow(), vec![(3, 12, true)]);
+    }
+}
+
+mod run {
+    use super::*;
+
+    #[test]
+    fn it_returns_the_outcome_rather_than_printing_it() {
+        let mut subject = subject(3, "search-3");
+
+        let outcome = Outcome { n: 3, wasted_symbols: 1, length: 9, lower_bounds: vec![3, 6] };
+        assert_eq!(subject.run(), Step::Finished(outcome.clone()));
+
+        // The search stays finished if it's stepped again.
+        assert_eq!(subject.step(), Step::Finished(outcome));
+    }
+
+    #[test]
+    fn it_finds_the_shortest_superpermutation_for_four_symbols() {
+        let mut subject = subject(4, "search-4");
+
+        match subject.run() {
+            Step::Finished(outcome) => {
+                assert_eq!(outcome.length, 33);
+                assert_eq!(outcome.lower_bounds, vec![4, 8, 12, 14, 18, 20, 24]);
+            },
+            step => panic!("Expected the search to finish, but it returned {:?}", step),
+        }
+    }
+
+    #[test]
+    fn it_stops_if_the_witnesses_cannot_be_recorded() {
+        let scratch_dir = "/tmp/superpermutation-test/search-14";
+        let filename = format!("{}/ancestry.dat", scratch_dir);
+
+        create_dir_all(scratch_dir).unwrap();
+        let _ = remove_file(&filename);
+        symlink("/dev/full", &filename).unwrap();
+
+        let witnesses = Witnesses::new(scratch_dir, &format!("{}/witnesses.txt", scratch_dir), false, 5).unwrap();
+        let mut subject = subject(5, "search-14");
+        subject.record_witnesses(witnesses);
+
+        assert_eq!(subject.run(), Step::Stopped);
+        assert_eq!(subject.witness_error().is_some(), true);
+        assert_eq!(subject.step(), Step::Stopped);
+    }
+}
+
+mod expand_in_batches {
+    use super::*;
+
+    #[test]
+    fn it_finds_the_same_bounds_as_expanding_one_at_a_time() {
+        let mut expected = subject(4, "search-7");
+        let expected = expected.run();
+
+        for &size in &[2, 64, 4096] {
+            let mut subject = subject(4, "search-7");
+            subject.expand_in_batches(size);
+
+            assert_eq!(subject.run(), expected);
+        }
+    }
+
+    #[test]
+    fn it_finds_the_same_bounds_for_five_symbols() {
+        let max_bounds = 20;
+
+        let mut expected = subject(5, "search-8");
+        expected.run_until(|s| s.bounds().lower_bounds.len() > max_bounds);
+
+        let mut subject = subject(5, "search-8");
+        subject.expand_in_batches(1024);
+        subject.run_until(|s| s.bounds().lower_bounds.len() > max_bounds);
+
+        assert_eq!(subject.bounds().lower_bounds[..max_bounds], expected.bounds().lower_bounds[..max_bounds]);
+    }
+}
+
+mod run_until {
+    use super::*;
+
+    #[test]
+    fn it_stops_when_the_predicate_is_true() {
+        let mut subject = subject(4, "search-5");
+
+        let step = subject.run_until(|s| s.bounds().lower_bounds.len() == 3);
+
+        assert_eq!(step, Step::Running);
+        assert_eq!(subject.bounds().lower_bounds.len(), 3);
+    }
+}
+
+mod observe {
+    use super::*;
+
+    #[test]
+    fn it_tells_observers_about_bounds_and_completion() {
+        let mut subject = subject(3, "search-6");
+        let recorder = Recorder::default();
+
+        let bounds = recorder.bounds.clone();
+        let outcomes = recorder.outcomes.clone();
+
+        subject.observe(Box::new(recorder));
+        subject.run();
+
+        assert_eq!(*bounds.borrow(), vec![
+            (0, 1, false), (0, 2, false), (0, 3, false),
+            (1, 3, true), (1, 4, false), (1, 5, false), (1, 6, false),
+        ]);
+        assert_eq!(outcomes.borrow().len(), 1);
+        assert_eq!(outcomes.borrow()[0].wasted_symbols, 1);
+    }
+}
+
+mod raise_lower_bound {
+    use super::*;
+
+    #[test]
+    fn it_only_raises_the_bound_being_searched() {
+        let mut subject = subject(4, "search-9");
+        subject.run_until(|s| s.bounds().lower_bounds.len() == 3);
+
+        assert_eq!(subject.raise_lower_bound(1, 9), false);
+        assert_eq!(subject.raise_lower_bound(3, 15), false);
+
+        assert_eq!(subject.raise_lower_bound(2, 12), true);
+        assert_eq!(subject.bounds().lower_bounds, vec![4, 8, 12]);
+    }
+
+    // The job for one prefix can't do as well as the others, so once it's told
+    // their bounds, every candidate it has can be pruned.
+    #[test]
+    fn it_moves_on_when_every_candidate_is_pruned() {
+        let exact = [4, 8, 12, 14, 18, 20, 24];
+        let job = Job::split(Bounds::new(4), 1, 4).pop().unwrap();
+
+        let scratch_dir = "/tmp/superpermutation-test/search-10";
+        let frontier = Frontier::new(1.0, scratch_dir, Codec::Raw, 4).unwrap();
+        let mut subject = Subject::from_prefix(frontier, job.bounds, job.prefix, 4);
+
+        let step = loop {
+            let w = subject.bounds().lower_bounds.len() - 1;
+            subject.raise_lower_bound(w, exact[w]);
+
+            match subject.step() {
+                Step::Running => continue,
+                step => break step,
+            }
+        };
+
+        match step {
+            // The job starts after one wasted symbol, so it doesn't raise that.
+            Step::Finished(outcome) => assert_eq!(outcome.lower_bounds, vec![4, 4, 12, 14, 18, 20, 24]),
+            step => panic!("Expected the search to finish, but it returned {:?}", step),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use super::bounds::Bounds;
+use super::candidate::Candidate;
+
+use bincode::{serialize_into, deserialize_from};
+
+use std::fs::{File, read_dir};
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+// A search can be split into jobs that run on different machines. Each job is
+// a string that the seed can be expanded into, to a chosen depth, and searches
+// the strings that start with it. Together they cover every string, and the
+// strings they were built from are counted in the bounds each job starts with.
+
+const VERSION: u32 = 1;
+
+/// One part of a search that has been split by prefix.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub n: usize,
+    /// The string the job searches from.
+    pub prefix: Candidate,
+    /// The bounds to start from, in prefix mode.
+    pub bounds: Bounds,
+}
+
+impl Job {
+    /// Expands the seed `depth` times, without pruning, into one job for each
+    /// string. Every job starts with `bounds`, which should be trusted, and
+    /// its prefix and the strings that led to it.
+    pub fn split(mut bounds: Bounds, depth: usize, n: usize) -> Vec<Self> {
+        // Without wasting a symbol, a string can only see the rotations of its
+        // first permutation, so the bound for no wasted symbols is n.
+        if bounds.trusted == 0 {
+            bounds.update(0, n);
+            bounds.trusted = 1;
+        }
+
+        bounds.from_prefix();
+
+        let mut jobs = vec![];
+        Self::expand(Candidate::seed(n), bounds, depth, n, &mut jobs);
+
+        jobs
+    }
+
+    /// A job for the strings that start with `prefix`. The bounds should be in
+    /// prefix mode and count the strings that led to it, e.g. the bounds of the
+    /// search it was taken from.
+    pub fn new(prefix: Candidate, mut bounds: Bounds, n: usize) -> Self {
+        // The prefix is counted as well so that the job has a row for its
+        // waste before it starts.
+        bounds.update(prefix.total_waste(n), prefix.number_of_permutations());
+
+        Job { n, prefix, bounds }
+    }
+
+    fn expand(candidate: Candidate, mut bounds: Bounds, depth: usize, n: usize, jobs: &mut Vec<Self>) {
+        if depth == 0 {
+            jobs.push(Self::new(candidate, bounds, n));
+            return;
+        }
+
+        bounds.update(candidate.total_waste(n), candidate.number_of_permutations());
+        let max = bounds.max;
+
+        for child in candidate.expand(max, n) {
+            Self::expand(child, bounds.clone(), depth - 1, n, jobs);
+        }
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        serialize_into(&mut writer, &VERSION).map_err(Self::to_io)?;
+        serialize_into(&mut writer, self).map_err(Self::to_io)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let fail = |reason: String| format!("Failed to load job {}: {}", path, reason);
+
+        let file = File::open(path).map_err(|e| fail(e.to_string()))?;
+        let mut reader = BufReader::new(file);
+
+        let version: u32 = deserialize_from(&mut reader).map_err(|e| fail(e.to_string()))?;
+
+        if version != VERSION {
+            return Err(fail(format!("it has version {} but this build reads version {}.", version, VERSION)));
+        }
+
+        deserialize_from(&mut reader).map_err(|e| fail(e.to_string()))
+    }
+
+    /// The name of each job's file, in order.
+    pub fn filename(dir: &str, index: usize) -> String {
+        format!("{}/job-{:05}.job", dir, index)
+    }
+
+    /// The job files in a directory, in order.
+    pub fn paths(dir: &str) -> io::Result<Vec<String>> {
+        let mut paths = read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|e| e == "job"))
+            .filter_map(|path| path.to_str().map(str::to_string))
+            .collect::<Vec<_>>();
+
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// The file a job's bounds were exported to, which has the same name as
+    /// the job but ends in .json, .csv or .txt.
+    pub fn result_path(path: &str) -> Option<String> {
+        let stem = path.strip_suffix(".job").unwrap_or(path);
+
+        ["json", "csv", "txt"].iter()
+            .map(|extension| format!("{}.{}", stem, extension))
+            .find(|result| Path::new(result).exists())
+    }
+
+    fn to_io(error: bincode::Error) -> io::Error {
+        io::Error::other(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,114 @@
+use super::*;
+use super::super::disk::Codec;
+use super::super::frontier::Frontier;
+use super::super::search::Search;
+
+use std::fs::{create_dir_all, remove_dir_all};
+
+type Subject = Job;
+
+fn run(job: Job, test_id: &'static str, max_bounds: usize) -> Bounds {
+    let scratch_dir = format!("/tmp/superpermutation-test/{}", test_id);
+    let frontier = Frontier::new(1.0, &scratch_dir, Codec::Raw, job.n).unwrap();
+
+    let mut search = Search::from_prefix(frontier, job.bounds, job.prefix, job.n);
+    search.run_until(|s| s.bounds().lower_bounds.len() > max_bounds);
+
+    search.bounds().clone()
+}
+
+fn global(n: usize, test_id: &'static str, max_bounds: usize) -> Vec<usize> {
+    let job = Job { n, prefix: Candidate::seed(n), bounds: Bounds::new(n) };
+    let mut lower_bounds = run(job, test_id, max_bounds).lower_bounds;
+
+    lower_bounds.truncate(max_bounds);
+    lower_bounds
+}
+
+mod split {
+    use super::*;
+
+    #[test]
+    fn it_makes_a_job_for_each_string_at_the_depth() {
+        assert_eq!(Subject::split(Bounds::new(4), 0, 4).len(), 1);
+        assert_eq!(Subject::split(Bounds::new(4), 1, 4).len(), 3);
+        assert_eq!(Subject::split(Bounds::new(4), 3, 4).len(), 27);
+    }
+
+    #[test]
+    fn it_starts_each_job_with_the_strings_that_led_to_its_prefix() {
+        let jobs = Subject::split(Bounds::new(4), 2, 4);
+
+        // The seed is "0123" and the first job is "012301", which sees three
+        // permutations without wasting a symbol.
+        assert_eq!(jobs[0].prefix.tail_of_string, vec![3, 0, 1]);
+        assert_eq!(jobs[0].bounds.lower_bounds, vec![4]);
+        assert_eq!(jobs[0].bounds.trusted, 1);
+    }
+}
+
+mod merge {
+    use super::*;
+
+    #[test]
+    fn it_finds_the_same_bounds_as_a_search_that_is_not_split() {
+        let expected = global(4, "split-1", 7);
+
+        for depth in 1..4 {
+            let parts = Subject::split(Bounds::new(4), depth, 4).into_iter()
+                .map(|job| run(job, "split-1", 7))
+                .collect::<Vec<_>>();
+
+            let merged = Bounds::merge(&parts, 4).unwrap();
+
+            assert_eq!(merged.lower_bounds, expected, "depth {}", depth);
+            assert_eq!(merged.trusted, 7);
+        }
+    }
+
+    #[test]
+    fn it_only_trusts_rows_that_every_job_has_finished() {
+        let max_bounds = 12;
+        let expected = global(5, "split-2", max_bounds);
+
+        let mut parts = Subject::split(Bounds::new(5), 2, 5).into_iter()
+            .map(|job| run(job, "split-2", max_bounds))
+            .collect::<Vec<_>>();
+
+        let merged = Bounds::merge(&parts, 5).unwrap();
+        assert_eq!(merged.lower_bounds[..max_bounds], expected[..]);
+
+        // The fourth job is still searching with four wasted symbols.
+        parts[3].lower_bounds.truncate(5);
+        parts[3].upper_bounds.truncate(5);
+        parts[3].upper_bounds[4] = parts[3].max;
+
+        let merged = Bounds::merge(&parts, 5).unwrap();
+        assert_eq!(merged.trusted, 4);
+        assert_eq!(merged.lower_bounds, expected[..5]);
+    }
+}
+
+mod save_and_load {
+    use super::*;
+
+    #[test]
+    fn it_writes_a_job_that_can_be_read_back() {
+        let dir = "/tmp/superpermutation-test/split-3";
+        let _ = remove_dir_all(dir);
+        create_dir_all(dir).unwrap();
+
+        for (i, job) in Subject::split(Bounds::new(4), 2, 4).iter().enumerate() {
+            job.save(&Subject::filename(dir, i)).unwrap();
+        }
+
+        let paths = Subject::paths(dir).unwrap();
+        let expected = Subject::split(Bounds::new(4), 2, 4);
+
+        assert_eq!(paths.len(), 9);
+        assert_eq!(paths[0], format!("{}/job-00000.job", dir));
+        assert_eq!(Subject::load(&paths[8]).unwrap(), expected[8]);
+
+        assert_eq!(Subject::result_path(&paths[0]), None);
+    }
+}
@@ -0,0 +1,95 @@
+use super::bounds::Bounds;
+use super::frontier::Stats;
+use leaps_and_bounds::Search;
+
+use std::time::{Duration, Instant};
+
+// Prints a line every so often so that a long phase of the search can be seen
+// to be making progress. The rate of expansions is since the last line, not
+// since the start, so that it shows when the search slows down. The memory the
+// frontier estimates it uses is shown next to what was measured, since the
+// limit is enforced on the measurement.
+
+pub struct Status {
+    interval: Duration,
+    last_printed: Instant,
+    last_expanded: u64,
+}
+
+impl Status {
+    pub fn new(interval_seconds: f64) -> Self {
+        Self {
+            interval: Duration::from_secs_f64(interval_seconds),
+            last_printed: Instant::now(),
+            last_expanded: 0,
+        }
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.last_printed.elapsed() >= self.interval
+    }
+
+    pub fn print(&mut self, search: &Search) {
+        let stats = search.frontier().stats();
+        println!("{}", self.line(&stats, search.bounds(), search.phase_elapsed(), Instant::now()));
+    }
+
+    fn line(&mut self, stats: &Stats, bounds: &Bounds, phase_time: Duration, now: Instant) -> String {
+        let seconds = now.duration_since(self.last_printed).as_secs_f64();
+        let expansions = stats.expanded.saturating_sub(self.last_expanded);
+        let rate = expansions as f64 / seconds.max(0.001);
+
+        self.last_printed = now;
+        self.last_expanded = stats.expanded;
+
+        let total = (stats.enabled + stats.disabled) as u64 + stats.on_disk;
+        let phase = bounds.lower_bounds.len() - 1;
+
+        format!(
+            "  [{} wasted symbols for {}] {} candidates: {} enabled, {} disabled, {} on disk | {:.0} expansions/s | {} written, {} read | {}",
+            phase, Self::format_duration(phase_time), total, stats.enabled, stats.disabled, stats.on_disk,
+            rate, Self::format_bytes(stats.bytes_written), Self::format_bytes(stats.bytes_read), Self::format_memory(stats),
+        )
+    }
+
+    fn format_memory(stats: &Stats) -> String {
+        let estimated = Self::format_bytes(stats.estimated_bytes);
+
+        match stats.resident_bytes {
+            None => format!("{} estimated", estimated),
+            Some(resident) => {
+                let difference = (resident as f64 / stats.estimated_bytes.max(1) as f64 - 1.) * 100.;
+                format!("{} estimated, {} measured ({:+.0}%)", estimated, Self::format_bytes(resident), difference)
+            },
+        }
+    }
+
+    fn format_duration(duration: Duration) -> String {
+        let seconds = duration.as_secs();
+
+        match seconds {
+            s if s < 60 => format!("{}s", s),
+            s if s < 3600 => format!("{}m{:02}s", s / 60, s % 60),
+            s => format!("{}h{:02}m", s / 3600, s % 3600 / 60),
+        }
+    }
+
+    fn format_bytes(bytes: u64) -> String {
+        let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+        let mut value = bytes as f64;
+        let mut unit = 0;
+
+        while value >= 1024. && unit < units.len() - 1 {
+            value /= 1024.;
+            unit += 1;
+        }
+
+        match unit {
+            0 => format!("{}B", bytes),
+            _ => format!("{:.1}{}", value, units[unit]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test;
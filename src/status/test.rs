@@ -0,0 +1,80 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use super::*;
+
+type Subject = Status;
+
+fn stats(expanded: u64) -> Stats {
+    Stats {
+        enabled: 100,
+        disabled: 20,
+        on_disk: 3000,
+        expanded,
+        bytes_written: 5 * 1024 * 1024,
+        bytes_read: 512,
+        estimated_bytes: 40 * 1024 * 1024,
+        resident_bytes: Some(50 * 1024 * 1024),
+    }
+}
+
+mod line {
+    use super::*;
+
+    #[test]
+    fn it_shows_the_phase_the_candidates_and_the_disk_usage() {
+        let mut subject = Subject::new(60.);
+        let mut bounds = Bounds::new(4);
+        bounds.update(1, 4);
+
+        let now = subject.last_printed + Duration::from_secs(75);
+        let line = subject.line(&stats(0), &bounds, Duration::from_secs(75), now);
+
+        assert_eq!(line, "  [1 wasted symbols for 1m15s] 3120 candidates: 100 enabled, 20 disabled, 3000 on disk | 0 expansions/s | 5.0MiB written, 512B read | 40.0MiB estimated, 50.0MiB measured (+25%)");
+    }
+
+    #[test]
+    fn it_only_shows_the_estimated_memory_if_it_cannot_be_measured() {
+        let mut subject = Subject::new(60.);
+        let bounds = Bounds::new(4);
+
+        let stats = Stats { resident_bytes: None, ..stats(0) };
+        let line = subject.line(&stats, &bounds, Duration::from_secs(1), subject.last_printed);
+
+        assert_eq!(line.ends_with("| 40.0MiB estimated"), true);
+    }
+
+    #[test]
+    fn it_measures_the_expansions_per_second_since_the_last_line() {
+        let mut subject = Subject::new(60.);
+        let bounds = Bounds::new(4);
+        let start = subject.last_printed;
+
+        subject.line(&stats(1000), &bounds, Duration::from_secs(10), start + Duration::from_secs(10));
+        let line = subject.line(&stats(1500), &bounds, Duration::from_secs(20), start + Duration::from_secs(20));
+
+        assert_eq!(line.contains("| 50 expansions/s |"), true);
+    }
+
+    #[test]
+    fn it_formats_the_time_since_the_phase_started() {
+        let mut subject = Subject::new(60.);
+        let bounds = Bounds::new(4);
+
+        let phase_time = Duration::from_secs(2 * 3600 + 5 * 60);
+        let now = subject.last_printed + Duration::from_secs(60);
+
+        assert_eq!(subject.line(&stats(0), &bounds, phase_time, now).contains("[0 wasted symbols for 2h05m]"), true);
+    }
+}
+
+mod format_bytes {
+    use super::*;
+
+    #[test]
+    fn it_uses_binary_units() {
+        assert_eq!(Subject::format_bytes(0), "0B");
+        assert_eq!(Subject::format_bytes(1023), "1023B");
+        assert_eq!(Subject::format_bytes(1536), "1.5KiB");
+        assert_eq!(Subject::format_bytes(3 * 1024 * 1024 * 1024), "3.0GiB");
+    }
+}
@@ -0,0 +1,107 @@
+use super::candidate::Candidate;
+
+use bit_set::BitSet;
+use lehmer::Lehmer;
+use std::collections::HashMap;
+
+// Two candidates that are the same up to renaming their symbols can be extended
+// in exactly the same ways, so they will go on to find the same bounds. This
+// puts candidates into a canonical form so that equivalent candidates become
+// identical and the Frontier can collapse them into one.
+//
+// Reversing the string is also a symmetry of superpermutations but it can't be
+// used here. Candidates are extended at the end of the string and the state we
+// keep (the tail and the permutations seen) says nothing about its start.
+
+/// Renames the symbols of candidates into a canonical form.
+pub struct Symmetry {
+    n: usize,
+    permutations: Vec<Vec<u8>>,
+    orders: Vec<Vec<Vec<u8>>>,
+    relabelled_ids: HashMap<Vec<u8>, Vec<u32>>,
+}
+
+impl Symmetry {
+    pub fn new(n: usize) -> Self {
+        let permutations = Self::all_permutations(n);
+        let orders = (0..n).map(Self::all_permutations).collect();
+
+        Self { n, permutations, orders, relabelled_ids: HashMap::new() }
+    }
+
+    /// The symbols in the tail are renamed 0, 1, 2, ... in the order they appear.
+    /// Every way of renaming the remaining symbols is then tried and the one that
+    /// gives the smallest set of permutations is chosen. Returns the relabelling
+    /// that was applied, which maps each old symbol to its new symbol.
+    pub fn canonicalize(&mut self, candidate: &mut Candidate) -> Vec<u8> {
+        let tail_len = candidate.tail_of_string.len();
+        let mut relabelling = vec![0; self.n];
+
+        for (i, &symbol) in candidate.tail_of_string.iter().enumerate() {
+            relabelling[symbol as usize] = i as u8;
+        }
+
+        let remaining: Vec<u8> = (0..self.n as u8)
+            .filter(|s| !candidate.tail_of_string.contains(s))
+            .collect();
+
+        let mut best: Option<(BitSet, Vec<u8>)> = None;
+
+        for i in 0..self.orders[remaining.len()].len() {
+            for (&symbol, &position) in remaining.iter().zip(self.orders[remaining.len()][i].iter()) {
+                relabelling[symbol as usize] = (tail_len + position as usize) as u8;
+            }
+
+            let permutations_seen = self.relabel(&candidate.permutations_seen, &relabelling);
+
+            let is_better = match &best {
+                None => true,
+                Some((b, _)) => permutations_seen.get_ref().blocks().lt(b.get_ref().blocks()),
+            };
+
+            if is_better {
+                best = Some((permutations_seen, relabelling.clone()));
+            }
+        }
+
+        let (permutations_seen, relabelling) = best.unwrap();
+
+        candidate.permutations_seen = permutations_seen;
+        candidate.tail_of_string = (0..tail_len as u8).collect();
+
+        relabelling
+    }
+
+    fn relabel(&mut self, permutations_seen: &BitSet, relabelling: &[u8]) -> BitSet {
+        if !self.relabelled_ids.contains_key(relabelling) {
+            let ids = self.permutations.iter().map(|permutation| {
+                let relabelled = permutation.iter().map(|&s| relabelling[s as usize]).collect();
+                Lehmer::from_permutation(relabelled).to_decimal() as u32
+            }).collect();
+
+            self.relabelled_ids.insert(relabelling.to_vec(), ids);
+        }
+
+        let ids = &self.relabelled_ids[relabelling];
+        let mut relabelled = BitSet::with_capacity(self.permutations.len());
+
+        for id in permutations_seen.iter() {
+            relabelled.insert(ids[id] as usize);
+        }
+
+        relabelled
+    }
+
+    fn all_permutations(n: usize) -> Vec<Vec<u8>> {
+        if n == 0 {
+            return vec![vec![]];
+        }
+
+        (0..=Lehmer::max_value(n))
+            .map(|id| Lehmer::from_decimal(id, n).to_permutation())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test;
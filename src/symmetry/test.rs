@@ -0,0 +1,152 @@
+use super::*;
+use super::super::bounds::Bounds;
+use super::super::disk::Codec;
+use super::super::frontier::Frontier;
+
+type Subject = Symmetry;
+
+const PATH: &str = "/tmp/superpermutation-test";
+
+fn candidate(string: &[u8], n: usize) -> Candidate {
+    string[n..].iter().fold(Candidate::seed(n), |c, &s| c.expand_one(s, false, n))
+}
+
+// Runs the same loop as the search until the given number of bounds are found
+// and returns the lower bounds.
+fn lower_bounds(n: usize, symmetry: bool, max_bounds: usize, test_id: &str) -> Vec<usize> {
+    let scratch_dir = format!("{}/{}", PATH, test_id);
+
+    let mut frontier = Frontier::new(1.0, &scratch_dir, Codec::Raw, n).unwrap();
+    let mut bounds = Bounds::new(n);
+    let mut subject = match symmetry {
+        true => Some(Subject::new(n)),
+        false => None,
+    };
+
+    if symmetry {
+        frontier.drop_duplicates();
+    }
+
+    frontier.add(Candidate::seed(n), n);
+
+    while let Some(mut wasted_symbols) = frontier.min_waste() {
+        wasted_symbols = frontier.unprune(wasted_symbols, &bounds.lower_bounds, &bounds.upper_bounds);
+
+        let candidate = frontier.next().unwrap();
+
+        if bounds.update(wasted_symbols, candidate.number_of_permutations()) {
+            let threshold = bounds.thresholds[wasted_symbols];
+            frontier.prune(wasted_symbols, threshold, true);
+        }
+
+        if bounds.found_for_superpermutation() || bounds.lower_bounds.len() > max_bounds {
+            break;
+        }
+
+        let upper_bound = bounds.upper(wasted_symbols);
+
+        for mut child in candidate.expand(upper_bound, n) {
+            if let Some(s) = subject.as_mut() {
+                s.canonicalize(&mut child);
+            }
+
+            frontier.add(child, n);
+        }
+    }
+
+    bounds.lower_bounds.truncate(max_bounds);
+    bounds.lower_bounds
+}
+
+mod canonicalize {
+    use super::*;
+
+    #[test]
+    fn it_renames_the_symbols_in_the_tail_in_the_order_they_appear() {
+        let mut subject = Subject::new(4);
+        let mut candidate = candidate(&[0, 1, 2, 3, 0, 2], 4);
+
+        let relabelling = subject.canonicalize(&mut candidate);
+
+        assert_eq!(candidate.tail_of_string, &[0, 1, 2]);
+        assert_eq!(relabelling[3], 0);
+        assert_eq!(relabelling[0], 1);
+        assert_eq!(relabelling[2], 2);
+        assert_eq!(relabelling[1], 3);
+    }
+
+    #[test]
+    fn it_keeps_the_same_number_of_permutations() {
+        let mut subject = Subject::new(4);
+        let mut candidate = candidate(&[0, 1, 2, 3, 0, 1, 3, 2], 4);
+        let permutations = candidate.number_of_permutations();
+
+        subject.canonicalize(&mut candidate);
+
+        assert_eq!(candidate.number_of_permutations(), permutations);
+    }
+
+    #[test]
+    fn it_makes_candidates_that_differ_by_a_renaming_identical() {
+        let mut subject = Subject::new(4);
+
+        let mut a = candidate(&[0, 1, 2, 3, 0, 2, 1], 4);
+        let renaming = [3, 2, 1, 0];
+
+        let mut b = Candidate {
+            permutations_seen: subject.relabel(&a.permutations_seen, &renaming),
+            tail_of_string: a.tail_of_string.iter().map(|&s| renaming[s as usize]).collect(),
+            wasted_symbols: a.wasted_symbols,
+            ancestry_id: 0.into(),
+        };
+
+        assert_ne!(a, b);
+
+        subject.canonicalize(&mut a);
+        subject.canonicalize(&mut b);
+
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn it_picks_the_same_form_whatever_order_the_remaining_symbols_are_in() {
+        let mut subject = Subject::new(5);
+
+        let mut a = candidate(&[0, 1, 2, 3, 4, 0, 1, 2], 5);
+        let mut b = candidate(&[0, 1, 2, 4, 3, 0, 1, 2], 5);
+
+        subject.canonicalize(&mut a);
+        subject.canonicalize(&mut b);
+
+        assert_eq!(a.tail_of_string, b.tail_of_string);
+        assert_eq!(a.permutations_seen, b.permutations_seen);
+    }
+}
+
+mod search {
+    use super::*;
+
+    #[test]
+    fn it_finds_the_same_bounds_for_three_symbols() {
+        let expected = lower_bounds(3, false, 10, "symmetry-1");
+        let actual = lower_bounds(3, true, 10, "symmetry-2");
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn it_finds_the_same_bounds_for_four_symbols() {
+        let expected = lower_bounds(4, false, 20, "symmetry-3");
+        let actual = lower_bounds(4, true, 20, "symmetry-4");
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn it_finds_the_same_bounds_for_five_symbols() {
+        let expected = lower_bounds(5, false, 26, "symmetry-5");
+        let actual = lower_bounds(5, true, 26, "symmetry-6");
+
+        assert_eq!(actual, expected);
+    }
+}
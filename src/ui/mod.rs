@@ -1,3 +1,10 @@
+use super::args::Options;
+use super::bounds::{Export, Format};
+use super::candidate::{MIN_SYMBOLS, MAX_SYMBOLS};
+use super::cluster::Address;
+use super::disk::{Codec, DEFAULT_ZLIB_LEVEL};
+use super::frontier::Priority;
+
 use std::io::{prelude::*, stdin, stdout};
 
 pub struct UI { }
@@ -75,24 +82,40 @@ impl UI {
         println!("> Ok, here we go! --->>>");
     }
 
+    pub fn ask_for_options() -> Options {
+        let n = Self::ask_for_n();
+        let memory = Self::ask_for_memory();
+        let codec = Self::ask_for_codec();
+        let verbose = Self::ask_for_verbose();
+
+        Options { n, memory, codec, verbose, ..Options::default() }
+    }
+
     pub fn ask_for_n() -> usize {
-        let input = Self::prompt("How many symbols should the string contain?", "5");
-        Self::parse_integer(&input)
+        Self::ask("How many symbols should the string contain?", "5", Self::parse_n)
     }
 
     pub fn ask_for_memory() -> f64 {
-        let input = Self::prompt("How many gigabytes of memory may this tool use?", "12");
-        Self::parse_float(&input)
+        Self::ask("How many gigabytes of memory may this tool use?", "12", Self::parse_memory)
     }
 
-    pub fn ask_for_gzip() -> bool {
-        let input = Self::prompt("Do you want to gzip scratch files to save space?", "no");
-        Self::parse_boolean(&input)
+    pub fn ask_for_codec() -> Codec {
+        Self::ask("How should scratch files be compressed? (none, zlib, zlib:<level> or bitset)", "none", Self::parse_codec)
     }
 
     pub fn ask_for_verbose() -> bool {
-        let input = Self::prompt("Do you want to print verbose output?", "no");
-        Self::parse_boolean(&input)
+        Self::ask("Do you want to print verbose output?", "no", Self::parse_boolean)
+    }
+
+    fn ask<T>(question: &'static str, default: &'static str, parse: fn(&str) -> Result<T, String>) -> T {
+        loop {
+            let input = Self::prompt(question, default);
+
+            match parse(&input) {
+                Ok(value) => return value,
+                Err(message) => println!("| {}", message),
+            }
+        }
     }
 
     fn prompt(question: &'static str, default: &'static str) -> String {
@@ -114,24 +137,166 @@ impl UI {
     }
 
     pub fn flush() {
-        stdout().flush().ok().expect("Failed to flush stdout.");
+        stdout().flush().expect("Failed to flush stdout.");
+    }
+
+    pub fn parse_n(input: &str) -> Result<usize, String> {
+        let n = Self::parse_integer(input)?;
+
+        if !(MIN_SYMBOLS..=MAX_SYMBOLS).contains(&n) {
+            return Err(format!("The number of symbols must be between {} and {}.", MIN_SYMBOLS, MAX_SYMBOLS));
+        }
+
+        Ok(n)
+    }
+
+    pub fn parse_memory(input: &str) -> Result<f64, String> {
+        let trimmed = input.trim();
+        let lowercase = trimmed.to_lowercase();
+
+        let not_memory = || format!("'{}' is not an amount of memory, e.g. 12G or 512M.", trimmed);
+
+        // A plain number is in gigabytes, but a bare B suffix means bytes.
+        let (number, bytes) = match lowercase.strip_suffix('b') {
+            Some(number) => (number, true),
+            None => (lowercase.as_str(), false),
+        };
+
+        let (number, binary) = match number.strip_suffix('i') {
+            Some(number) => (number, true),
+            None => (number, false),
+        };
+
+        let (number, scale) = match number.chars().last() {
+            Some('k') => (&number[..number.len() - 1], 1. / 1024. / 1024.),
+            Some('m') => (&number[..number.len() - 1], 1. / 1024.),
+            Some('g') => (&number[..number.len() - 1], 1.),
+            Some('t') => (&number[..number.len() - 1], 1024.),
+            _ if binary => return Err(not_memory()),
+            _ if bytes => (number, 1. / 1024. / 1024. / 1024.),
+            _ => (number, 1.),
+        };
+
+        let gigabytes = match Self::parse_float(number) {
+            Ok(float) => float * scale,
+            Err(_) => return Err(not_memory()),
+        };
+
+        if !gigabytes.is_finite() || gigabytes <= 0. {
+            return Err(format!("The amount of memory must be positive, but was '{}'.", trimmed));
+        }
+
+        Ok(gigabytes)
+    }
+
+    pub fn parse_minutes(input: &str) -> Result<f64, String> {
+        let minutes = Self::parse_float(input)?;
+
+        if !minutes.is_finite() || minutes <= 0. {
+            return Err(format!("The number of minutes must be positive, but was '{}'.", input.trim()));
+        }
+
+        Ok(minutes)
+    }
+
+    pub fn parse_status(input: &str) -> Result<Option<f64>, String> {
+        if let Ok(false) = Self::parse_boolean(input) {
+            return Ok(None);
+        }
+
+        let seconds = Self::parse_float(input)?;
+
+        if !seconds.is_finite() || seconds <= 0. {
+            return Err(format!("The number of seconds must be positive, but was '{}'.", input.trim()));
+        }
+
+        Ok(Some(seconds))
+    }
+
+    pub fn parse_count(input: &str) -> Result<usize, String> {
+        let count = Self::parse_integer(input)?;
+
+        if count == 0 {
+            return Err("The number must be at least 1.".to_string());
+        }
+
+        Ok(count)
     }
 
-    fn parse_integer(input: &str) -> usize {
-        input.trim().parse().expect("Failed to parse integer.")
+    pub fn parse_codec(input: &str) -> Result<Codec, String> {
+        let trimmed = input.trim();
+        let lowercase = trimmed.to_lowercase();
+
+        let level = lowercase.strip_prefix("zlib:").map(|level| level.parse::<u32>());
+
+        match (lowercase.as_str(), level) {
+            ("none", _) => Ok(Codec::Raw),
+            ("zlib", _) => Ok(Codec::Zlib(DEFAULT_ZLIB_LEVEL)),
+            ("bitset", _) => Ok(Codec::Bitset),
+            (_, Some(Ok(level))) if level <= 9 => Ok(Codec::Zlib(level)),
+            _ => Err(format!("'{}' is not a codec, e.g. none, zlib, zlib:9 or bitset.", trimmed)),
+        }
     }
 
-    fn parse_float(input: &str) -> f64 {
-        input.trim().parse().expect("Failed to parse float.")
+    pub fn parse_priority(input: &str) -> Result<Priority, String> {
+        let trimmed = input.trim();
+        let lowercase = trimmed.to_lowercase();
+
+        let seed = lowercase.strip_prefix("random:").map(|seed| seed.parse::<u64>());
+
+        match (lowercase.as_str(), seed) {
+            ("fifo", _) => Ok(Priority::Fifo),
+            ("tail", _) => Ok(Priority::LongestTail),
+            ("reachable", _) => Ok(Priority::Reachable),
+            ("random", _) => Ok(Priority::Random(0)),
+            (_, Some(Ok(seed))) => Ok(Priority::Random(seed)),
+            _ => Err(format!("'{}' is not a priority, e.g. fifo, tail, reachable or random:<seed>.", trimmed)),
+        }
     }
 
-    fn parse_boolean(input: &str) -> bool {
+    pub fn parse_export(input: &str) -> Result<Export, String> {
+        let path = Self::parse_path(input)?;
+
+        match Format::for_path(&path) {
+            Some(format) => Ok(Export { path, format }),
+            None => Err(format!("'{}' must end in .json, .csv or .txt for an OEIS b-file.", path)),
+        }
+    }
+
+    /// Known bounds are read in any of the export formats.
+    pub fn parse_known_bounds(input: &str) -> Result<String, String> {
+        Ok(Self::parse_export(input)?.path)
+    }
+
+    pub fn parse_address(input: &str) -> Result<Address, String> {
+        Address::parse(&Self::parse_path(input)?)
+    }
+
+    pub fn parse_path(input: &str) -> Result<String, String> {
+        match input.trim() {
+            "" => Err("The path must not be empty.".to_string()),
+            path => Ok(path.to_string()),
+        }
+    }
+
+    pub fn parse_boolean(input: &str) -> Result<bool, String> {
         match input.to_lowercase().trim() {
-            "y" => true,
-            "n" => false,
-            "yes" => true,
-            "no" => false,
-            _ => panic!("Failed to parse boolean."),
+            "y" => Ok(true),
+            "n" => Ok(false),
+            "yes" => Ok(true),
+            "no" => Ok(false),
+            _ => Err(format!("'{}' is not yes or no.", input.trim())),
         }
     }
+
+    fn parse_integer(input: &str) -> Result<usize, String> {
+        input.trim().parse().map_err(|_| format!("'{}' is not a whole number.", input.trim()))
+    }
+
+    fn parse_float(input: &str) -> Result<f64, String> {
+        input.trim().parse().map_err(|_| format!("'{}' is not a number.", input.trim()))
+    }
 }
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,174 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use super::*;
+
+type Subject = UI;
+
+mod parse_n {
+    use super::*;
+
+    #[test]
+    fn it_parses_the_number_of_symbols() {
+        assert_eq!(Subject::parse_n("5\n"), Ok(5));
+        assert_eq!(Subject::parse_n(" 2 "), Ok(2));
+    }
+
+    #[test]
+    fn it_returns_an_error_if_the_number_is_out_of_range_or_invalid() {
+        assert_eq!(Subject::parse_n("1").is_err(), true);
+        assert_eq!(Subject::parse_n("11").is_err(), true);
+        assert_eq!(Subject::parse_n("five").is_err(), true);
+    }
+}
+
+mod parse_memory {
+    use super::*;
+
+    #[test]
+    fn it_treats_a_plain_number_as_gigabytes() {
+        assert_eq!(Subject::parse_memory("12\n"), Ok(12.));
+        assert_eq!(Subject::parse_memory("0.5"), Ok(0.5));
+    }
+
+    #[test]
+    fn it_supports_binary_unit_suffixes() {
+        assert_eq!(Subject::parse_memory("12G"), Ok(12.));
+        assert_eq!(Subject::parse_memory("12GiB"), Ok(12.));
+        assert_eq!(Subject::parse_memory("512m"), Ok(0.5));
+        assert_eq!(Subject::parse_memory("2T"), Ok(2048.));
+        assert_eq!(Subject::parse_memory("1048576K"), Ok(1.));
+        assert_eq!(Subject::parse_memory("12kib"), Ok(12. / 1024. / 1024.));
+        assert_eq!(Subject::parse_memory("12 KB"), Ok(12. / 1024. / 1024.));
+    }
+
+    #[test]
+    fn it_treats_a_bare_b_suffix_as_bytes() {
+        assert_eq!(Subject::parse_memory("1073741824b"), Ok(1.));
+        assert_eq!(Subject::parse_memory("536870912 B"), Ok(0.5));
+    }
+
+    #[test]
+    fn it_returns_an_error_for_invalid_or_non_positive_amounts() {
+        assert_eq!(Subject::parse_memory("G").is_err(), true);
+        assert_eq!(Subject::parse_memory("lots").is_err(), true);
+        assert_eq!(Subject::parse_memory("0G").is_err(), true);
+        assert_eq!(Subject::parse_memory("-1").is_err(), true);
+        assert_eq!(Subject::parse_memory("12ib").is_err(), true);
+        assert_eq!(Subject::parse_memory("12bb").is_err(), true);
+    }
+}
+
+mod parse_status {
+    use super::*;
+
+    #[test]
+    fn it_parses_the_number_of_seconds_between_status_lines() {
+        assert_eq!(Subject::parse_status("30\n"), Ok(Some(30.)));
+        assert_eq!(Subject::parse_status("0.5"), Ok(Some(0.5)));
+    }
+
+    #[test]
+    fn it_turns_the_status_line_off_for_no() {
+        assert_eq!(Subject::parse_status("no"), Ok(None));
+    }
+
+    #[test]
+    fn it_returns_an_error_for_zero_or_invalid_numbers() {
+        assert_eq!(Subject::parse_status("0").is_err(), true);
+        assert_eq!(Subject::parse_status("-1").is_err(), true);
+        assert_eq!(Subject::parse_status("often").is_err(), true);
+    }
+}
+
+mod parse_count {
+    use super::*;
+
+    #[test]
+    fn it_parses_a_positive_whole_number() {
+        assert_eq!(Subject::parse_count("16\n"), Ok(16));
+    }
+
+    #[test]
+    fn it_returns_an_error_for_zero_or_invalid_numbers() {
+        assert_eq!(Subject::parse_count("0").is_err(), true);
+        assert_eq!(Subject::parse_count("-1").is_err(), true);
+        assert_eq!(Subject::parse_count("1.5").is_err(), true);
+    }
+}
+
+mod parse_priority {
+    use super::*;
+
+    #[test]
+    fn it_parses_the_name_of_the_priority() {
+        assert_eq!(Subject::parse_priority("fifo\n"), Ok(Priority::Fifo));
+        assert_eq!(Subject::parse_priority("Tail"), Ok(Priority::LongestTail));
+        assert_eq!(Subject::parse_priority("reachable"), Ok(Priority::Reachable));
+        assert_eq!(Subject::parse_priority("random"), Ok(Priority::Random(0)));
+        assert_eq!(Subject::parse_priority("random:42"), Ok(Priority::Random(42)));
+    }
+
+    #[test]
+    fn it_returns_an_error_for_unknown_priorities_or_seeds() {
+        assert_eq!(Subject::parse_priority("lifo").is_err(), true);
+        assert_eq!(Subject::parse_priority("random:").is_err(), true);
+        assert_eq!(Subject::parse_priority("random:-1").is_err(), true);
+    }
+}
+
+mod parse_codec {
+    use super::*;
+
+    #[test]
+    fn it_parses_the_name_of_the_codec() {
+        assert_eq!(Subject::parse_codec("none\n"), Ok(Codec::Raw));
+        assert_eq!(Subject::parse_codec("Zlib"), Ok(Codec::Zlib(6)));
+        assert_eq!(Subject::parse_codec("bitset"), Ok(Codec::Bitset));
+    }
+
+    #[test]
+    fn it_parses_a_compression_level_for_zlib() {
+        assert_eq!(Subject::parse_codec("zlib:0"), Ok(Codec::Zlib(0)));
+        assert_eq!(Subject::parse_codec("zlib:9"), Ok(Codec::Zlib(9)));
+    }
+
+    #[test]
+    fn it_returns_an_error_for_unknown_codecs_or_levels() {
+        assert_eq!(Subject::parse_codec("gzip").is_err(), true);
+        assert_eq!(Subject::parse_codec("zlib:10").is_err(), true);
+        assert_eq!(Subject::parse_codec("zlib:").is_err(), true);
+    }
+}
+
+mod parse_export {
+    use super::*;
+
+    #[test]
+    fn it_chooses_the_format_from_the_extension() {
+        let export = |path: &str, format| Ok(Export { path: path.to_string(), format });
+
+        assert_eq!(Subject::parse_export("bounds.json\n"), export("bounds.json", Format::Json));
+        assert_eq!(Subject::parse_export("/tmp/bounds.csv"), export("/tmp/bounds.csv", Format::Csv));
+        assert_eq!(Subject::parse_export("b123456.txt"), export("b123456.txt", Format::BFile));
+    }
+
+    #[test]
+    fn it_returns_an_error_for_other_extensions() {
+        assert_eq!(Subject::parse_export("bounds.xml").is_err(), true);
+        assert_eq!(Subject::parse_export("bounds").is_err(), true);
+        assert_eq!(Subject::parse_export("").is_err(), true);
+    }
+}
+
+mod parse_boolean {
+    use super::*;
+
+    #[test]
+    fn it_parses_yes_and_no() {
+        assert_eq!(Subject::parse_boolean("Y"), Ok(true));
+        assert_eq!(Subject::parse_boolean("yes\n"), Ok(true));
+        assert_eq!(Subject::parse_boolean("n"), Ok(false));
+        assert_eq!(Subject::parse_boolean("NO"), Ok(false));
+        assert_eq!(Subject::parse_boolean("maybe").is_err(), true);
+    }
+}
@@ -0,0 +1,123 @@
+use super::candidate::{Candidate, MIN_SYMBOLS, MAX_SYMBOLS};
+
+/// What each symbol of a verified string did.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mark {
+    Prefix,
+    NewPermutation,
+    SingleWaste,
+    DoubleWaste,
+}
+
+/// The permutations and wasted symbols in a string.
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    pub n: usize,
+    pub length: usize,
+    pub permutations: usize,
+    pub wasted_symbols: usize,
+    pub marks: Vec<Mark>,
+}
+
+/// Checks strings, such as witnesses, with the same rules the search uses.
+pub struct Verifier { }
+
+impl Verifier {
+    /// Symbols are written 1..9 then a..z, as in witness strings. If the string
+    /// contains a 0 then it is assumed the symbols start from zero instead.
+    pub fn parse(input: &str) -> Result<Vec<u8>, String> {
+        let mut symbols = vec![];
+
+        for c in input.chars().filter(|c| !c.is_whitespace()) {
+            match c.to_digit(36) {
+                Some(digit) => symbols.push(digit as u8),
+                None => return Err(format!("'{}' is not a valid symbol.", c)),
+            }
+        }
+
+        if !symbols.contains(&0) {
+            for symbol in symbols.iter_mut() {
+                *symbol -= 1;
+            }
+        }
+
+        Ok(symbols)
+    }
+
+    /// The string is relabelled so that it starts from the seed permutation and
+    /// each symbol is then added with the same rules the search uses to expand
+    /// candidates. This means a double waste is marked on the symbol that is
+    /// wasted, but it also accounts for the symbol that is forced to follow it.
+    pub fn verify(symbols: &[u8], n: Option<usize>) -> Result<Report, String> {
+        let n = n.unwrap_or_else(|| symbols.iter().max().map_or(0, |&s| s as usize + 1));
+        let relabelled = Self::relabel(symbols, n)?;
+
+        let mut candidate = Candidate::seed(n);
+        let mut marks = vec![Mark::Prefix; n - 1];
+
+        marks.push(Mark::NewPermutation);
+
+        for &symbol in &relabelled[n..] {
+            let waste_before = candidate.wasted_symbols;
+            candidate = candidate.expand_one(symbol, false, n);
+
+            let mark = match candidate.wasted_symbols - waste_before {
+                0 => Mark::NewPermutation,
+                1 => Mark::SingleWaste,
+                _ => Mark::DoubleWaste,
+            };
+
+            marks.push(mark);
+        }
+
+        Ok(Report {
+            n,
+            length: symbols.len(),
+            permutations: candidate.number_of_permutations(),
+            wasted_symbols: candidate.wasted_symbols as usize,
+            marks,
+        })
+    }
+
+    fn relabel(symbols: &[u8], n: usize) -> Result<Vec<u8>, String> {
+        if !(MIN_SYMBOLS..=MAX_SYMBOLS).contains(&n) {
+            return Err(format!("The number of symbols must be between {} and {}.", MIN_SYMBOLS, MAX_SYMBOLS));
+        }
+
+        let not_a_permutation = || format!("The string must start with a permutation of {} symbols.", n);
+        let first_permutation = symbols.get(..n).ok_or_else(not_a_permutation)?;
+        let mut labels = vec![None; n];
+
+        for (i, &symbol) in first_permutation.iter().enumerate() {
+            match labels.get_mut(symbol as usize) {
+                Some(label @ None) => *label = Some(i as u8),
+                _ => return Err(not_a_permutation()),
+            }
+        }
+
+        symbols.iter().map(|&symbol| match labels.get(symbol as usize) {
+            Some(&Some(label)) => Ok(label),
+            _ => Err(format!("The string contains more than {} symbols.", n)),
+        }).collect()
+    }
+}
+
+impl Report {
+    pub fn actual_waste(&self) -> usize {
+        self.length - (self.n - 1) - self.permutations
+    }
+
+    /// A line with the mark for each symbol, to print under the string: + adds
+    /// a new permutation, 1 wastes a symbol and 2 wastes it and the next one.
+    pub fn marks(&self) -> String {
+        self.marks.iter().map(|mark| match mark {
+            Mark::Prefix => ' ',
+            Mark::NewPermutation => '+',
+            Mark::SingleWaste => '1',
+            Mark::DoubleWaste => '2',
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod test;
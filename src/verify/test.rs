@@ -0,0 +1,95 @@
+use super::*;
+
+type Subject = Verifier;
+
+fn verify(string: &str) -> Result<Report, String> {
+    Subject::verify(&Subject::parse(string)?, None)
+}
+
+mod parse {
+    use super::*;
+
+    #[test]
+    fn it_parses_symbols_starting_from_one() {
+        assert_eq!(Subject::parse("1234 1"), Ok(vec![0, 1, 2, 3, 0]));
+    }
+
+    #[test]
+    fn it_parses_symbols_starting_from_zero_if_the_string_contains_a_zero() {
+        assert_eq!(Subject::parse("01234"), Ok(vec![0, 1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn it_returns_an_error_for_invalid_symbols() {
+        assert_eq!(Subject::parse("12-3"), Err("'-' is not a valid symbol.".to_string()));
+    }
+}
+
+mod verify {
+    use super::*;
+
+    #[test]
+    fn it_counts_the_permutations_and_wasted_symbols_of_a_superpermutation() {
+        let report = verify("123412314231243121342132413214321").unwrap();
+
+        assert_eq!(report.n, 4);
+        assert_eq!(report.length, 33);
+        assert_eq!(report.permutations, 24);
+        assert_eq!(report.wasted_symbols, 6);
+        assert_eq!(report.actual_waste(), 6);
+    }
+
+    #[test]
+    fn it_marks_each_symbol_with_the_rule_that_applied() {
+        let report = verify("1234123421").unwrap();
+
+        use self::Mark::*;
+        assert_eq!(report.marks, &[
+            Prefix, Prefix, Prefix, NewPermutation,
+            NewPermutation, NewPermutation, NewPermutation,
+            DoubleWaste, SingleWaste, NewPermutation,
+        ]);
+    }
+
+    #[test]
+    fn it_applies_the_double_waste_rule_when_the_next_permutation_has_been_seen() {
+        let report = verify("012340123412").unwrap();
+
+        assert_eq!(report.marks[9], Mark::DoubleWaste);
+        assert_eq!(report.permutations, 5);
+    }
+
+    #[test]
+    fn it_relabels_strings_that_do_not_start_with_the_first_permutation() {
+        let relabelled = verify("432143241").unwrap();
+        let original = verify("123412314").unwrap();
+
+        assert_eq!(relabelled, original);
+    }
+
+    #[test]
+    fn it_returns_an_error_if_the_string_does_not_start_with_a_permutation() {
+        let expected = "The string must start with a permutation of 3 symbols.";
+
+        assert_eq!(verify("1213"), Err(expected.to_string()));
+        assert_eq!(verify("1223"), Err(expected.to_string()));
+    }
+
+    #[test]
+    fn it_returns_an_error_if_the_string_has_more_symbols_than_n() {
+        let symbols = Subject::parse("1231234").unwrap();
+        let expected = "The string contains more than 3 symbols.";
+
+        assert_eq!(Subject::verify(&symbols, Some(3)), Err(expected.to_string()));
+    }
+}
+
+mod marks {
+    use super::*;
+
+    #[test]
+    fn it_writes_a_character_under_each_symbol() {
+        let report = verify("1234123421").unwrap();
+        assert_eq!(report.marks(), "   ++++21+");
+    }
+}
@@ -0,0 +1,178 @@
+use super::candidate::Candidate;
+
+use bincode::{serialize_into, deserialize_from};
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, SeekFrom, prelude::*};
+use std::char;
+
+// Each candidate is given an ID for a node in an append-only file of parent
+// links. A node stores its parent's ID and the symbol that was appended to the
+// parent's string, so the full string can be rebuilt by walking back to the
+// seed (which has an ID of zero). Only the best node for each number of wasted
+// symbols is kept in memory.
+//
+// If candidates are relabelled into a canonical form, each node also stores the
+// relabelling that was applied to it. These are composed when walking back so
+// that the whole string is rebuilt using the symbols of the final candidate.
+//
+// Errors say which file they're for, since there are two.
+
+/// Records how each candidate was built so that a string achieving each bound
+/// can be written out.
+pub struct Witnesses {
+    filename: String,
+    writer: BufWriter<File>,
+    reader: File,
+    output_path: String,
+    output: File,
+    nodes: u64,
+    best: Vec<(usize, u64)>,
+    relabelled: bool,
+    n: usize,
+}
+
+impl Witnesses {
+    pub fn new(scratch_dir: &str, output_path: &str, relabelled: bool, n: usize) -> io::Result<Self> {
+        let filename = Self::filename(scratch_dir);
+        let file = File::create(&filename).map_err(in_file(&filename))?;
+
+        Self::with_file(file, filename, output_path, 0, vec![], relabelled, n)
+    }
+
+    /// Nodes that were recorded after the checkpoint was saved are truncated
+    /// from the ancestry file so that new nodes are given the same IDs again.
+    pub fn resume<R: Read>(scratch_dir: &str, output_path: &str, relabelled: bool, n: usize, reader: &mut R) -> bincode::Result<Self> {
+        let (nodes, best): (u64, Vec<(usize, u64)>) = deserialize_from(reader)?;
+
+        let filename = Self::filename(scratch_dir);
+        let file = OpenOptions::new().write(true).open(&filename).map_err(in_file(&filename))?;
+
+        let mut witnesses = Self::with_file(file, filename, output_path, nodes, best, relabelled, n)?;
+        let len = nodes * witnesses.node_size();
+
+        witnesses.writer.get_ref().set_len(len).map_err(in_file(&witnesses.filename))?;
+        witnesses.writer.seek(SeekFrom::End(0)).map_err(in_file(&witnesses.filename))?;
+
+        Ok(witnesses)
+    }
+
+    pub fn save<W: Write>(&mut self, writer: &mut W) -> bincode::Result<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()?;
+
+        serialize_into(writer, &(self.nodes, &self.best))
+    }
+
+    fn filename(scratch_dir: &str) -> String {
+        format!("{}/ancestry.dat", scratch_dir)
+    }
+
+    fn with_file(file: File, filename: String, output_path: &str, nodes: u64, best: Vec<(usize, u64)>, relabelled: bool, n: usize) -> io::Result<Self> {
+        let reader = File::open(&filename).map_err(in_file(&filename))?;
+
+        let output = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(output_path)
+            .map_err(in_file(output_path))?;
+
+        let writer = BufWriter::new(file);
+        let output_path = output_path.to_string();
+
+        Ok(Self { filename, writer, reader, output_path, output, nodes, best, relabelled, n })
+    }
+
+    /// If the candidate was relabelled, this must be called afterwards so that
+    /// the symbol that is stored uses the same labels as the candidate.
+    pub fn record(&mut self, candidate: &mut Candidate, relabelling: Option<&[u8]>) -> io::Result<()> {
+        let parent = u64::from(candidate.ancestry_id);
+        let symbol = *candidate.tail_of_string.last().unwrap();
+
+        self.writer.write_all(&parent.to_le_bytes()).map_err(in_file(&self.filename))?;
+        self.writer.write_all(&[symbol]).map_err(in_file(&self.filename))?;
+
+        if self.relabelled {
+            let relabelling = relabelling.expect("Expected the candidate to have been relabelled");
+            self.writer.write_all(relabelling).map_err(in_file(&self.filename))?;
+        }
+
+        self.nodes += 1;
+        candidate.ancestry_id = self.nodes.into();
+
+        Ok(())
+    }
+
+    pub fn improve(&mut self, wasted_symbols: usize, permutations: usize, ancestry_id: u64) {
+        if self.best.len() <= wasted_symbols {
+            let previous = self.best.last().cloned().unwrap_or((0, 0));
+            self.best.resize(wasted_symbols + 1, previous);
+        }
+
+        if self.best[wasted_symbols].0 < permutations {
+            self.best[wasted_symbols] = (permutations, ancestry_id);
+        }
+    }
+
+    /// Appends the string for the best bound with this many wasted symbols to
+    /// the output file and returns it, or None if there isn't one.
+    pub fn publish(&mut self, wasted_symbols: usize) -> io::Result<Option<String>> {
+        let (permutations, ancestry_id) = match self.best.get(wasted_symbols) {
+            Some(&best) => best,
+            None => return Ok(None),
+        };
+
+        let string = Self::to_string(&self.rebuild(ancestry_id)?);
+
+        writeln!(self.output, "{}\t{}\t{}", wasted_symbols, permutations, string)
+            .map_err(in_file(&self.output_path))?;
+
+        Ok(Some(string))
+    }
+
+    pub fn rebuild(&mut self, mut ancestry_id: u64) -> io::Result<Vec<u8>> {
+        self.writer.flush().map_err(in_file(&self.filename))?;
+
+        let node_size = self.node_size();
+
+        let mut symbols = vec![];
+        let mut node = vec![0; node_size as usize];
+        let mut relabelling: Vec<u8> = (0..self.n as u8).collect();
+
+        while ancestry_id != 0 {
+            self.reader.seek(SeekFrom::Start((ancestry_id - 1) * node_size)).map_err(in_file(&self.filename))?;
+            self.reader.read_exact(&mut node).map_err(in_file(&self.filename))?;
+
+            let mut parent = [0; 8];
+            parent.copy_from_slice(&node[..8]);
+
+            symbols.push(relabelling[node[8] as usize]);
+
+            if self.relabelled {
+                relabelling = node[9..].iter().map(|&s| relabelling[s as usize]).collect();
+            }
+
+            ancestry_id = u64::from_le_bytes(parent);
+        }
+
+        Ok(relabelling.into_iter().chain(symbols.into_iter().rev()).collect())
+    }
+
+    fn node_size(&self) -> u64 {
+        match self.relabelled {
+            true => 9 + self.n as u64,
+            false => 9,
+        }
+    }
+
+    pub fn to_string(symbols: &[u8]) -> String {
+        symbols.iter().map(|&s| char::from_digit(s as u32 + 1, 36).unwrap()).collect()
+    }
+}
+
+fn in_file(filename: &str) -> impl Fn(io::Error) -> io::Error + '_ {
+    move |e| io::Error::new(e.kind(), format!("{}: {}", filename, e))
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,168 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use super::*;
+use super::super::symmetry::Symmetry;
+use super::super::verify::Verifier;
+
+use std::fs::{create_dir_all, read_to_string, remove_file};
+use std::os::unix::fs::symlink;
+
+type Subject = Witnesses;
+
+const N: usize = 5;
+const PATH: &str = "/tmp/superpermutation-test";
+
+fn subject(test_id: &'static str) -> Subject {
+    let path = format!("{}/{}", PATH, test_id);
+    create_dir_all(&path).unwrap();
+
+    Subject::new(&path, &format!("{}/witnesses.txt", path), false, N).unwrap()
+}
+
+fn expand_last(candidate: Candidate, subject: &mut Subject) -> Candidate {
+    let mut child = candidate.expand(usize::MAX, N).last().unwrap();
+    subject.record(&mut child, None).unwrap();
+
+    child
+}
+
+mod record {
+    use super::*;
+
+    #[test]
+    fn it_gives_each_candidate_a_new_ancestry_id() {
+        let mut subject = subject("witness-1");
+        let seed = Candidate::seed(N);
+
+        let mut children: Vec<_> = seed.expand(usize::MAX, N).collect();
+
+        for child in children.iter_mut() {
+            subject.record(child, None).unwrap();
+        }
+
+        let ids: Vec<u64> = children.iter().map(|c| c.ancestry_id.into()).collect();
+        assert_eq!(ids, &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn it_returns_an_error_if_the_ancestry_file_cannot_be_written() {
+        let path = format!("{}/witness-6", PATH);
+        let filename = format!("{}/ancestry.dat", path);
+
+        create_dir_all(&path).unwrap();
+        let _ = remove_file(&filename);
+        symlink("/dev/full", &filename).unwrap();
+
+        let mut subject = Subject::new(&path, &format!("{}/witnesses.txt", path), false, N).unwrap();
+        let seed = Candidate::seed(N);
+
+        // The writer is buffered, so the error comes once the buffer is full.
+        let error = (0..10_000).find_map(|_| subject.record(&mut seed.clone(), None).err()).unwrap();
+        assert_eq!(error.to_string().starts_with(&filename), true);
+    }
+}
+
+mod new {
+    use super::*;
+
+    #[test]
+    fn it_returns_an_error_if_the_output_file_cannot_be_opened() {
+        let path = format!("{}/witness-7", PATH);
+        create_dir_all(&path).unwrap();
+
+        let output_path = format!("{}/missing/witnesses.txt", path);
+        let error = Subject::new(&path, &output_path, false, N).err().unwrap();
+
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert_eq!(error.to_string().starts_with(&output_path), true);
+    }
+}
+
+mod rebuild {
+    use super::*;
+
+    #[test]
+    fn it_rebuilds_the_full_string_from_the_seed() {
+        let mut subject = subject("witness-2");
+
+        let seed = Candidate::seed(N);
+        assert_eq!(subject.rebuild(seed.ancestry_id.into()).unwrap(), &[0, 1, 2, 3, 4]);
+
+        let depth_1 = expand_last(seed, &mut subject);
+        let depth_2 = expand_last(depth_1, &mut subject);
+
+        assert_eq!(subject.rebuild(depth_2.ancestry_id.into()).unwrap(), &[0, 1, 2, 3, 4, 3, 4]);
+    }
+
+    #[test]
+    fn it_undoes_relabelling_when_rebuilding_the_string() {
+        let path = format!("{}/witness-5", PATH);
+        create_dir_all(&path).unwrap();
+
+        let mut subject = Subject::new(&path, &format!("{}/witnesses.txt", path), true, N).unwrap();
+        let mut symmetry = Symmetry::new(N);
+
+        let mut candidate = Candidate::seed(N);
+
+        for &symbol in &[3, 1, 0] {
+            candidate = candidate.expand_one(symbol, false, N);
+
+            let relabelling = symmetry.canonicalize(&mut candidate);
+            subject.record(&mut candidate, Some(&relabelling)).unwrap();
+        }
+
+        let string = subject.rebuild(candidate.ancestry_id.into()).unwrap();
+
+        assert_eq!(string.len(), 8);
+        assert_eq!(string.ends_with(&candidate.tail_of_string), true);
+
+        let report = Verifier::verify(&string, Some(N)).unwrap();
+        assert_eq!(report.wasted_symbols, candidate.wasted_symbols as usize);
+        assert_eq!(report.permutations, candidate.number_of_permutations());
+    }
+}
+
+mod improve {
+    use super::*;
+
+    #[test]
+    fn it_keeps_the_candidate_with_the_most_permutations_for_each_index() {
+        let mut subject = subject("witness-3");
+
+        subject.improve(0, 5, 1);
+        subject.improve(0, 3, 2);
+        subject.improve(2, 7, 3);
+
+        assert_eq!(subject.best, &[(5, 1), (5, 1), (7, 3)]);
+    }
+}
+
+mod publish {
+    use super::*;
+
+    #[test]
+    fn it_appends_the_string_for_the_bound_to_the_output_file() {
+        let mut subject = subject("witness-4");
+
+        let seed = Candidate::seed(N);
+        let mut child = seed.expand(usize::MAX, N).next().unwrap();
+        subject.record(&mut child, None).unwrap();
+
+        assert_eq!(subject.publish(0).unwrap(), None);
+
+        subject.improve(0, 2, child.ancestry_id.into());
+        assert_eq!(subject.publish(0).unwrap(), Some("123451".to_string()));
+
+        let contents = read_to_string(format!("{}/witness-4/witnesses.txt", PATH)).unwrap();
+        assert_eq!(contents.ends_with("0\t2\t123451\n"), true);
+    }
+}
+
+mod to_string {
+    use super::*;
+
+    #[test]
+    fn it_formats_symbols_starting_from_one() {
+        assert_eq!(Subject::to_string(&[0, 1, 2, 9]), "123a");
+    }
+}
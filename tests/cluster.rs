@@ -0,0 +1,75 @@
+extern crate leaps_and_bounds;
+
+use leaps_and_bounds::{Address, Bounds, Coordinator, Job};
+
+use std::fs::create_dir_all;
+use std::io::{BufRead, BufReader, Lines};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::thread::sleep;
+use std::time::Duration;
+
+// These start workers with the binary, in processes of their own, rather than
+// on threads as the unit tests do.
+
+fn socket(test_id: &str) -> Address {
+    create_dir_all("/tmp/superpermutation-test").unwrap();
+    Address::Unix(format!("/tmp/superpermutation-test/{}.sock", test_id))
+}
+
+fn spawn_worker(address: &Address, test_id: &str, index: usize) -> (Child, Lines<BufReader<ChildStdout>>) {
+    let scratch_dir = format!("/tmp/superpermutation-test/{}/worker-{}", test_id, index);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_leaps-and-bounds"))
+        .args(["--connect", &address.to_string(), "--scratch-dir", &scratch_dir, "--memory", "1G", "--status", "no"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    (child, lines)
+}
+
+fn next_job(lines: &mut Lines<BufReader<ChildStdout>>) -> String {
+    lines.map(|line| line.unwrap()).find(|line| line.starts_with("Searching job")).unwrap()
+}
+
+#[test]
+fn workers_in_separate_processes_find_the_shortest_superpermutation() {
+    let address = socket("cluster-process-1");
+    let subject = Coordinator::bind(&address, Job::split(Bounds::new(4), 2, 4), 4).unwrap();
+
+    let workers = (0..2).map(|i| spawn_worker(&address, "cluster-process-1", i)).collect::<Vec<_>>();
+    let bounds = subject.run(|_| (), |_| ()).unwrap();
+
+    assert_eq!(bounds.lower_bounds, vec![4, 8, 12, 14, 18, 20, 24]);
+
+    // The workers' output is read to the end so that they can finish printing.
+    for (mut worker, lines) in workers {
+        lines.for_each(drop);
+        assert!(worker.wait().unwrap().success());
+    }
+}
+
+// A search of five symbols takes minutes, so the first job is still running
+// when its worker is killed.
+#[test]
+fn a_job_is_handed_out_again_when_its_worker_is_killed_halfway_through() {
+    let address = socket("cluster-process-2");
+    let _subject = Coordinator::bind(&address, Job::split(Bounds::new(5), 1, 5), 5).unwrap();
+
+    let (mut first, mut lines) = spawn_worker(&address, "cluster-process-2", 0);
+    assert_eq!(next_job(&mut lines), "Searching job 0.");
+
+    // Long enough for the worker to send its bounds at least once.
+    sleep(Duration::from_millis(1500));
+    assert_eq!(first.try_wait().unwrap(), None);
+
+    first.kill().unwrap();
+    first.wait().unwrap();
+
+    let (mut second, mut lines) = spawn_worker(&address, "cluster-process-2", 1);
+    assert_eq!(next_job(&mut lines), "Searching job 0.");
+
+    second.kill().unwrap();
+    second.wait().unwrap();
+}